@@ -1,4 +1,5 @@
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     tonic_build::compile_protos("protos/category.proto")?;
+    tonic_build::compile_protos("protos/payment.proto")?;
     Ok(())
-}
\ No newline at end of file
+}
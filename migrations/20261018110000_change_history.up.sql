@@ -0,0 +1,14 @@
+CREATE TABLE IF NOT EXISTS change_history (
+    id UUID PRIMARY KEY NOT NULL DEFAULT (uuid_generate_v4()),
+    entity_type VARCHAR(32) NOT NULL,
+    entity_id UUID NOT NULL,
+    action VARCHAR(16) NOT NULL,
+    actor_id UUID,
+    before JSONB,
+    after JSONB,
+    diff JSONB NOT NULL DEFAULT '{}'::jsonb,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    CONSTRAINT fk_actor FOREIGN KEY(actor_id) REFERENCES users(id) ON DELETE SET NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_change_history_entity ON change_history (entity_type, entity_id, created_at);
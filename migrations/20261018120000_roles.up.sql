@@ -0,0 +1,18 @@
+CREATE TABLE IF NOT EXISTS roles (
+    id UUID PRIMARY KEY NOT NULL DEFAULT (uuid_generate_v4()),
+    name VARCHAR(255) NOT NULL UNIQUE,
+    description VARCHAR(510) NOT NULL,
+    admin BOOLEAN NOT NULL DEFAULT FALSE,
+    super_admin BOOLEAN NOT NULL DEFAULT FALSE,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT NOW(),
+    updated_at TIMESTAMP WITH TIME ZONE DEFAULT NOW()
+);
+
+INSERT INTO roles (id, name, description, admin, super_admin) VALUES
+    ('00000000-0000-0000-0000-000000000001', 'user', 'Regular user', FALSE, FALSE),
+    ('00000000-0000-0000-0000-000000000002', 'admin', 'Administrator', TRUE, FALSE),
+    ('00000000-0000-0000-0000-000000000003', 'super_admin', 'Super administrator', TRUE, TRUE)
+ON CONFLICT DO NOTHING;
+
+ALTER TABLE users ADD COLUMN IF NOT EXISTS role_id UUID NOT NULL DEFAULT '00000000-0000-0000-0000-000000000001';
+ALTER TABLE users ADD CONSTRAINT fk_role FOREIGN KEY(role_id) REFERENCES roles(id);
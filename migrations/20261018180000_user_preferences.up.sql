@@ -0,0 +1,11 @@
+CREATE TABLE IF NOT EXISTS user_preferences (
+    user_id UUID PRIMARY KEY NOT NULL,
+    timezone VARCHAR(64) NOT NULL DEFAULT 'UTC',
+    locale VARCHAR(16) NOT NULL DEFAULT 'en-US',
+    currency CHAR(3) NOT NULL DEFAULT 'USD',
+    week_start VARCHAR(16) NOT NULL DEFAULT 'monday',
+    date_format VARCHAR(16) NOT NULL DEFAULT 'YYYY-MM-DD',
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT NOW(),
+    updated_at TIMESTAMP WITH TIME ZONE DEFAULT NOW(),
+    CONSTRAINT fk_user FOREIGN KEY(user_id) REFERENCES users(id) ON DELETE CASCADE
+);
@@ -0,0 +1,13 @@
+-- Data subject erasure may scrub personal fields from audit entries, but only
+-- inside a transaction that opted in with SET LOCAL app.gdpr_erasure = 'on'.
+-- Entries still can't be deleted.
+CREATE OR REPLACE FUNCTION audit_logs_append_only() RETURNS TRIGGER AS $$
+BEGIN
+    IF TG_OP = 'UPDATE' AND current_setting('app.gdpr_erasure', true) = 'on'
+        AND NEW.id = OLD.id AND NEW.event = OLD.event AND NEW.success = OLD.success
+        AND NEW.created_at = OLD.created_at THEN
+        RETURN NEW;
+    END IF;
+    RAISE EXCEPTION 'audit_logs is append-only';
+END;
+$$ LANGUAGE plpgsql;
@@ -0,0 +1,11 @@
+DROP INDEX IF EXISTS idx_payments_household_id;
+ALTER TABLE payments DROP CONSTRAINT IF EXISTS fk_household;
+ALTER TABLE payments DROP COLUMN IF EXISTS household_id;
+
+DROP INDEX IF EXISTS idx_categories_household_id;
+ALTER TABLE categories DROP CONSTRAINT IF EXISTS fk_household;
+ALTER TABLE categories DROP COLUMN IF EXISTS household_id;
+
+DROP TABLE IF EXISTS household_invitations;
+DROP TABLE IF EXISTS household_members;
+DROP TABLE IF EXISTS households;
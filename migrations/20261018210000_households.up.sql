@@ -0,0 +1,44 @@
+CREATE TABLE IF NOT EXISTS households (
+    id UUID PRIMARY KEY NOT NULL DEFAULT (uuid_generate_v4()),
+    name VARCHAR(255) NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT NOW(),
+    updated_at TIMESTAMP WITH TIME ZONE DEFAULT NOW()
+);
+
+CREATE TABLE IF NOT EXISTS household_members (
+    household_id UUID NOT NULL,
+    user_id UUID NOT NULL,
+    role VARCHAR(16) NOT NULL CHECK (role IN ('owner', 'editor', 'viewer')),
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (household_id, user_id),
+    CONSTRAINT fk_household FOREIGN KEY(household_id) REFERENCES households(id) ON DELETE CASCADE,
+    CONSTRAINT fk_user FOREIGN KEY(user_id) REFERENCES users(id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_household_members_user_id ON household_members (user_id);
+
+CREATE TABLE IF NOT EXISTS household_invitations (
+    id UUID PRIMARY KEY NOT NULL DEFAULT (uuid_generate_v4()),
+    household_id UUID NOT NULL,
+    email TEXT NOT NULL,
+    role VARCHAR(16) NOT NULL CHECK (role IN ('editor', 'viewer')),
+    token_hash TEXT NOT NULL UNIQUE,
+    invited_by UUID,
+    expires_at TIMESTAMP WITH TIME ZONE NOT NULL,
+    accepted_at TIMESTAMP WITH TIME ZONE,
+    revoked_at TIMESTAMP WITH TIME ZONE,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    CONSTRAINT fk_household FOREIGN KEY(household_id) REFERENCES households(id) ON DELETE CASCADE,
+    CONSTRAINT fk_invited_by FOREIGN KEY(invited_by) REFERENCES users(id) ON DELETE SET NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_household_invitations_household_id ON household_invitations (household_id, created_at);
+
+-- Rows without a household make up their creator's personal ledger.
+ALTER TABLE categories ADD COLUMN IF NOT EXISTS household_id UUID;
+ALTER TABLE categories ADD CONSTRAINT fk_household FOREIGN KEY(household_id) REFERENCES households(id);
+CREATE INDEX IF NOT EXISTS idx_categories_household_id ON categories (household_id);
+
+ALTER TABLE payments ADD COLUMN IF NOT EXISTS household_id UUID;
+ALTER TABLE payments ADD CONSTRAINT fk_household FOREIGN KEY(household_id) REFERENCES households(id);
+CREATE INDEX IF NOT EXISTS idx_payments_household_id ON payments (household_id);
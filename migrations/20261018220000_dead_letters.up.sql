@@ -0,0 +1,16 @@
+-- Messages drained from payments-dead-letter.queue so they can be inspected,
+-- corrected and replayed.
+CREATE TABLE IF NOT EXISTS dead_letters (
+    id UUID PRIMARY KEY NOT NULL DEFAULT (uuid_generate_v4()),
+    message_id TEXT,
+    routing_key TEXT NOT NULL DEFAULT '',
+    payload TEXT NOT NULL,
+    headers JSONB NOT NULL DEFAULT '{}'::jsonb,
+    reason TEXT,
+    retry_count INTEGER NOT NULL DEFAULT 0,
+    replayed_at TIMESTAMP WITH TIME ZONE,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_dead_letters_created_at ON dead_letters (created_at);
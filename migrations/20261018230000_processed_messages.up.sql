@@ -0,0 +1,8 @@
+-- Keys of consumed messages, written in the same transaction as their
+-- effect so redeliveries can be recognised and skipped.
+CREATE TABLE IF NOT EXISTS processed_messages (
+    message_key TEXT PRIMARY KEY NOT NULL,
+    payment_id UUID,
+    processed_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    CONSTRAINT fk_payment FOREIGN KEY(payment_id) REFERENCES payments(id) ON DELETE SET NULL
+);
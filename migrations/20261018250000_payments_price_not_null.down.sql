@@ -0,0 +1 @@
+ALTER TABLE payments ALTER COLUMN price DROP NOT NULL;
@@ -0,0 +1,3 @@
+-- Every writer already sends a price; older rows without one count as free.
+UPDATE payments SET price = 0 WHERE price IS NULL;
+ALTER TABLE payments ALTER COLUMN price SET NOT NULL;
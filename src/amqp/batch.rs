@@ -0,0 +1,221 @@
+use std::{
+    collections::{HashMap, HashSet},
+    error::Error,
+    time::Duration,
+};
+
+use futures::StreamExt;
+use lapin::{
+    message::Delivery,
+    options::{BasicAckOptions, BasicCancelOptions},
+    Channel, Consumer,
+};
+use sqlx::{Pool, Postgres};
+use tokio::{sync::watch, time::Instant};
+use uuid::Uuid;
+
+use crate::{
+    amqp::{
+        command::{self, Command, CommandError},
+        config::get_config,
+        payment::{handle_delivery, message_key, settle_failure},
+    },
+    payment::{
+        schema::CreatePaymentSchema,
+        service::{self, PaymentError},
+    },
+};
+
+/// Collects deliveries until `batch_size` arrive or `batch_max_wait_ms` has
+/// passed since the first one, then processes them together. On shutdown the
+/// consumer is cancelled and the pending batch is processed before returning.
+pub async fn listen(
+    db: &Pool<Postgres>,
+    channel: &Channel,
+    mut consumer: Consumer,
+    shutdown: &mut watch::Receiver<bool>,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let config = get_config();
+    let max_wait = Duration::from_millis(config.batch_max_wait_ms);
+
+    let mut batch = Vec::with_capacity(config.batch_size);
+    let mut deadline: Option<Instant> = None;
+
+    loop {
+        let timeout = async move {
+            match deadline {
+                Some(deadline) => tokio::time::sleep_until(deadline).await,
+                None => std::future::pending().await,
+            }
+        };
+
+        tokio::select! {
+            next = consumer.next() => match next {
+                Some(Ok(delivery)) => {
+                    if batch.is_empty() {
+                        deadline = Some(Instant::now() + max_wait);
+                    }
+                    batch.push(delivery);
+                    if batch.len() < config.batch_size {
+                        continue;
+                    }
+                }
+                Some(Err(err)) => return Err(err.into()),
+                None => return Err("payments.queue consumer was cancelled by the broker".into()),
+            },
+            _ = timeout => {}
+            _ = shutdown.changed() => break,
+        }
+
+        process(db, channel, std::mem::take(&mut batch)).await;
+        deadline = None;
+    }
+
+    log::info!("Draining payments.queue batch consumer");
+    channel.basic_cancel("consumer", BasicCancelOptions::default()).await?;
+    process(db, channel, batch).await;
+
+    Ok(())
+}
+
+/// Inserts the batch's `payment.create` commands with one multi-row insert
+/// and settles every delivery on its own outcome: acked when inserted or
+/// already processed, dead-lettered when refused. Anything else (other
+/// commands, malformed messages, repeated keys) and the whole batch when the
+/// insert fails go through the regular one-by-one path, which isolates the
+/// offending message.
+async fn process(db: &Pool<Postgres>, channel: &Channel, deliveries: Vec<Delivery>) {
+    if deliveries.is_empty() {
+        return;
+    }
+
+    let mut creates: Vec<(Delivery, CreatePaymentSchema, String)> = Vec::new();
+    let mut one_by_one: Vec<Delivery> = Vec::new();
+    let mut keys = HashSet::new();
+
+    for delivery in deliveries {
+        let message = match command::parse(&delivery.data) {
+            Ok(message) => message,
+            Err(_) => {
+                one_by_one.push(delivery);
+                continue;
+            }
+        };
+
+        let key = match message_key(&delivery, &message) {
+            Some(key) if !keys.contains(&key) => key,
+            _ => {
+                one_by_one.push(delivery);
+                continue;
+            }
+        };
+
+        match message.command {
+            Command::CreatePayment(p) => {
+                keys.insert(key.clone());
+                let input = CreatePaymentSchema {
+                    name: p.name,
+                    description: p.description.unwrap_or_default(),
+                    price: p.price,
+                    userId: p.userId,
+                    categoryId: p.categoryId,
+                };
+                creates.push((delivery, input, key));
+            }
+            _ => one_by_one.push(delivery),
+        }
+    }
+
+    // Duplicates are acknowledged before authorization, as they are one by one.
+    let batch_keys: Vec<String> = creates.iter().map(|(_, _, key)| key.clone()).collect();
+    let processed = match service::processed_keys(db, &batch_keys).await {
+        Ok(processed) => processed,
+        Err(err) => {
+            log::warn!("Failed to check batch for duplicates, processing one by one: {}", err);
+            one_by_one.extend(creates.into_iter().map(|(delivery, _, _)| delivery));
+            return settle_one_by_one(db, channel, one_by_one).await;
+        }
+    };
+
+    // Partner feeds reuse a handful of users and categories, so each pair is
+    // only checked once.
+    let mut authorized: HashMap<(Uuid, Uuid), Result<(), String>> = HashMap::new();
+    let mut accepted: Vec<(Delivery, CreatePaymentSchema, String)> = Vec::new();
+
+    for (delivery, input, key) in creates {
+        if processed.contains(&key) {
+            ack(&delivery).await;
+            continue;
+        }
+
+        let pair = (input.userId, input.categoryId);
+        let outcome = match authorized.get(&pair) {
+            Some(outcome) => outcome.clone(),
+            None => match service::authorize_category(db, input.userId, input.categoryId).await {
+                Err(PaymentError::Database(_)) => {
+                    one_by_one.push(delivery);
+                    continue;
+                }
+                outcome => {
+                    let outcome = outcome.map_err(|err| err.to_string());
+                    authorized.insert(pair, outcome.clone());
+                    outcome
+                }
+            },
+        };
+
+        match outcome {
+            Ok(()) => accepted.push((delivery, input, key)),
+            Err(reason) => {
+                if let Err(err) = settle_failure(channel, &delivery, &CommandError::Rejected(reason)).await {
+                    log::error!("Failed to settle payment message: {}", err);
+                }
+            }
+        }
+    }
+
+    if !accepted.is_empty() {
+        let (deliveries, inputs): (Vec<Delivery>, Vec<(CreatePaymentSchema, String)>) = accepted
+            .into_iter()
+            .map(|(delivery, input, key)| (delivery, (input, key)))
+            .unzip();
+
+        match service::create_payments_once(db, &inputs).await {
+            Ok(payments) => {
+                let inserted = payments.iter().filter(|payment| payment.is_some()).count();
+                log::info!(
+                    "Created {} payments in one batch, skipped {} already processed",
+                    inserted,
+                    payments.len() - inserted
+                );
+                for delivery in &deliveries {
+                    ack(delivery).await;
+                }
+            }
+            Err(err) => {
+                log::warn!(
+                    "Batch insert of {} payments failed, processing one by one: {}",
+                    deliveries.len(),
+                    err
+                );
+                one_by_one.extend(deliveries);
+            }
+        }
+    }
+
+    settle_one_by_one(db, channel, one_by_one).await;
+}
+
+async fn settle_one_by_one(db: &Pool<Postgres>, channel: &Channel, deliveries: Vec<Delivery>) {
+    for delivery in deliveries {
+        if let Err(err) = handle_delivery(db, channel, delivery).await {
+            log::error!("Failed to settle payment message: {}", err);
+        }
+    }
+}
+
+async fn ack(delivery: &Delivery) {
+    if let Err(err) = delivery.ack(BasicAckOptions::default()).await {
+        log::error!("Failed to ack payment message: {}", err);
+    }
+}
@@ -0,0 +1,544 @@
+use std::fmt;
+
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use sqlx::{Pool, Postgres, Transaction};
+use uuid::Uuid;
+
+use crate::{
+    amqp::schema::{
+        CreateCategoryV1, CreatePaymentV1, DeleteCategoryV1, DeletePaymentV1, Envelope,
+        PaymentMessage, UpdateCategoryV1, UpdatePaymentV1,
+    },
+    category::{
+        model::CategoryModel,
+        schema::{CreateCategorySchema, UpdateCategorySchema},
+        service as category_service,
+    },
+    history::recorder::Entity,
+    household::access,
+    payment::{
+        model::PaymentModel,
+        schema::{CreatePaymentSchema, UpdatePaymentSchema},
+        service::{self as payment_service, PaymentError},
+    },
+};
+
+pub const PAYMENT_CREATE: &str = "payment.create";
+pub const PAYMENT_UPDATE: &str = "payment.update";
+pub const PAYMENT_DELETE: &str = "payment.delete";
+pub const CATEGORY_CREATE: &str = "category.create";
+pub const CATEGORY_UPDATE: &str = "category.update";
+pub const CATEGORY_DELETE: &str = "category.delete";
+
+const NAME_MAX_LENGTH: usize = 255;
+const DESCRIPTION_MAX_LENGTH: usize = 510;
+
+#[derive(Debug)]
+pub enum Command {
+    CreatePayment(CreatePaymentV1),
+    UpdatePayment(UpdatePaymentV1),
+    DeletePayment(DeletePaymentV1),
+    CreateCategory(CreateCategoryV1),
+    UpdateCategory(UpdateCategoryV1),
+    DeleteCategory(DeleteCategoryV1),
+}
+
+impl Command {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Command::CreatePayment(_) => PAYMENT_CREATE,
+            Command::UpdatePayment(_) => PAYMENT_UPDATE,
+            Command::DeletePayment(_) => PAYMENT_DELETE,
+            Command::CreateCategory(_) => CATEGORY_CREATE,
+            Command::UpdateCategory(_) => CATEGORY_UPDATE,
+            Command::DeleteCategory(_) => CATEGORY_DELETE,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Message {
+    pub command: Command,
+    pub correlation_id: Option<String>,
+    pub idempotency_key: Option<String>,
+}
+
+/// What a command changed, or `None` from [`execute`] for a duplicate.
+#[derive(Debug)]
+pub enum Applied {
+    Payment(PaymentModel),
+    Category(CategoryModel),
+}
+
+impl Applied {
+    pub fn id(&self) -> Uuid {
+        match self {
+            Applied::Payment(payment) => payment.id,
+            Applied::Category(category) => category.id,
+        }
+    }
+}
+
+/// Why a command was not applied. Only `Database` failures are worth
+/// retrying, and only transient ones; the others fail the same way every time.
+#[derive(Debug)]
+pub enum CommandError {
+    Rejected(String),
+    Forbidden(String),
+    NotFound(String),
+    Database(sqlx::Error),
+}
+
+impl CommandError {
+    /// Constraint violations (SQLSTATE class 23, e.g. a taken description or
+    /// a missing foreign key) come from the message itself and won't clear up
+    /// on a retry.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            CommandError::Database(sqlx::Error::Database(db_err)) => {
+                !db_err.code().is_some_and(|code| code.starts_with("23"))
+            }
+            CommandError::Database(_) => true,
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for CommandError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CommandError::Rejected(message)
+            | CommandError::Forbidden(message)
+            | CommandError::NotFound(message) => write!(f, "{}", message),
+            CommandError::Database(err) => write!(f, "Error: {:?}", err),
+        }
+    }
+}
+
+impl std::error::Error for CommandError {}
+
+impl From<sqlx::Error> for CommandError {
+    fn from(err: sqlx::Error) -> Self {
+        CommandError::Database(err)
+    }
+}
+
+impl From<PaymentError> for CommandError {
+    fn from(err: PaymentError) -> Self {
+        match err {
+            PaymentError::Invalid(message) => CommandError::Rejected(message),
+            PaymentError::Forbidden(message) => CommandError::Forbidden(message),
+            PaymentError::NotFound(message) => CommandError::NotFound(message),
+            PaymentError::Database(err) => CommandError::Database(err),
+        }
+    }
+}
+
+/// Parses and validates a message body. Bodies without a `type` field are
+/// read as a legacy [`PaymentMessage`].
+pub fn parse(data: &[u8]) -> Result<Message, CommandError> {
+    let body: Value = serde_json::from_slice(data)
+        .map_err(|err| CommandError::Rejected(format!("Message is not valid JSON: {}", err)))?;
+
+    if body.get("type").is_none() {
+        return parse_legacy(body);
+    }
+
+    let envelope: Envelope = serde_json::from_value(body)
+        .map_err(|err| CommandError::Rejected(format!("Invalid message envelope: {}", err)))?;
+
+    let command = match (envelope.kind.as_str(), envelope.version) {
+        (PAYMENT_CREATE, 1) => Command::CreatePayment(payload(&envelope)?),
+        (PAYMENT_UPDATE, 1) => Command::UpdatePayment(payload(&envelope)?),
+        (PAYMENT_DELETE, 1) => Command::DeletePayment(payload(&envelope)?),
+        (CATEGORY_CREATE, 1) => Command::CreateCategory(payload(&envelope)?),
+        (CATEGORY_UPDATE, 1) => Command::UpdateCategory(payload(&envelope)?),
+        (CATEGORY_DELETE, 1) => Command::DeleteCategory(payload(&envelope)?),
+        (
+            PAYMENT_CREATE | PAYMENT_UPDATE | PAYMENT_DELETE | CATEGORY_CREATE | CATEGORY_UPDATE
+            | CATEGORY_DELETE,
+            version,
+        ) => {
+            return Err(CommandError::Rejected(format!(
+                "Unsupported version {} of {}, expected 1",
+                version, envelope.kind
+            )))
+        }
+        (kind, _) => return Err(CommandError::Rejected(format!("Unknown message type `{}`", kind))),
+    };
+
+    validate(&command).map_err(|reason| {
+        CommandError::Rejected(format!("Invalid {} payload: {}", command.kind(), reason))
+    })?;
+
+    Ok(Message {
+        command,
+        correlation_id: envelope.correlationId,
+        idempotency_key: envelope.idempotencyKey,
+    })
+}
+
+fn parse_legacy(body: Value) -> Result<Message, CommandError> {
+    let message: PaymentMessage = serde_json::from_value(body)
+        .map_err(|err| CommandError::Rejected(format!("Invalid payment message: {}", err)))?;
+
+    let command = Command::CreatePayment(CreatePaymentV1 {
+        name: message.name,
+        description: message.description,
+        price: message.price,
+        userId: message.userId,
+        categoryId: message.categoryId,
+    });
+
+    validate(&command).map_err(|reason| {
+        CommandError::Rejected(format!("Invalid {} payload: {}", command.kind(), reason))
+    })?;
+
+    Ok(Message {
+        command,
+        correlation_id: None,
+        idempotency_key: message.idempotencyKey,
+    })
+}
+
+pub(crate) fn payload<T: DeserializeOwned>(envelope: &Envelope) -> Result<T, CommandError> {
+    T::deserialize(&envelope.payload).map_err(|err| {
+        CommandError::Rejected(format!(
+            "Invalid {} v{} payload: {}",
+            envelope.kind, envelope.version, err
+        ))
+    })
+}
+
+fn validate(command: &Command) -> Result<(), String> {
+    match command {
+        Command::CreatePayment(p) => {
+            check_text("name", &p.name, NAME_MAX_LENGTH)?;
+            check_description(p.description.as_deref())?;
+            check_price(p.price)
+        }
+        Command::UpdatePayment(p) => {
+            check_text("name", &p.name, NAME_MAX_LENGTH)?;
+            check_description(p.description.as_deref())?;
+            check_price(p.price)
+        }
+        Command::CreateCategory(p) => {
+            check_text("name", &p.name, NAME_MAX_LENGTH)?;
+            check_description(Some(&p.description))
+        }
+        Command::UpdateCategory(p) => {
+            check_text("name", &p.name, NAME_MAX_LENGTH)?;
+            check_description(Some(&p.description))
+        }
+        Command::DeletePayment(_) | Command::DeleteCategory(_) => Ok(()),
+    }
+}
+
+fn check_text(field: &str, value: &str, max_length: usize) -> Result<(), String> {
+    if value.trim().is_empty() {
+        return Err(format!("`{}` must not be empty", field));
+    }
+    check_length(field, value, max_length)
+}
+
+fn check_length(field: &str, value: &str, max_length: usize) -> Result<(), String> {
+    if value.chars().count() > max_length {
+        return Err(format!("`{}` must be at most {} characters", field, max_length));
+    }
+    Ok(())
+}
+
+fn check_description(description: Option<&str>) -> Result<(), String> {
+    description.map_or(Ok(()), |d| check_length("description", d, DESCRIPTION_MAX_LENGTH))
+}
+
+fn check_price(price: f64) -> Result<(), String> {
+    if !price.is_finite() {
+        return Err("`price` must be a finite number".to_string());
+    }
+    Ok(())
+}
+
+/// Applies a command at most once per `message_key`. The key is claimed in
+/// the same transaction as the change, so a redelivered message returns
+/// `None` instead of applying it twice.
+pub async fn execute(
+    db: &Pool<Postgres>,
+    command: Command,
+    message_key: &str,
+) -> Result<Option<Applied>, CommandError> {
+    // Checked up front so a duplicate is acknowledged even if it would no
+    // longer pass authorization, e.g. because its target was deleted since.
+    if payment_service::is_processed(db, message_key).await? {
+        return Ok(None);
+    }
+
+    match command {
+        Command::CreatePayment(p) => {
+            let input = CreatePaymentSchema {
+                name: p.name,
+                description: p.description.unwrap_or_default(),
+                price: p.price,
+                userId: p.userId,
+                categoryId: p.categoryId,
+            };
+            let payment = payment_service::create_payment_once(db, &input, message_key).await?;
+            Ok(payment.map(Applied::Payment))
+        }
+        Command::UpdatePayment(p) => {
+            require_write(db, Entity::Payment, p.id, p.userId).await?;
+            let input = UpdatePaymentSchema {
+                name: p.name,
+                description: p.description.unwrap_or_default(),
+                price: p.price,
+            };
+
+            let mut tx = db.begin().await?;
+            let payment = payment_service::update(&mut tx, p.id, &input, p.userId)
+                .await?
+                .ok_or_else(|| not_found(Entity::Payment, p.id))?;
+            let applied = commit_once(tx, message_key, Some(payment.id)).await?;
+            Ok(applied.then_some(Applied::Payment(payment)))
+        }
+        Command::DeletePayment(p) => {
+            require_write(db, Entity::Payment, p.id, p.userId).await?;
+
+            let mut tx = db.begin().await?;
+            let payment = payment_service::set_deleted(&mut tx, p.id, true, p.userId)
+                .await?
+                .ok_or_else(|| not_found(Entity::Payment, p.id))?;
+            let applied = commit_once(tx, message_key, Some(payment.id)).await?;
+            Ok(applied.then_some(Applied::Payment(payment)))
+        }
+        Command::CreateCategory(p) => {
+            if let Some(household_id) = p.householdId {
+                match access::member_role(db, household_id, p.userId).await? {
+                    Some(role) if role.can_write() => {}
+                    Some(_) => {
+                        return Err(CommandError::Forbidden(
+                            "Viewers can't change this household's ledger".to_string(),
+                        ))
+                    }
+                    None => {
+                        return Err(CommandError::NotFound(format!(
+                            "Household with ID: {} not found",
+                            household_id
+                        )))
+                    }
+                }
+            }
+            let input = CreateCategorySchema {
+                name: p.name,
+                description: p.description,
+                userId: p.userId,
+                householdId: p.householdId,
+            };
+
+            let mut tx = db.begin().await?;
+            let category = category_service::insert(&mut tx, &input, p.userId).await?;
+            let applied = commit_once(tx, message_key, None).await?;
+            Ok(applied.then_some(Applied::Category(category)))
+        }
+        Command::UpdateCategory(p) => {
+            require_write(db, Entity::Category, p.id, p.userId).await?;
+            let input = UpdateCategorySchema {
+                name: p.name,
+                description: p.description,
+            };
+
+            let mut tx = db.begin().await?;
+            let category = category_service::update(&mut tx, p.id, &input, p.userId)
+                .await?
+                .ok_or_else(|| not_found(Entity::Category, p.id))?;
+            let applied = commit_once(tx, message_key, None).await?;
+            Ok(applied.then_some(Applied::Category(category)))
+        }
+        Command::DeleteCategory(p) => {
+            require_write(db, Entity::Category, p.id, p.userId).await?;
+
+            let mut tx = db.begin().await?;
+            let category = category_service::set_deleted(&mut tx, p.id, true, p.userId)
+                .await?
+                .ok_or_else(|| not_found(Entity::Category, p.id))?;
+            let applied = commit_once(tx, message_key, None).await?;
+            Ok(applied.then_some(Applied::Category(category)))
+        }
+    }
+}
+
+async fn require_write(
+    db: &Pool<Postgres>,
+    entity: Entity,
+    entity_id: Uuid,
+    user_id: Uuid,
+) -> Result<(), CommandError> {
+    match access::entity_role(db, entity, entity_id, user_id).await? {
+        Some(role) if role.can_write() => Ok(()),
+        Some(_) => Err(CommandError::Forbidden(
+            "Viewers can't change this household's ledger".to_string(),
+        )),
+        None => Err(not_found(entity, entity_id)),
+    }
+}
+
+fn not_found(entity: Entity, entity_id: Uuid) -> CommandError {
+    let name = match entity {
+        Entity::Category => "Category",
+        Entity::Payment => "Payment",
+    };
+    CommandError::NotFound(format!("{} with ID: {} not found", name, entity_id))
+}
+
+/// Claims the message key and commits, or rolls back if a concurrent
+/// delivery of the same message got there first.
+async fn commit_once(
+    mut tx: Transaction<'_, Postgres>,
+    message_key: &str,
+    payment_id: Option<Uuid>,
+) -> Result<bool, CommandError> {
+    if !payment_service::claim_message(&mut tx, message_key, payment_id).await? {
+        tx.rollback().await?;
+        return Ok(false);
+    }
+
+    tx.commit().await?;
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn create_payment(name: &str, description: Option<String>, price: f64) -> Command {
+        Command::CreatePayment(CreatePaymentV1 {
+            name: name.to_string(),
+            description,
+            price,
+            userId: Uuid::nil(),
+            categoryId: Uuid::nil(),
+        })
+    }
+
+    fn rejection(result: Result<Message, CommandError>) -> String {
+        match result {
+            Err(CommandError::Rejected(message)) => message,
+            other => panic!("expected a rejection, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn parses_envelopes_and_legacy_bodies() {
+        let payment = json!({
+            "name": "Coffee",
+            "description": "Morning",
+            "price": 3.5,
+            "userId": Uuid::nil(),
+            "categoryId": Uuid::nil(),
+        });
+        let mut legacy = payment.clone();
+        legacy["idempotencyKey"] = json!("legacy-key");
+
+        let cases = [
+            (
+                json!({"type": PAYMENT_CREATE, "version": 1, "idempotencyKey": "key", "payload": payment}),
+                PAYMENT_CREATE,
+                Some("key"),
+            ),
+            (
+                json!({"type": CATEGORY_DELETE, "version": 1, "payload": {"id": Uuid::nil(), "userId": Uuid::nil()}}),
+                CATEGORY_DELETE,
+                None,
+            ),
+            (legacy, PAYMENT_CREATE, Some("legacy-key")),
+        ];
+
+        for (body, kind, idempotency_key) in cases {
+            let message = parse(body.to_string().as_bytes()).unwrap();
+            assert_eq!(message.command.kind(), kind, "{}", body);
+            assert_eq!(message.idempotency_key.as_deref(), idempotency_key, "{}", body);
+        }
+    }
+
+    #[test]
+    fn rejects_malformed_messages() {
+        let delete = json!({"id": Uuid::nil(), "userId": Uuid::nil()});
+
+        let cases = [
+            (b"not json".to_vec(), "Message is not valid JSON"),
+            (
+                json!({"type": "payment.refund", "version": 1, "payload": delete}).to_string().into_bytes(),
+                "Unknown message type `payment.refund`",
+            ),
+            (
+                json!({"type": PAYMENT_DELETE, "version": 2, "payload": delete}).to_string().into_bytes(),
+                "Unsupported version 2 of payment.delete, expected 1",
+            ),
+            (
+                json!({"type": PAYMENT_DELETE, "version": 1, "payload": {"id": Uuid::nil()}}).to_string().into_bytes(),
+                "Invalid payment.delete v1 payload",
+            ),
+            (
+                json!({"name": "Coffee", "price": 3.5}).to_string().into_bytes(),
+                "Invalid payment message",
+            ),
+            (
+                json!({"name": " ", "price": 3.5, "userId": Uuid::nil(), "categoryId": Uuid::nil()})
+                    .to_string()
+                    .into_bytes(),
+                "Invalid payment.create payload: `name` must not be empty",
+            ),
+        ];
+
+        for (body, expected) in cases {
+            let message = rejection(parse(&body));
+            assert!(message.starts_with(expected), "{:?} should start with {:?}", message, expected);
+        }
+    }
+
+    #[test]
+    fn validates_payload_fields() {
+        let cases = [
+            (create_payment("Coffee", None, 3.5), Ok(())),
+            (
+                create_payment("", None, 3.5),
+                Err("`name` must not be empty".to_string()),
+            ),
+            (
+                create_payment(&"n".repeat(NAME_MAX_LENGTH + 1), None, 3.5),
+                Err(format!("`name` must be at most {} characters", NAME_MAX_LENGTH)),
+            ),
+            (
+                create_payment("Coffee", Some("d".repeat(DESCRIPTION_MAX_LENGTH)), 3.5),
+                Ok(()),
+            ),
+            (
+                create_payment("Coffee", Some("d".repeat(DESCRIPTION_MAX_LENGTH + 1)), 3.5),
+                Err(format!("`description` must be at most {} characters", DESCRIPTION_MAX_LENGTH)),
+            ),
+            (
+                create_payment("Coffee", None, f64::NAN),
+                Err("`price` must be a finite number".to_string()),
+            ),
+            (
+                create_payment("Coffee", None, f64::INFINITY),
+                Err("`price` must be a finite number".to_string()),
+            ),
+            (
+                Command::CreateCategory(CreateCategoryV1 {
+                    name: "\t".to_string(),
+                    description: "Groceries".to_string(),
+                    userId: Uuid::nil(),
+                    householdId: None,
+                }),
+                Err("`name` must not be empty".to_string()),
+            ),
+        ];
+
+        for (command, expected) in cases {
+            assert_eq!(validate(&command), expected, "{:?}", command);
+        }
+    }
+}
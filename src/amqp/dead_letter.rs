@@ -0,0 +1,232 @@
+use std::error::Error;
+
+use futures::StreamExt;
+use lapin::{
+    message::Delivery,
+    options::{BasicAckOptions, BasicConsumeOptions, BasicNackOptions, BasicPublishOptions, ConfirmSelectOptions},
+    types::{AMQPValue, FieldTable, LongString},
+    BasicProperties, Channel, Connection, ConnectionProperties,
+};
+use serde_json::{Map, Value};
+use sqlx::{Pool, Postgres};
+use uuid::Uuid;
+
+use crate::amqp::{
+    config::get_config,
+    model::DeadLetterModel,
+    retry::{FAILURE_REASON_HEADER, RETRY_COUNT_HEADER},
+};
+
+pub const DEAD_LETTER_QUEUE: &str = "payments-dead-letter.queue";
+pub const REPLAY_EXCHANGE: &str = "payments.exchange";
+pub const REPLAYED_FROM_HEADER: &str = "x-replayed-from";
+
+/// Drains the dead letter queue into the `dead_letters` table. Messages are
+/// only acked once stored, so nothing is lost if the database is down.
+pub async fn listen(db: Pool<Postgres>, channel: Channel) -> Result<(), lapin::Error> {
+    let mut consumer = channel
+        .basic_consume(
+            DEAD_LETTER_QUEUE,
+            "dead-letter-consumer",
+            BasicConsumeOptions::default(),
+            FieldTable::default(),
+        )
+        .await?;
+
+    log::info!("Server listening to {}", DEAD_LETTER_QUEUE);
+
+    while let Some(result) = consumer.next().await {
+        let delivery = match result {
+            Ok(delivery) => delivery,
+            Err(err) => {
+                log::error!("Failed to receive dead letter: {}", err);
+                continue;
+            }
+        };
+
+        match store(&db, &delivery).await {
+            Ok(id) => {
+                log::info!("Stored dead letter {}", id);
+                delivery.ack(BasicAckOptions::default()).await?;
+            }
+            Err(err) => {
+                log::error!("Failed to store dead letter, requeueing: {:?}", err);
+                delivery.nack(BasicNackOptions { requeue: true, ..Default::default() }).await?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+async fn store(db: &Pool<Postgres>, delivery: &Delivery) -> Result<Uuid, sqlx::Error> {
+    let headers = delivery.properties.headers().clone().unwrap_or_default();
+    let header = |name: &str| headers.inner().get(name).cloned();
+
+    let reason = match header(FAILURE_REASON_HEADER) {
+        Some(AMQPValue::LongString(reason)) => Some(reason.to_string()),
+        // Rejected without going through the retry policy.
+        _ => None,
+    };
+    let retry_count = match header(RETRY_COUNT_HEADER) {
+        Some(AMQPValue::LongUInt(count)) => count as i32,
+        Some(AMQPValue::LongInt(count)) => count,
+        Some(AMQPValue::LongLongInt(count)) => count as i32,
+        _ => 0,
+    };
+
+    sqlx::query_scalar!(
+        "INSERT INTO dead_letters (message_id, routing_key, payload, headers, reason, retry_count)
+        VALUES ($1, $2, $3, $4, $5, $6) RETURNING id",
+        delivery.properties.message_id().as_ref().map(|id| id.to_string()),
+        delivery.routing_key.to_string(),
+        String::from_utf8_lossy(&delivery.data).into_owned(),
+        headers_to_json(&headers),
+        reason,
+        retry_count
+    )
+    .fetch_one(db)
+    .await
+}
+
+fn headers_to_json(headers: &FieldTable) -> Value {
+    let map: Map<String, Value> = headers
+        .inner()
+        .iter()
+        .map(|(key, value)| (key.to_string(), amqp_value_to_json(value)))
+        .collect();
+    Value::Object(map)
+}
+
+fn amqp_value_to_json(value: &AMQPValue) -> Value {
+    match value {
+        AMQPValue::Boolean(v) => Value::from(*v),
+        AMQPValue::ShortShortInt(v) => Value::from(*v),
+        AMQPValue::ShortShortUInt(v) => Value::from(*v),
+        AMQPValue::ShortInt(v) => Value::from(*v),
+        AMQPValue::ShortUInt(v) => Value::from(*v),
+        AMQPValue::LongInt(v) => Value::from(*v),
+        AMQPValue::LongUInt(v) => Value::from(*v),
+        AMQPValue::LongLongInt(v) => Value::from(*v),
+        AMQPValue::Float(v) => Value::from(*v),
+        AMQPValue::Double(v) => Value::from(*v),
+        AMQPValue::ShortString(v) => Value::from(v.as_str()),
+        AMQPValue::LongString(v) => Value::from(v.to_string()),
+        AMQPValue::FieldArray(v) => Value::Array(v.as_slice().iter().map(amqp_value_to_json).collect()),
+        AMQPValue::FieldTable(v) => headers_to_json(v),
+        other => Value::from(format!("{:?}", other)),
+    }
+}
+
+pub async fn list(
+    db: &Pool<Postgres>,
+    include_replayed: bool,
+    limit: i64,
+    offset: i64,
+) -> Result<Vec<DeadLetterModel>, sqlx::Error> {
+    sqlx::query_as!(
+        DeadLetterModel,
+        "SELECT * FROM dead_letters WHERE ($1 OR replayed_at IS NULL)
+        ORDER BY created_at DESC LIMIT $2 OFFSET $3",
+        include_replayed,
+        limit,
+        offset
+    )
+    .fetch_all(db)
+    .await
+}
+
+pub async fn find(db: &Pool<Postgres>, id: Uuid) -> Result<Option<DeadLetterModel>, sqlx::Error> {
+    sqlx::query_as!(DeadLetterModel, "SELECT * FROM dead_letters WHERE id = $1", id)
+        .fetch_optional(db)
+        .await
+}
+
+/// Replaces the payload of a message that hasn't been replayed yet, e.g. to
+/// fix a typo in a category ID before replaying it.
+pub async fn update_payload(
+    db: &Pool<Postgres>,
+    id: Uuid,
+    payload: &str,
+) -> Result<Option<DeadLetterModel>, sqlx::Error> {
+    sqlx::query_as!(
+        DeadLetterModel,
+        "UPDATE dead_letters SET payload = $2, updated_at = NOW()
+        WHERE id = $1 AND replayed_at IS NULL RETURNING *",
+        id,
+        payload
+    )
+    .fetch_optional(db)
+    .await
+}
+
+pub async fn discard(db: &Pool<Postgres>, id: Uuid) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query!("DELETE FROM dead_letters WHERE id = $1", id)
+        .execute(db)
+        .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+/// Republishes the given messages to `payments.exchange` with a fresh retry
+/// count and marks them replayed. Returns the IDs that were replayed;
+/// unknown or already replayed IDs, and messages the broker refused, are
+/// skipped.
+pub async fn replay(db: &Pool<Postgres>, ids: &[Uuid]) -> Result<Vec<Uuid>, Box<dyn Error + Send + Sync>> {
+    let dead_letters = sqlx::query_as!(
+        DeadLetterModel,
+        "SELECT * FROM dead_letters WHERE id = ANY($1) AND replayed_at IS NULL ORDER BY created_at",
+        ids
+    )
+    .fetch_all(db)
+    .await?;
+
+    if dead_letters.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let connection = Connection::connect(&get_config().amqp_addr, ConnectionProperties::default()).await?;
+    let channel = connection.create_channel().await?;
+    channel.confirm_select(ConfirmSelectOptions::default()).await?;
+
+    let mut replayed = Vec::new();
+    for dead_letter in dead_letters {
+        let mut headers = FieldTable::default();
+        headers.insert(
+            REPLAYED_FROM_HEADER.into(),
+            AMQPValue::LongString(LongString::from(dead_letter.id.to_string())),
+        );
+
+        let mut properties = BasicProperties::default()
+            .with_content_type("application/json".into())
+            .with_delivery_mode(2)
+            .with_headers(headers);
+        if let Some(message_id) = &dead_letter.message_id {
+            properties = properties.with_message_id(message_id.as_str().into());
+        }
+
+        let confirmation = channel
+            .basic_publish(
+                REPLAY_EXCHANGE,
+                "",
+                BasicPublishOptions::default(),
+                dead_letter.payload.as_bytes(),
+                properties,
+            )
+            .await?
+            .await?;
+
+        // Left unmarked so it can be replayed again.
+        if confirmation.is_nack() {
+            log::warn!("Broker rejected replay of dead letter {}", dead_letter.id);
+            continue;
+        }
+
+        sqlx::query!("UPDATE dead_letters SET replayed_at = NOW() WHERE id = $1", dead_letter.id)
+            .execute(db)
+            .await?;
+        replayed.push(dead_letter.id);
+    }
+
+    connection.close(0, "replay finished").await?;
+    Ok(replayed)
+}
@@ -0,0 +1,112 @@
+//! Inspect and recover dead-lettered payment messages from a terminal.
+//!
+//! ```text
+//! dead-letters list [--all] [--limit N]
+//! dead-letters show <id>
+//! dead-letters edit <id> <payload-file | ->
+//! dead-letters drop <id>...
+//! dead-letters replay <id>...
+//! ```
+
+use std::io::Read;
+
+use backend::amqp::dead_letter;
+use dotenv::dotenv;
+use sqlx::postgres::PgPoolOptions;
+use uuid::Uuid;
+
+type CliResult = Result<(), Box<dyn std::error::Error + Send + Sync>>;
+
+fn usage() -> ! {
+    eprintln!("usage: dead-letters <list [--all] [--limit N] | show ID | edit ID FILE|- | drop ID... | replay ID...>");
+    std::process::exit(2)
+}
+
+fn parse_ids(args: &[String]) -> Result<Vec<Uuid>, uuid::Error> {
+    args.iter().map(|arg| Uuid::parse_str(arg)).collect()
+}
+
+#[tokio::main]
+async fn main() -> CliResult {
+    dotenv().ok();
+
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let (command, rest) = match args.split_first() {
+        Some((command, rest)) => (command.as_str(), rest),
+        None => usage(),
+    };
+
+    let database_url = std::env::var("DATABASE_URL").expect("DATABASE_URL must be set");
+    let db = PgPoolOptions::new().max_connections(1).connect(&database_url).await?;
+
+    match command {
+        "list" => {
+            let include_replayed = rest.iter().any(|arg| arg == "--all");
+            let limit = rest
+                .iter()
+                .position(|arg| arg == "--limit")
+                .and_then(|i| rest.get(i + 1))
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(50);
+
+            for dead_letter in dead_letter::list(&db, include_replayed, limit, 0).await? {
+                println!(
+                    "{}  {}  retries={}  {}{}",
+                    dead_letter.id,
+                    dead_letter.created_at.to_rfc3339(),
+                    dead_letter.retry_count,
+                    dead_letter.reason.as_deref().unwrap_or("(no reason)"),
+                    if dead_letter.replayed_at.is_some() { "  [replayed]" } else { "" }
+                );
+            }
+        }
+        "show" => {
+            let id = rest.first().map(|arg| Uuid::parse_str(arg)).unwrap_or_else(|| usage())?;
+            match dead_letter::find(&db, id).await? {
+                Some(dead_letter) => println!("{}", serde_json::to_string_pretty(&dead_letter)?),
+                None => return Err(format!("Dead letter {} not found", id).into()),
+            }
+        }
+        "edit" => {
+            let (id, source) = match rest {
+                [id, source] => (Uuid::parse_str(id)?, source),
+                _ => usage(),
+            };
+            let payload = if source == "-" {
+                let mut payload = String::new();
+                std::io::stdin().read_to_string(&mut payload)?;
+                payload
+            } else {
+                std::fs::read_to_string(source)?
+            };
+
+            match dead_letter::update_payload(&db, id, payload.trim_end()).await? {
+                Some(_) => println!("Updated {}", id),
+                None => return Err(format!("Pending dead letter {} not found", id).into()),
+            }
+        }
+        "drop" if !rest.is_empty() => {
+            for id in parse_ids(rest)? {
+                if dead_letter::discard(&db, id).await? {
+                    println!("Dropped {}", id);
+                } else {
+                    eprintln!("Dead letter {} not found", id);
+                }
+            }
+        }
+        "replay" if !rest.is_empty() => {
+            let ids = parse_ids(rest)?;
+            let replayed = dead_letter::replay(&db, &ids).await?;
+            for id in &ids {
+                if replayed.contains(id) {
+                    println!("Replayed {}", id);
+                } else {
+                    eprintln!("Skipped {} (not found or already replayed)", id);
+                }
+            }
+        }
+        _ => usage(),
+    }
+
+    Ok(())
+}
@@ -0,0 +1,178 @@
+use crate::{
+    amqp::{
+        dead_letter,
+        schema::{ DeadLetterFilterOptions, ReplayDeadLettersSchema, UpdateDeadLetterSchema },
+    },
+    jwt_auth,
+    user::role,
+    AppState,
+};
+use actix_web::{ delete, get, patch, post, web, HttpResponse, Responder };
+use serde_json::json;
+
+fn forbidden() -> HttpResponse {
+    HttpResponse::Forbidden().json(
+        json!({"status": "fail","message": "Only administrators can manage dead-lettered messages"})
+    )
+}
+
+#[get("/")]
+async fn dead_letter_list_handler(
+    opts: web::Query<DeadLetterFilterOptions>,
+    data: web::Data<AppState>,
+    jwt: jwt_auth::JwtMiddleware
+) -> impl Responder {
+    if !role::is_admin(&data.db, jwt.user_id).await {
+        return forbidden();
+    }
+
+    let limit = opts.limit.unwrap_or(50);
+    let offset = (opts.page.unwrap_or(1) - 1) * limit;
+    let include_replayed = opts.include_replayed.unwrap_or(false);
+
+    match dead_letter::list(&data.db, include_replayed, limit as i64, offset as i64).await {
+        Ok(dead_letters) => {
+            HttpResponse::Ok().json(json!({
+                "status": "success",
+                "results": dead_letters.len(),
+                "deadLetters": dead_letters
+            }))
+        }
+        Err(err) => {
+            let message = format!("Error: {:?}", err);
+            HttpResponse::InternalServerError().json(json!({"status": "error","message": message}))
+        }
+    }
+}
+
+#[post("/replay")]
+async fn replay_dead_letters_handler(
+    body: web::Json<ReplayDeadLettersSchema>,
+    data: web::Data<AppState>,
+    jwt: jwt_auth::JwtMiddleware
+) -> impl Responder {
+    if !role::is_admin(&data.db, jwt.user_id).await {
+        return forbidden();
+    }
+
+    replay(&data, &body.ids).await
+}
+
+#[get("/{id}")]
+async fn get_dead_letter_handler(
+    path: web::Path<uuid::Uuid>,
+    data: web::Data<AppState>,
+    jwt: jwt_auth::JwtMiddleware
+) -> impl Responder {
+    if !role::is_admin(&data.db, jwt.user_id).await {
+        return forbidden();
+    }
+
+    let id = path.into_inner();
+    match dead_letter::find(&data.db, id).await {
+        Ok(Some(dead_letter)) => {
+            HttpResponse::Ok().json(json!({"status": "success","data": {"deadLetter": dead_letter}}))
+        }
+        Ok(None) => {
+            let message = format!("Dead letter with ID: {} not found", id);
+            HttpResponse::NotFound().json(json!({"status": "fail","message": message}))
+        }
+        Err(err) => {
+            let message = format!("Error: {:?}", err);
+            HttpResponse::InternalServerError().json(json!({"status": "error","message": message}))
+        }
+    }
+}
+
+#[patch("/{id}")]
+async fn edit_dead_letter_handler(
+    path: web::Path<uuid::Uuid>,
+    body: web::Json<UpdateDeadLetterSchema>,
+    data: web::Data<AppState>,
+    jwt: jwt_auth::JwtMiddleware
+) -> impl Responder {
+    if !role::is_admin(&data.db, jwt.user_id).await {
+        return forbidden();
+    }
+
+    let id = path.into_inner();
+    match dead_letter::update_payload(&data.db, id, &body.payload).await {
+        Ok(Some(dead_letter)) => {
+            HttpResponse::Ok().json(json!({"status": "success","data": {"deadLetter": dead_letter}}))
+        }
+        Ok(None) => {
+            let message = format!("Pending dead letter with ID: {} not found", id);
+            HttpResponse::NotFound().json(json!({"status": "fail","message": message}))
+        }
+        Err(err) => {
+            let message = format!("Error: {:?}", err);
+            HttpResponse::InternalServerError().json(json!({"status": "error","message": message}))
+        }
+    }
+}
+
+#[delete("/{id}")]
+async fn delete_dead_letter_handler(
+    path: web::Path<uuid::Uuid>,
+    data: web::Data<AppState>,
+    jwt: jwt_auth::JwtMiddleware
+) -> impl Responder {
+    if !role::is_admin(&data.db, jwt.user_id).await {
+        return forbidden();
+    }
+
+    let id = path.into_inner();
+    match dead_letter::discard(&data.db, id).await {
+        Ok(true) => HttpResponse::NoContent().finish(),
+        Ok(false) => {
+            let message = format!("Dead letter with ID: {} not found", id);
+            HttpResponse::NotFound().json(json!({"status": "fail","message": message}))
+        }
+        Err(err) => {
+            let message = format!("Error: {:?}", err);
+            HttpResponse::InternalServerError().json(json!({"status": "error","message": message}))
+        }
+    }
+}
+
+#[post("/{id}/replay")]
+async fn replay_dead_letter_handler(
+    path: web::Path<uuid::Uuid>,
+    data: web::Data<AppState>,
+    jwt: jwt_auth::JwtMiddleware
+) -> impl Responder {
+    if !role::is_admin(&data.db, jwt.user_id).await {
+        return forbidden();
+    }
+
+    replay(&data, &[path.into_inner()]).await
+}
+
+async fn replay(data: &web::Data<AppState>, ids: &[uuid::Uuid]) -> HttpResponse {
+    match dead_letter::replay(&data.db, ids).await {
+        Ok(replayed) => {
+            HttpResponse::Ok().json(json!({
+                "status": "success",
+                "results": replayed.len(),
+                "replayed": replayed
+            }))
+        }
+        Err(err) => {
+            let message = format!("Error: {:?}", err);
+            HttpResponse::InternalServerError().json(json!({"status": "error","message": message}))
+        }
+    }
+}
+
+pub fn config(conf: &mut web::ServiceConfig) {
+    let scope = web
+        ::scope("/dead-letters")
+        .service(dead_letter_list_handler)
+        .service(replay_dead_letters_handler)
+        .service(get_dead_letter_handler)
+        .service(edit_dead_letter_handler)
+        .service(delete_dead_letter_handler)
+        .service(replay_dead_letter_handler);
+
+    conf.service(scope);
+}
@@ -1,3 +1,10 @@
 pub mod schema;
+pub mod batch;
+pub mod command;
 pub mod config;
-pub mod payment;
\ No newline at end of file
+pub mod dead_letter;
+pub mod handler;
+pub mod model;
+pub mod payment;
+pub mod retry;
+pub mod rpc;
@@ -0,0 +1,23 @@
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use uuid::Uuid;
+
+#[derive(Debug, FromRow, Deserialize, Serialize)]
+pub struct DeadLetterModel {
+    pub id: Uuid,
+    #[serde(rename = "messageId")]
+    pub message_id: Option<String>,
+    #[serde(rename = "routingKey")]
+    pub routing_key: String,
+    pub payload: String,
+    pub headers: serde_json::Value,
+    pub reason: Option<String>,
+    #[serde(rename = "retryCount")]
+    pub retry_count: i32,
+    #[serde(rename = "replayedAt")]
+    pub replayed_at: Option<chrono::DateTime<chrono::Utc>>,
+    #[serde(rename = "createdAt")]
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    #[serde(rename = "updatedAt")]
+    pub updated_at: chrono::DateTime<chrono::Utc>
+}
@@ -1,40 +1,108 @@
-use std::{error::Error, sync::Arc};
+use std::{error::Error, sync::Arc, time::Duration};
 use futures::StreamExt;
 use lapin::{
+    message::Delivery,
     options::{
-        BasicAckOptions, BasicConsumeOptions, BasicNackOptions, BasicRejectOptions,
-        ExchangeDeclareOptions, QueueBindOptions, QueueDeclareOptions,
+        BasicAckOptions, BasicCancelOptions, BasicConsumeOptions, BasicNackOptions, BasicQosOptions,
+        ConfirmSelectOptions, ExchangeDeclareOptions, QueueBindOptions, QueueDeclareOptions,
     },
     types::{AMQPValue, FieldTable},
     Connection, ConnectionProperties,
 };
-use serde_json::from_slice;
-use crate::{
-    amqp::{config::get_config, dto::PaymentMessage},
-    payment::model::CreatePaymentSchema,
-    payment::handler::create_payment_handler
+use sqlx::{Pool, Postgres};
+use tokio::sync::{watch, Semaphore};
+use crate::amqp::{
+    command::{self, Applied, CommandError, Message},
+    config::get_config,
+    batch, dead_letter, retry, rpc,
 };
-use actix_web::{web, HttpResponse};
 
-pub async fn run(pg_pool: Arc<Pool>) -> Result<(), Box<dyn Error>> {
+pub const IDEMPOTENCY_KEY_HEADER: &str = "x-idempotency-key";
+
+/// Consumes payment commands until `shutdown` flips to `true`, reconnecting
+/// with backoff whenever the broker connection is lost.
+pub async fn run(db: Pool<Postgres>, mut shutdown: watch::Receiver<bool>) {
     let config = get_config();
+    let mut attempt = 0;
 
-    let connection = Arc::new(Connection::connect(&config.amqp_addr, ConnectionProperties::default()).await?);
+    loop {
+        match consume(&db, &mut shutdown, &mut attempt).await {
+            Ok(()) => return,
+            Err(err) => log::error!("Payment consumer disconnected: {}", err),
+        }
 
-    connection.on_error(|err| {
-        log::error!("{}", err);
-        std::process::exit(1);
-    });
+        if *shutdown.borrow() {
+            return;
+        }
+
+        attempt += 1;
+        let factor = 1u64.checked_shl(attempt - 1).unwrap_or(u64::MAX);
+        let delay = config.reconnect_base_delay_ms.saturating_mul(factor).min(config.reconnect_max_delay_ms);
+        log::warn!("Reconnecting to the broker in {} ms (attempt {})", delay, attempt);
+
+        tokio::select! {
+            _ = tokio::time::sleep(Duration::from_millis(delay)) => {}
+            _ = shutdown.changed() => return,
+        }
+    }
+}
+
+/// Runs one connection's worth of consuming. Returns `Ok` only after a
+/// requested shutdown has drained the in-flight deliveries.
+async fn consume(
+    db: &Pool<Postgres>,
+    shutdown: &mut watch::Receiver<bool>,
+    attempt: &mut u32,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let config = get_config();
+
+    let connection = Connection::connect(&config.amqp_addr, ConnectionProperties::default()).await?;
 
     let declare_channel = connection.create_channel().await?;
 
     setup_dead_letter_exchange(&declare_channel).await?;
     setup_payments_exchange(&declare_channel).await?;
     setup_payments_queue(&declare_channel).await?;
-    declare_channel.close(0, "declare channel fineshed").await?;
+    retry::setup_delay_queues(&declare_channel, "payments.exchange").await?;
+    rpc::setup_queue(&declare_channel).await?;
+    declare_channel.close(0, "declare channel finished").await?;
+
+    // Retries and dead letters are republished from this channel, so
+    // confirms are needed before the original delivery can be acked.
+    let consumer_channel = connection.create_channel().await?;
+    consumer_channel.confirm_select(ConfirmSelectOptions::default()).await?;
+    // A batch can only fill up if the broker sends that many deliveries ahead.
+    let prefetch_count = if config.batch_enabled {
+        config.prefetch_count.max(u16::try_from(config.batch_size).unwrap_or(u16::MAX))
+    } else {
+        config.prefetch_count
+    };
+    consumer_channel.basic_qos(prefetch_count, BasicQosOptions::default()).await?;
+
+    // Dead letters are moved into the database for inspection and replay.
+    // The listener ends with the connection.
+    let dead_letter_channel = connection.create_channel().await?;
+    let dead_letter_db = db.clone();
+    tokio::spawn(async move {
+        if let Err(err) = dead_letter::listen(dead_letter_db, dead_letter_channel).await {
+            log::error!("Dead letter consumer stopped: {}", err);
+        }
+    });
+
+    // Queries from services that only speak AMQP are answered on their own
+    // channel, so slow commands don't hold up replies.
+    let rpc_channel = connection.create_channel().await?;
+    let rpc_db = db.clone();
+    tokio::spawn(async move {
+        if let Err(err) = rpc::listen(rpc_db, rpc_channel).await {
+            log::error!("RPC consumer stopped: {}", err);
+        }
+    });
 
-    listen_for_payments(consumer_channel).await?;
+    *attempt = 0;
+    listen_for_payments(db, &consumer_channel, shutdown).await?;
 
+    connection.close(0, "consumer shutting down").await?;
     Ok(())
 }
 
@@ -119,7 +187,15 @@ async fn setup_payments_queue(declare_channel: &lapin::Channel) -> Result<(), la
     Ok(())
 }
 
-async fn listen_for_payments(consumer_channel: lapin::Channel) -> Result<(), lapin::Error> {
+/// Hands deliveries to up to `concurrency` workers. On shutdown the consumer
+/// is cancelled and running workers get `shutdown_timeout_secs` to finish;
+/// anything still unacked is redelivered once the connection closes.
+async fn listen_for_payments(
+    db: &Pool<Postgres>,
+    consumer_channel: &lapin::Channel,
+    shutdown: &mut watch::Receiver<bool>,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let config = get_config();
     let mut consumer = consumer_channel
         .basic_consume(
             "payments.queue",
@@ -131,41 +207,387 @@ async fn listen_for_payments(consumer_channel: lapin::Channel) -> Result<(), lap
 
     log::info!("Server listening to payments.queue");
 
-    while let Some(result) = consumer.next().await {
-        if let Ok(delivery) = result {
-            match process_payment_message(delivery.data.as_slice()).await {
-                Ok(_) => delivery.ack(BasicAckOptions::default()).await?,
-                Err(err) => {
-                    log::error!("Failed to process payment message: {}", err);
-                    delivery.nack(BasicNackOptions::default()).await?;
-                }
+    if config.batch_enabled {
+        return batch::listen(db, consumer_channel, consumer, shutdown).await;
+    }
+
+    let workers = Arc::new(Semaphore::new(config.concurrency));
+
+    loop {
+        // Shutdown is checked before waiting on a worker as well, so a full
+        // pool doesn't delay the drain.
+        let permit = tokio::select! {
+            permit = workers.clone().acquire_owned() => permit?,
+            _ = shutdown.changed() => break,
+        };
+
+        let delivery = tokio::select! {
+            next = consumer.next() => match next {
+                Some(Ok(delivery)) => delivery,
+                Some(Err(err)) => return Err(err.into()),
+                None => return Err("payments.queue consumer was cancelled by the broker".into()),
+            },
+            _ = shutdown.changed() => break,
+        };
+
+        let db = db.clone();
+        let channel = consumer_channel.clone();
+        tokio::spawn(async move {
+            if let Err(err) = handle_delivery(&db, &channel, delivery).await {
+                log::error!("Failed to settle payment message: {}", err);
             }
+            drop(permit);
+        });
+    }
+
+    log::info!("Draining payments.queue consumer");
+    consumer_channel.basic_cancel("consumer", BasicCancelOptions::default()).await?;
+
+    let drained = tokio::time::timeout(
+        Duration::from_secs(config.shutdown_timeout_secs),
+        workers.acquire_many(config.concurrency as u32),
+    )
+    .await;
+
+    match drained {
+        Ok(_) => log::info!("Payment consumer drained"),
+        Err(_) => log::warn!("Payment consumer shutdown timed out with deliveries still in flight"),
+    }
+
+    Ok(())
+}
+
+/// Acks applied commands and duplicates. Failures are retried with backoff
+/// when another attempt could succeed, and dead-lettered with their reason
+/// otherwise.
+pub(crate) async fn handle_delivery(db: &Pool<Postgres>, channel: &lapin::Channel, delivery: Delivery) -> Result<(), lapin::Error> {
+    let err = match process_message(db, &delivery).await {
+        Ok((kind, Some(applied))) => {
+            log::info!("Applied {} to {} from payments.queue", kind, applied.id());
+            return delivery.ack(BasicAckOptions::default()).await;
+        }
+        Ok((kind, None)) => {
+            log::info!("Skipped already processed {} message", kind);
+            return delivery.ack(BasicAckOptions::default()).await;
         }
+        Err(err) => err,
+    };
+
+    settle_failure(channel, &delivery, &err).await
+}
+
+/// Retries a failed delivery or dead-letters it, falling back to a requeue
+/// if neither copy can be published.
+pub(crate) async fn settle_failure(channel: &lapin::Channel, delivery: &Delivery, err: &CommandError) -> Result<(), lapin::Error> {
+    if let Err(publish_err) = retry::retry_or_dead_letter(channel, delivery, &err.to_string(), err.is_retryable()).await {
+        log::error!("Failed to reschedule payment message, requeueing: {}", publish_err);
+        delivery.nack(BasicNackOptions { requeue: true, ..Default::default() }).await?;
     }
 
     Ok(())
 }
 
-async fn process_payment_message(data: &[u8]) -> Result<(), Box<dyn Error>> {
-    let payment_message: PaymentMessage = from_slice(data)?;
+/// The key deduplicating a message: its `message_id` property, else the
+/// `x-idempotency-key` header, else the body's `idempotencyKey`.
+pub(crate) fn message_key(delivery: &Delivery, message: &Message) -> Option<String> {
+    let from_header = || {
+        delivery.properties.headers().as_ref().and_then(|headers| {
+            match headers.inner().get(IDEMPOTENCY_KEY_HEADER) {
+                Some(AMQPValue::LongString(key)) => Some(key.to_string()),
+                Some(AMQPValue::ShortString(key)) => Some(key.to_string()),
+                _ => None,
+            }
+        })
+    };
+
+    delivery.properties
+        .message_id()
+        .as_ref()
+        .map(|id| id.to_string())
+        .or_else(from_header)
+        .or_else(|| message.idempotency_key.clone())
+        .filter(|key| !key.trim().is_empty())
+}
 
-    let create_payment_schema = CreatePaymentSchema {
-        name: payment_message.name,
-        description: payment_message.description.unwrap_or_default(),
-        price: payment_message.price.unwrap_or_default(),
-        userId: payment_message.userId.unwrap_or_default(),
-        categoryId: payment_message.categoryId.unwrap_or_default(),
+/// Returns the command type and what it changed, or `None` when the message
+/// was already processed.
+async fn process_message(db: &Pool<Postgres>, delivery: &Delivery) -> Result<(&'static str, Option<Applied>), CommandError> {
+    let message = command::parse(&delivery.data)?;
+    let kind = message.command.kind();
+    let key = message_key(delivery, &message).ok_or_else(|| {
+        CommandError::Rejected(format!(
+            "Message has no message_id, {} header or idempotencyKey",
+            IDEMPOTENCY_KEY_HEADER
+        ))
+    })?;
+
+    if let Some(correlation_id) = &message.correlation_id {
+        log::info!("Processing {} with correlation ID {}", kind, correlation_id);
+    }
+
+    let applied = command::execute(db, message.command, &key).await?;
+    Ok((kind, applied))
+}
+
+#[cfg(test)]
+mod tests {
+    use lapin::{
+        options::{BasicGetOptions, BasicPublishOptions},
+        types::ShortString,
+        BasicProperties, Channel, Connection, ConnectionProperties,
     };
+    use serde_json::json;
+    use uuid::Uuid;
 
-    let response = create_payment_handler(web::Json(create_payment_schema)).await;
-    match response {
-        Ok(http_response) => {
-            if http_response.status().is_success() {
-                Ok(())
-            } else {
-                Err("Failed to create payment".into())
+    use super::*;
+    use crate::{
+        payment::{schema::CreatePaymentSchema, service},
+        test_support,
+    };
+
+    /// A private queue test messages are delivered from, and a tap receiving
+    /// a copy of everything the consumer sends for a first retry or to the
+    /// dead letter exchange. Both go away with the connection.
+    struct Broker {
+        connection: Connection,
+        input: String,
+        tap: String,
+    }
+
+    impl Broker {
+        async fn connect() -> Self {
+            let connection = Connection::connect(&get_config().amqp_addr, ConnectionProperties::default())
+                .await
+                .expect("Failed to connect to the test broker");
+
+            let channel = connection.create_channel().await.unwrap();
+            setup_dead_letter_exchange(&channel).await.unwrap();
+            retry::setup_delay_queues(&channel, "payments.exchange").await.unwrap();
+
+            let private = || QueueDeclareOptions {
+                exclusive: true,
+                auto_delete: true,
+                ..Default::default()
+            };
+            let input = channel.queue_declare("", private(), FieldTable::default()).await.unwrap();
+            let tap = channel.queue_declare("", private(), FieldTable::default()).await.unwrap();
+            for (exchange, routing_key) in [(retry::RETRY_EXCHANGE, "1"), (retry::DEAD_LETTER_EXCHANGE, "")] {
+                channel
+                    .queue_bind(tap.name().as_str(), exchange, routing_key, QueueBindOptions::default(), FieldTable::default())
+                    .await
+                    .unwrap();
+            }
+
+            Broker {
+                connection,
+                input: input.name().to_string(),
+                tap: tap.name().to_string(),
+            }
+        }
+
+        /// A channel set up like the consumer's.
+        async fn channel(&self) -> Channel {
+            let channel = self.connection.create_channel().await.unwrap();
+            channel.confirm_select(ConfirmSelectOptions::default()).await.unwrap();
+            channel
+        }
+
+        /// Publishes `body` to the input queue, hands it to the consumer on its
+        /// own channel and closes that channel, which would requeue the
+        /// message had it been left unsettled.
+        async fn handle(&self, db: &Pool<Postgres>, key: &str, headers: FieldTable, body: &[u8]) {
+            let channel = self.channel().await;
+            let properties = BasicProperties::default()
+                .with_message_id(ShortString::from(key))
+                .with_headers(headers);
+            channel
+                .basic_publish("", &self.input, BasicPublishOptions::default(), body, properties)
+                .await
+                .unwrap()
+                .await
+                .unwrap();
+
+            let delivery = channel
+                .basic_get(&self.input, BasicGetOptions::default())
+                .await
+                .unwrap()
+                .expect("the test message should be queued")
+                .delivery;
+            handle_delivery(db, &channel, delivery).await.expect("the delivery should be settled");
+
+            channel.close(200, "test finished").await.unwrap();
+        }
+
+        async fn pending_input(&self) -> u32 {
+            let channel = self.channel().await;
+            let queue = channel
+                .queue_declare(
+                    &self.input,
+                    QueueDeclareOptions {
+                        passive: true,
+                        ..Default::default()
+                    },
+                    FieldTable::default(),
+                )
+                .await
+                .unwrap();
+            queue.message_count()
+        }
+
+        /// The copy of the message with `key` that reached the tap, if any.
+        async fn copy(&self, key: &str) -> Option<Delivery> {
+            let channel = self.channel().await;
+            while let Some(message) = channel.basic_get(&self.tap, BasicGetOptions { no_ack: true }).await.unwrap() {
+                if message.delivery.properties.message_id().as_ref().map(|id| id.as_str()) == Some(key) {
+                    return Some(message.delivery);
+                }
             }
+            None
+        }
+    }
+
+    fn create_payment_body(user_id: Uuid, category_id: Uuid, description: &str) -> Vec<u8> {
+        json!({
+            "type": command::PAYMENT_CREATE,
+            "version": 1,
+            "payload": {
+                "name": "Groceries",
+                "description": description,
+                "price": 42.5,
+                "userId": user_id,
+                "categoryId": category_id
+            }
+        })
+        .to_string()
+        .into_bytes()
+    }
+
+    fn failure_reason(delivery: &Delivery) -> Option<String> {
+        match delivery.properties.headers().as_ref()?.inner().get(retry::FAILURE_REASON_HEADER) {
+            Some(AMQPValue::LongString(reason)) => Some(reason.to_string()),
+            _ => None,
         }
-        Err(_) => Err("Failed to create payment".into()),
+    }
+
+    async fn payment_count(db: &Pool<Postgres>, description: &str) -> i64 {
+        sqlx::query_scalar!(r#"SELECT COUNT(*) AS "count!" FROM payments WHERE description = $1"#, description)
+            .fetch_one(db)
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    #[ignore = "needs the docker-compose Postgres and RabbitMQ"]
+    async fn acks_applied_commands_and_their_redeliveries() {
+        let db = test_support::pool().await;
+        let broker = Broker::connect().await;
+        let user_id = test_support::user(&db, true).await;
+        let category_id = test_support::category(&db, user_id, None).await;
+        let key = Uuid::new_v4().to_string();
+        let description = format!("Groceries {}", key);
+        let body = create_payment_body(user_id, category_id, &description);
+
+        broker.handle(&db, &key, FieldTable::default(), &body).await;
+        broker.handle(&db, &key, FieldTable::default(), &body).await;
+
+        let payments = payment_count(&db, &description).await;
+        let pending = broker.pending_input().await;
+        let copy = broker.copy(&key).await;
+        test_support::cleanup(&db, &[user_id]).await;
+
+        assert_eq!(payments, 1);
+        assert_eq!(pending, 0);
+        assert!(copy.is_none(), "{:?}", copy);
+    }
+
+    #[tokio::test]
+    #[ignore = "needs the docker-compose RabbitMQ"]
+    async fn dead_letters_invalid_messages_without_retrying() {
+        let db = test_support::unreachable_pool();
+        let broker = Broker::connect().await;
+        let key = Uuid::new_v4().to_string();
+
+        broker.handle(&db, &key, FieldTable::default(), b"not json").await;
+
+        assert_eq!(broker.pending_input().await, 0);
+        let copy = broker.copy(&key).await.expect("the message should be dead-lettered");
+        assert_eq!(copy.exchange.as_str(), retry::DEAD_LETTER_EXCHANGE);
+        assert_eq!(retry::retry_count(&copy), 0);
+        assert!(failure_reason(&copy).is_some_and(|reason| reason.contains("not valid JSON")));
+    }
+
+    #[tokio::test]
+    #[ignore = "needs the docker-compose Postgres and RabbitMQ"]
+    async fn dead_letters_constraint_violations_without_retrying() {
+        let db = test_support::pool().await;
+        let broker = Broker::connect().await;
+        let user_id = test_support::user(&db, true).await;
+        let category_id = test_support::category(&db, user_id, None).await;
+        let key = Uuid::new_v4().to_string();
+        let description = format!("Groceries {}", key);
+
+        // Payment descriptions are unique, so the command's insert fails.
+        let existing = CreatePaymentSchema {
+            name: "Groceries".to_string(),
+            description: description.clone(),
+            price: 1.0,
+            userId: user_id,
+            categoryId: category_id,
+        };
+        service::create_payment(&db, &existing).await.unwrap();
+
+        let body = create_payment_body(user_id, category_id, &description);
+        broker.handle(&db, &key, FieldTable::default(), &body).await;
+
+        let pending = broker.pending_input().await;
+        let copy = broker.copy(&key).await;
+        test_support::cleanup(&db, &[user_id]).await;
+
+        assert_eq!(pending, 0);
+        let copy = copy.expect("the message should be dead-lettered");
+        assert_eq!(copy.exchange.as_str(), retry::DEAD_LETTER_EXCHANGE);
+        assert_eq!(retry::retry_count(&copy), 0);
+    }
+
+    #[tokio::test]
+    #[ignore = "needs the docker-compose RabbitMQ"]
+    async fn retries_database_failures_with_backoff() {
+        let db = test_support::unreachable_pool();
+        db.close().await;
+        let broker = Broker::connect().await;
+        let key = Uuid::new_v4().to_string();
+        let body = create_payment_body(Uuid::new_v4(), Uuid::new_v4(), "Groceries");
+
+        broker.handle(&db, &key, FieldTable::default(), &body).await;
+
+        assert_eq!(broker.pending_input().await, 0);
+        let copy = broker.copy(&key).await.expect("the message should be scheduled for a retry");
+        assert_eq!(copy.exchange.as_str(), retry::RETRY_EXCHANGE);
+        assert_eq!(copy.routing_key.as_str(), "1");
+        assert_eq!(retry::retry_count(&copy), 1);
+        assert_eq!(
+            copy.properties.expiration().as_ref().map(|ttl| ttl.as_str().to_string()),
+            Some(retry::backoff_ms(1).to_string())
+        );
+    }
+
+    #[tokio::test]
+    #[ignore = "needs the docker-compose RabbitMQ"]
+    async fn dead_letters_once_retries_run_out() {
+        let db = test_support::unreachable_pool();
+        db.close().await;
+        let broker = Broker::connect().await;
+        let key = Uuid::new_v4().to_string();
+        let body = create_payment_body(Uuid::new_v4(), Uuid::new_v4(), "Groceries");
+        let max_retries = get_config().max_retries;
+
+        let mut headers = FieldTable::default();
+        headers.insert(retry::RETRY_COUNT_HEADER.into(), AMQPValue::LongUInt(max_retries));
+        broker.handle(&db, &key, headers, &body).await;
+
+        assert_eq!(broker.pending_input().await, 0);
+        let copy = broker.copy(&key).await.expect("the message should be dead-lettered");
+        assert_eq!(copy.exchange.as_str(), retry::DEAD_LETTER_EXCHANGE);
+        assert_eq!(retry::retry_count(&copy), max_retries);
     }
 }
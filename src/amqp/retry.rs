@@ -0,0 +1,208 @@
+use std::error::Error;
+
+use lapin::{
+    message::Delivery,
+    options::{BasicAckOptions, BasicPublishOptions, ExchangeDeclareOptions, QueueBindOptions, QueueDeclareOptions},
+    types::{AMQPValue, FieldTable, LongString, ShortString},
+    Channel, ExchangeKind,
+};
+
+use crate::amqp::config::get_config;
+
+pub const RETRY_EXCHANGE: &str = "payments-retry.exchange";
+pub const DEAD_LETTER_EXCHANGE: &str = "payments-dead-letter.exchange";
+pub const RETRY_COUNT_HEADER: &str = "x-retry-count";
+pub const FAILURE_REASON_HEADER: &str = "x-failure-reason";
+
+/// Retries wait in one delay queue per attempt. Every message in a queue
+/// shares the same TTL, so one with a long delay never holds up a shorter
+/// one behind it. Expired messages dead-letter back to `target_exchange`.
+pub async fn setup_delay_queues(channel: &Channel, target_exchange: &str) -> Result<(), lapin::Error> {
+    channel
+        .exchange_declare(
+            RETRY_EXCHANGE,
+            ExchangeKind::Direct,
+            ExchangeDeclareOptions {
+                durable: true,
+                ..Default::default()
+            },
+            FieldTable::default(),
+        )
+        .await?;
+
+    for attempt in 1..=get_config().max_retries {
+        let queue = delay_queue(attempt);
+        let mut queue_field = FieldTable::default();
+        queue_field.insert(
+            "x-dead-letter-exchange".into(),
+            AMQPValue::LongString(target_exchange.into()),
+        );
+        queue_field.insert("x-dead-letter-routing-key".into(), AMQPValue::LongString("".into()));
+
+        channel
+            .queue_declare(
+                &queue,
+                QueueDeclareOptions {
+                    durable: true,
+                    ..Default::default()
+                },
+                queue_field,
+            )
+            .await?;
+
+        channel
+            .queue_bind(
+                &queue,
+                RETRY_EXCHANGE,
+                &attempt.to_string(),
+                QueueBindOptions::default(),
+                FieldTable::default(),
+            )
+            .await?;
+    }
+
+    Ok(())
+}
+
+fn delay_queue(attempt: u32) -> String {
+    format!("payments-retry.{}.queue", attempt)
+}
+
+/// How many times the message has already been retried.
+pub fn retry_count(delivery: &Delivery) -> u32 {
+    let value = delivery
+        .properties
+        .headers()
+        .as_ref()
+        .and_then(|headers| headers.inner().get(RETRY_COUNT_HEADER).cloned());
+
+    match value {
+        Some(AMQPValue::LongUInt(count)) => count,
+        Some(AMQPValue::LongInt(count)) => count.max(0) as u32,
+        Some(AMQPValue::LongLongInt(count)) => count.max(0) as u32,
+        Some(AMQPValue::ShortUInt(count)) => count as u32,
+        _ => 0,
+    }
+}
+
+/// Delay before the given retry: the base delay doubled per attempt, capped.
+pub fn backoff_ms(attempt: u32) -> u64 {
+    let config = get_config();
+    let factor = 1u64.checked_shl(attempt.saturating_sub(1)).unwrap_or(u64::MAX);
+    config.retry_base_delay_ms.saturating_mul(factor).min(config.retry_max_delay_ms)
+}
+
+/// Schedules another attempt when `retryable` and attempts remain, otherwise
+/// sends the message to the dead letter exchange with the failure reason.
+/// The original delivery is only acked once the broker has confirmed the copy;
+/// a nacked copy is an error so the caller can requeue the original instead.
+pub async fn retry_or_dead_letter(
+    channel: &Channel,
+    delivery: &Delivery,
+    reason: &str,
+    retryable: bool,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let retries = retry_count(delivery);
+    let mut headers = delivery.properties.headers().clone().unwrap_or_default();
+    headers.insert(FAILURE_REASON_HEADER.into(), AMQPValue::LongString(LongString::from(reason)));
+
+    let (exchange, routing_key, properties) = if retryable && retries < get_config().max_retries {
+        let attempt = retries + 1;
+        let delay = backoff_ms(attempt);
+        log::warn!("Retrying payment message in {} ms (retry {}): {}", delay, attempt, reason);
+
+        headers.insert(RETRY_COUNT_HEADER.into(), AMQPValue::LongUInt(attempt));
+        let properties = delivery.properties
+            .clone()
+            .with_headers(headers)
+            .with_expiration(ShortString::from(delay.to_string()));
+        (RETRY_EXCHANGE, attempt.to_string(), properties)
+    } else {
+        log::error!("Dead-lettering payment message after {} retries: {}", retries, reason);
+
+        headers.insert(RETRY_COUNT_HEADER.into(), AMQPValue::LongUInt(retries));
+        (DEAD_LETTER_EXCHANGE, String::new(), delivery.properties.clone().with_headers(headers))
+    };
+
+    let confirmation = channel
+        .basic_publish(
+            exchange,
+            &routing_key,
+            BasicPublishOptions::default(),
+            &delivery.data,
+            properties,
+        )
+        .await?
+        .await?;
+
+    if confirmation.is_nack() {
+        return Err(format!("Broker rejected the copy sent to {}", exchange).into());
+    }
+
+    delivery.ack(BasicAckOptions::default()).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use lapin::{acker::Acker, BasicProperties};
+
+    use super::*;
+
+    fn delivery(headers: Option<FieldTable>) -> Delivery {
+        let properties = match headers {
+            Some(headers) => BasicProperties::default().with_headers(headers),
+            None => BasicProperties::default(),
+        };
+        Delivery {
+            delivery_tag: 1,
+            exchange: ShortString::from(""),
+            routing_key: ShortString::from(""),
+            redelivered: false,
+            properties,
+            data: Vec::new(),
+            acker: Acker::default(),
+        }
+    }
+
+    fn with_retry_count(value: AMQPValue) -> Delivery {
+        let mut headers = FieldTable::default();
+        headers.insert(RETRY_COUNT_HEADER.into(), value);
+        delivery(Some(headers))
+    }
+
+    #[test]
+    fn backoff_doubles_per_attempt_up_to_the_cap() {
+        let config = get_config();
+        let mut expected = config.retry_base_delay_ms;
+
+        for attempt in 1..=64 {
+            assert_eq!(backoff_ms(attempt), expected.min(config.retry_max_delay_ms), "attempt {}", attempt);
+            expected = expected.saturating_mul(2);
+        }
+        assert_eq!(backoff_ms(u32::MAX), config.retry_max_delay_ms);
+    }
+
+    #[test]
+    fn retry_count_reads_integer_headers() {
+        let cases = [
+            (AMQPValue::LongUInt(3), 3),
+            (AMQPValue::LongInt(2), 2),
+            (AMQPValue::LongLongInt(4), 4),
+            (AMQPValue::ShortUInt(1), 1),
+            (AMQPValue::LongInt(-1), 0),
+        ];
+
+        for (value, expected) in cases {
+            assert_eq!(retry_count(&with_retry_count(value.clone())), expected, "{:?}", value);
+        }
+    }
+
+    #[test]
+    fn retry_count_defaults_to_zero() {
+        assert_eq!(retry_count(&delivery(None)), 0);
+        assert_eq!(retry_count(&delivery(Some(FieldTable::default()))), 0);
+        assert_eq!(retry_count(&with_retry_count(AMQPValue::LongString("3".into()))), 0);
+        assert_eq!(retry_count(&with_retry_count(AMQPValue::Boolean(true))), 0);
+    }
+}
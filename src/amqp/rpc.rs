@@ -0,0 +1,200 @@
+use futures::StreamExt;
+use lapin::{
+    message::Delivery,
+    options::{
+        BasicAckOptions, BasicConsumeOptions, BasicPublishOptions, BasicQosOptions,
+        BasicRejectOptions, QueueDeclareOptions,
+    },
+    types::FieldTable,
+    BasicProperties, Channel,
+};
+use serde_json::{json, Value};
+use sqlx::{Pool, Postgres};
+
+use crate::{
+    amqp::{
+        command::{self, CommandError},
+        config::get_config,
+        schema::{Envelope, GetCategoryV1, ListPaymentsV1},
+    },
+    category::service as category_service,
+    history::recorder::Entity,
+    household::access,
+    payment::service::{self as payment_service, PaymentQuery},
+};
+
+/// Requests are published to the default exchange with this routing key,
+/// `reply_to` set to the client's reply queue and a `correlation_id`.
+pub const RPC_QUEUE: &str = "payments-rpc.queue";
+
+pub const PAYMENTS_LIST: &str = "payments.list";
+pub const CATEGORY_GET: &str = "category.get";
+
+const MAX_LIMIT: usize = 100;
+
+pub async fn setup_queue(declare_channel: &Channel) -> Result<(), lapin::Error> {
+    declare_channel
+        .queue_declare(
+            RPC_QUEUE,
+            QueueDeclareOptions {
+                durable: true,
+                ..Default::default()
+            },
+            FieldTable::default(),
+        )
+        .await?;
+
+    Ok(())
+}
+
+/// Answers requests on [`RPC_QUEUE`] until the channel closes. Every request
+/// gets a reply in the REST response format, including failures; requests
+/// without `reply_to`, or whose reply can't be published, are rejected since
+/// nobody could read the answer.
+pub async fn listen(db: Pool<Postgres>, channel: Channel) -> Result<(), lapin::Error> {
+    channel.basic_qos(get_config().prefetch_count, BasicQosOptions::default()).await?;
+
+    let mut consumer = channel
+        .basic_consume(
+            RPC_QUEUE,
+            "rpc-consumer",
+            BasicConsumeOptions::default(),
+            FieldTable::default(),
+        )
+        .await?;
+
+    log::info!("Server listening to {}", RPC_QUEUE);
+
+    while let Some(result) = consumer.next().await {
+        let delivery = match result {
+            Ok(delivery) => delivery,
+            Err(err) => {
+                log::error!("Failed to receive RPC request: {}", err);
+                continue;
+            }
+        };
+
+        let reply_to = match delivery.properties.reply_to() {
+            Some(reply_to) => reply_to.clone(),
+            None => {
+                log::warn!("Rejecting RPC request without reply_to");
+                delivery.reject(BasicRejectOptions { requeue: false }).await?;
+                continue;
+            }
+        };
+
+        let (response, envelope_correlation_id) = answer(&db, &delivery).await;
+
+        let correlation_id = delivery
+            .properties
+            .correlation_id()
+            .clone()
+            .or_else(|| envelope_correlation_id.map(Into::into));
+
+        let mut properties = BasicProperties::default().with_content_type("application/json".into());
+        if let Some(correlation_id) = correlation_id {
+            properties = properties.with_correlation_id(correlation_id);
+        }
+
+        let published = match channel
+            .basic_publish(
+                "",
+                reply_to.as_str(),
+                BasicPublishOptions::default(),
+                response.to_string().as_bytes(),
+                properties,
+            )
+            .await
+        {
+            Ok(confirm) => confirm.await.map(|_| ()),
+            Err(err) => Err(err),
+        };
+
+        // The client times out waiting; one lost reply shouldn't stop the others.
+        if let Err(err) = published {
+            log::error!("Failed to publish RPC reply to {}: {}", reply_to.as_str(), err);
+            delivery.reject(BasicRejectOptions { requeue: false }).await?;
+            continue;
+        }
+
+        delivery.ack(BasicAckOptions::default()).await?;
+    }
+
+    Ok(())
+}
+
+/// Returns the reply body and the envelope's correlation ID, used when the
+/// request has no `correlation_id` property.
+async fn answer(db: &Pool<Postgres>, delivery: &Delivery) -> (Value, Option<String>) {
+    let envelope: Envelope = match serde_json::from_slice(&delivery.data) {
+        Ok(envelope) => envelope,
+        Err(err) => {
+            let message = format!("Invalid request envelope: {}", err);
+            return (json!({"status": "fail","message": message}), None);
+        }
+    };
+
+    let correlation_id = envelope.correlationId.clone();
+    let response = match dispatch(db, &envelope).await {
+        Ok(response) => response,
+        Err(CommandError::Database(err)) => {
+            let message = format!("Error: {:?}", err);
+            json!({"status": "error","message": message})
+        }
+        Err(err) => json!({"status": "fail","message": err.to_string()}),
+    };
+
+    (response, correlation_id)
+}
+
+async fn dispatch(db: &Pool<Postgres>, envelope: &Envelope) -> Result<Value, CommandError> {
+    match (envelope.kind.as_str(), envelope.version) {
+        (PAYMENTS_LIST, 1) => {
+            let request: ListPaymentsV1 = command::payload(envelope)?;
+            let limit = request.limit.unwrap_or(10).clamp(1, MAX_LIMIT);
+            let offset = (request.page.unwrap_or(1).max(1) - 1)
+                .checked_mul(limit)
+                .and_then(|offset| i64::try_from(offset).ok())
+                .ok_or_else(|| CommandError::Rejected("page is out of range".to_string()))?;
+
+            let query = PaymentQuery {
+                limit: limit as i64,
+                offset,
+                with_deleted: request.withDeleted.unwrap_or(false),
+                household_id: request.householdId,
+                from: request.from,
+                to: request.to,
+            };
+            let payments = payment_service::list_payments(db, request.userId, &query).await?;
+
+            Ok(json!({
+                "status": "success",
+                "results": payments.len(),
+                "payments": payments
+            }))
+        }
+        (CATEGORY_GET, 1) => {
+            let request: GetCategoryV1 = command::payload(envelope)?;
+            let not_found = || {
+                CommandError::NotFound(format!("Category with ID: {} not found", request.id))
+            };
+
+            access::entity_role(db, Entity::Category, request.id, request.userId)
+                .await?
+                .ok_or_else(not_found)?;
+
+            let category = category_service::find_category(db, request.id, request.withDeleted.unwrap_or(false))
+                .await?
+                .ok_or_else(not_found)?;
+
+            Ok(json!({"status": "success","data": json!({
+                "category": category
+            })}))
+        }
+        (PAYMENTS_LIST | CATEGORY_GET, version) => Err(CommandError::Rejected(format!(
+            "Unsupported version {} of {}, expected 1",
+            version, envelope.kind
+        ))),
+        (kind, _) => Err(CommandError::Rejected(format!("Unknown request type `{}`", kind))),
+    }
+}
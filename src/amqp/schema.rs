@@ -1,6 +1,30 @@
-use serde::Deserialize;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
 use uuid::Uuid;
 
+/// Wraps every command published to `payments.exchange` and every request
+/// sent to the RPC queue. `type` and `version` select the payload schema; see
+/// [`crate::amqp::command`] and [`crate::amqp::rpc`].
+#[allow(non_snake_case)]
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct Envelope {
+    /// Command name, e.g. `payment.create`.
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub version: u32,
+    /// Echoed in logs and replies so producers can trace a command.
+    pub correlationId: Option<String>,
+    /// Used when the message has no `message_id` property or
+    /// `x-idempotency-key` header.
+    pub idempotencyKey: Option<String>,
+    pub payload: Value,
+}
+
+/// The flat `payment.create` body sent before commands were wrapped in an
+/// [`Envelope`]. Still accepted so older producers keep working.
+#[allow(non_snake_case)]
 #[derive(Debug, Deserialize)]
 pub struct PaymentMessage {
     pub name: String,
@@ -8,4 +32,112 @@ pub struct PaymentMessage {
     pub price: f64,
     pub userId: Uuid,
     pub categoryId: Uuid,
+    pub idempotencyKey: Option<String>,
+}
+
+#[allow(non_snake_case)]
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct CreatePaymentV1 {
+    pub name: String,
+    pub description: Option<String>,
+    pub price: f64,
+    /// The user the payment is created for. They need write access to the
+    /// category's ledger.
+    pub userId: Uuid,
+    pub categoryId: Uuid,
+}
+
+#[allow(non_snake_case)]
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct UpdatePaymentV1 {
+    pub id: Uuid,
+    pub userId: Uuid,
+    pub name: String,
+    pub description: Option<String>,
+    pub price: f64,
+}
+
+#[allow(non_snake_case)]
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct DeletePaymentV1 {
+    pub id: Uuid,
+    pub userId: Uuid,
+}
+
+#[allow(non_snake_case)]
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct CreateCategoryV1 {
+    pub name: String,
+    pub description: String,
+    pub userId: Uuid,
+    /// Creates the category in a household the user edits instead of their
+    /// personal ledger.
+    pub householdId: Option<Uuid>,
+}
+
+#[allow(non_snake_case)]
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct UpdateCategoryV1 {
+    pub id: Uuid,
+    pub userId: Uuid,
+    pub name: String,
+    pub description: String,
+}
+
+#[allow(non_snake_case)]
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct DeleteCategoryV1 {
+    pub id: Uuid,
+    pub userId: Uuid,
+}
+
+#[allow(non_snake_case)]
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct ListPaymentsV1 {
+    /// Lists this user's personal payments and those of their households.
+    pub userId: Uuid,
+    pub householdId: Option<Uuid>,
+    /// Only payments created at or after this instant.
+    pub from: Option<chrono::DateTime<chrono::Utc>>,
+    /// Only payments created before this instant.
+    pub to: Option<chrono::DateTime<chrono::Utc>>,
+    pub page: Option<usize>,
+    pub limit: Option<usize>,
+    pub withDeleted: Option<bool>,
+}
+
+#[allow(non_snake_case)]
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct GetCategoryV1 {
+    pub id: Uuid,
+    /// The category is only returned if this user can see it.
+    pub userId: Uuid,
+    pub withDeleted: Option<bool>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct DeadLetterFilterOptions {
+    pub page: Option<usize>,
+    pub limit: Option<usize>,
+    /// Also list messages that were already replayed.
+    #[serde(rename = "includeReplayed")]
+    pub include_replayed: Option<bool>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct UpdateDeadLetterSchema {
+    pub payload: String,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ReplayDeadLettersSchema {
+    pub ids: Vec<Uuid>,
 }
@@ -0,0 +1,46 @@
+//! Writes JSON Schema files for the AMQP message envelope, every command
+//! payload and every RPC request, for producers to validate against.
+//!
+//! ```text
+//! amqp-schemas [OUTPUT_DIR]    # defaults to schemas/amqp
+//! ```
+
+use std::{fs, path::PathBuf};
+
+use backend::amqp::{
+    command::{
+        CATEGORY_CREATE, CATEGORY_DELETE, CATEGORY_UPDATE, PAYMENT_CREATE, PAYMENT_DELETE,
+        PAYMENT_UPDATE,
+    },
+    rpc::{CATEGORY_GET, PAYMENTS_LIST},
+    schema::{
+        CreateCategoryV1, CreatePaymentV1, DeleteCategoryV1, DeletePaymentV1, Envelope,
+        GetCategoryV1, ListPaymentsV1, UpdateCategoryV1, UpdatePaymentV1,
+    },
+};
+use schemars::{schema::RootSchema, schema_for};
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let output_dir = PathBuf::from(std::env::args().nth(1).unwrap_or_else(|| "schemas/amqp".to_string()));
+    fs::create_dir_all(&output_dir)?;
+
+    let schemas: [(String, RootSchema); 9] = [
+        ("envelope".to_string(), schema_for!(Envelope)),
+        (format!("{}.v1", PAYMENT_CREATE), schema_for!(CreatePaymentV1)),
+        (format!("{}.v1", PAYMENT_UPDATE), schema_for!(UpdatePaymentV1)),
+        (format!("{}.v1", PAYMENT_DELETE), schema_for!(DeletePaymentV1)),
+        (format!("{}.v1", CATEGORY_CREATE), schema_for!(CreateCategoryV1)),
+        (format!("{}.v1", CATEGORY_UPDATE), schema_for!(UpdateCategoryV1)),
+        (format!("{}.v1", CATEGORY_DELETE), schema_for!(DeleteCategoryV1)),
+        (format!("{}.v1", PAYMENTS_LIST), schema_for!(ListPaymentsV1)),
+        (format!("{}.v1", CATEGORY_GET), schema_for!(GetCategoryV1)),
+    ];
+
+    for (name, schema) in schemas {
+        let path = output_dir.join(format!("{}.schema.json", name));
+        fs::write(&path, serde_json::to_string_pretty(&schema)? + "\n")?;
+        println!("{}", path.display());
+    }
+
+    Ok(())
+}
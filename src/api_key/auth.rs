@@ -0,0 +1,93 @@
+use actix_web::http::Method;
+use sqlx::{Pool, Postgres};
+
+use crate::{api_key::model::ApiKeyModel, token};
+
+/// Lets the key read data.
+pub const SCOPE_READ: &str = "read";
+/// Lets the key create, change and delete data.
+pub const SCOPE_WRITE: &str = "write";
+pub const SCOPES: [&str; 2] = [SCOPE_READ, SCOPE_WRITE];
+
+const KEY_PREFIX: &str = "rf";
+
+/// Returns `(full_key, prefix)`. The prefix is stored in clear so users can
+/// tell their keys apart; the full key is only ever shown once.
+pub fn generate() -> (String, String) {
+    let secret = token::generate();
+    let prefix = secret[..8].to_string();
+    (format!("{}_{}", KEY_PREFIX, secret), prefix)
+}
+
+/// Looks up an active key and records that it was used. `last_used_at` is
+/// only refreshed once a minute to keep busy scripts from writing on every call.
+pub async fn authenticate(db: &Pool<Postgres>, key: &str) -> Result<Option<ApiKeyModel>, sqlx::Error> {
+    let api_key = sqlx::query_as!(
+        ApiKeyModel,
+        "SELECT k.* FROM api_keys k JOIN users u ON u.id = k.user_id
+        WHERE k.key_hash = $1 AND k.revoked_at IS NULL
+        AND (k.expires_at IS NULL OR k.expires_at > NOW())
+        AND u.deleted_at IS NULL",
+        token::hash(key)
+    )
+    .fetch_optional(db)
+    .await?;
+
+    if let Some(api_key) = &api_key {
+        sqlx::query!(
+            "UPDATE api_keys SET last_used_at = NOW()
+            WHERE id = $1 AND (last_used_at IS NULL OR last_used_at < NOW() - INTERVAL '1 minute')",
+            api_key.id
+        )
+        .execute(db)
+        .await?;
+    }
+
+    Ok(api_key)
+}
+
+pub fn allows(scopes: &[String], method: &Method) -> bool {
+    let required = if matches!(*method, Method::GET | Method::HEAD | Method::OPTIONS) {
+        SCOPE_READ
+    } else {
+        SCOPE_WRITE
+    };
+
+    scopes.iter().any(|scope| scope == required)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scopes(names: &[&str]) -> Vec<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    #[test]
+    fn requires_read_for_safe_methods_and_write_for_the_rest() {
+        let cases = [
+            (Method::GET, &[SCOPE_READ][..], true),
+            (Method::HEAD, &[SCOPE_READ][..], true),
+            (Method::OPTIONS, &[SCOPE_READ][..], true),
+            (Method::GET, &[SCOPE_WRITE][..], false),
+            (Method::POST, &[SCOPE_READ][..], false),
+            (Method::PATCH, &[SCOPE_READ][..], false),
+            (Method::DELETE, &[SCOPE_READ][..], false),
+            (Method::POST, &[SCOPE_WRITE][..], true),
+            (Method::PUT, &[SCOPE_WRITE][..], true),
+            (Method::DELETE, &[SCOPE_READ, SCOPE_WRITE][..], true),
+            (Method::GET, &[][..], false),
+        ];
+
+        for (method, granted, expected) in cases {
+            assert_eq!(allows(&scopes(granted), &method), expected, "{} with {:?}", method, granted);
+        }
+    }
+
+    #[test]
+    fn matches_scopes_exactly() {
+        assert!(!allows(&scopes(&["READ", " read"]), &Method::GET));
+        assert!(!allows(&scopes(&["writer"]), &Method::POST));
+    }
+}
@@ -0,0 +1,151 @@
+use crate::{
+    api_key::{ auth, model::ApiKeyModel, schema::CreateApiKeySchema },
+    jwt_auth,
+    token,
+    AppState,
+};
+use actix_web::{ delete, get, post, web, HttpResponse, Responder };
+use serde_json::json;
+
+/// Ten years; also keeps the expiry well inside what `chrono` can represent.
+const MAX_EXPIRES_IN_DAYS: i64 = 3650;
+
+/// Keys can't be used to mint or revoke other keys, and neither can an
+/// impersonating admin.
+fn reject_api_key_auth(jwt: &jwt_auth::JwtMiddleware) -> Option<HttpResponse> {
+    (!jwt.is_session()).then(|| {
+        HttpResponse::Forbidden().json(
+            json!({"status": "fail","message": "API keys can only be managed with your own session token"})
+        )
+    })
+}
+
+#[get("/")]
+async fn api_key_list_handler(
+    data: web::Data<AppState>,
+    jwt: jwt_auth::JwtMiddleware
+) -> impl Responder {
+    if let Some(response) = reject_api_key_auth(&jwt) {
+        return response;
+    }
+
+    let query_result = sqlx
+        ::query_as!(
+            ApiKeyModel,
+            "SELECT * FROM api_keys WHERE user_id = $1 ORDER BY created_at DESC",
+            jwt.user_id
+        )
+        .fetch_all(&data.db).await;
+
+    match query_result {
+        Ok(keys) => {
+            let json_response =
+                serde_json::json!({
+                "status": "success",
+                "results": keys.len(),
+                "apiKeys": keys
+            });
+            HttpResponse::Ok().json(json_response)
+        }
+        Err(err) => {
+            let message = format!("Error: {:?}", err);
+            HttpResponse::InternalServerError().json(json!({"status": "error","message": message}))
+        }
+    }
+}
+
+#[post("/")]
+async fn create_api_key_handler(
+    body: web::Json<CreateApiKeySchema>,
+    data: web::Data<AppState>,
+    jwt: jwt_auth::JwtMiddleware
+) -> impl Responder {
+    if let Some(response) = reject_api_key_auth(&jwt) {
+        return response;
+    }
+
+    if body.scopes.is_empty() || body.scopes.iter().any(|scope| !auth::SCOPES.contains(&scope.as_str())) {
+        let message = format!("Scopes must be a non-empty subset of {:?}", auth::SCOPES);
+        return HttpResponse::BadRequest().json(json!({"status": "fail","message": message}));
+    }
+
+    if body.expiresInDays.is_some_and(|days| !(1..=MAX_EXPIRES_IN_DAYS).contains(&days)) {
+        let message = format!("expiresInDays must be between 1 and {}", MAX_EXPIRES_IN_DAYS);
+        return HttpResponse::BadRequest().json(json!({"status": "fail","message": message}));
+    }
+
+    let expires_at = body.expiresInDays.map(|days| chrono::Utc::now() + chrono::Duration::days(days));
+    let (key, prefix) = auth::generate();
+
+    let query_result = sqlx
+        ::query_as!(
+            ApiKeyModel,
+            "INSERT INTO api_keys (user_id, name, prefix, key_hash, scopes, expires_at)
+            VALUES ($1, $2, $3, $4, $5, $6) RETURNING *",
+            jwt.user_id,
+            body.name,
+            prefix,
+            token::hash(&key),
+            &body.scopes,
+            expires_at
+        )
+        .fetch_one(&data.db).await;
+
+    match query_result {
+        Ok(api_key) => {
+            let api_key_response =
+                serde_json::json!({"status": "success","data": serde_json::json!({
+                "apiKey": api_key,
+                "key": key
+            })});
+
+            HttpResponse::Ok().json(api_key_response)
+        }
+        Err(err) => {
+            let message = format!("Error: {:?}", err);
+            HttpResponse::InternalServerError().json(json!({"status": "error","message": message}))
+        }
+    }
+}
+
+#[delete("/{id}")]
+async fn revoke_api_key_handler(
+    path: web::Path<uuid::Uuid>,
+    data: web::Data<AppState>,
+    jwt: jwt_auth::JwtMiddleware
+) -> impl Responder {
+    if let Some(response) = reject_api_key_auth(&jwt) {
+        return response;
+    }
+
+    let api_key_id = path.into_inner();
+    let query_result = sqlx
+        ::query!(
+            "UPDATE api_keys SET revoked_at = NOW() WHERE id = $1 AND user_id = $2 AND revoked_at IS NULL",
+            api_key_id,
+            jwt.user_id
+        )
+        .execute(&data.db).await;
+
+    match query_result {
+        Ok(result) if result.rows_affected() == 0 => {
+            let message = format!("API key with ID: {} not found", api_key_id);
+            HttpResponse::NotFound().json(json!({"status": "fail","message": message}))
+        }
+        Ok(_) => HttpResponse::NoContent().finish(),
+        Err(err) => {
+            let message = format!("Error: {:?}", err);
+            HttpResponse::InternalServerError().json(json!({"status": "error","message": message}))
+        }
+    }
+}
+
+pub fn config(conf: &mut web::ServiceConfig) {
+    let scope = web
+        ::scope("/api-keys")
+        .service(api_key_list_handler)
+        .service(create_api_key_handler)
+        .service(revoke_api_key_handler);
+
+    conf.service(scope);
+}
@@ -0,0 +1,9 @@
+use serde::{Deserialize, Serialize};
+
+#[allow(non_snake_case)]
+#[derive(Serialize, Deserialize, Debug)]
+pub struct CreateApiKeySchema {
+    pub name: String,
+    pub scopes: Vec<String>,
+    pub expiresInDays: Option<i64>,
+}
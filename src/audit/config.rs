@@ -0,0 +1,28 @@
+use lazy_static::lazy_static;
+use std::{env, net::IpAddr};
+
+lazy_static! {
+    static ref AUDIT_CONFIG: Config = Config::from_env();
+}
+
+pub fn get_config() -> &'static Config {
+    &AUDIT_CONFIG
+}
+
+#[derive(Debug, Clone)]
+pub struct Config {
+    /// Reverse proxies whose `Forwarded`/`X-Forwarded-For` headers are
+    /// believed. Requests from anywhere else are recorded with the peer
+    /// address, since those headers are client-controlled.
+    pub trusted_proxies: Vec<IpAddr>,
+}
+
+impl Config {
+    fn from_env() -> Self {
+        Self {
+            trusted_proxies: env::var("TRUSTED_PROXIES")
+                .map(|v| v.split(',').filter_map(|ip| ip.trim().parse().ok()).collect())
+                .unwrap_or_default(),
+        }
+    }
+}
@@ -0,0 +1,88 @@
+use crate::{
+    audit::{ log, schema::AuditFilterOptions },
+    jwt_auth,
+    user::role,
+    AppState,
+};
+use actix_web::{ get, http::header, web, HttpResponse, Responder };
+use serde_json::json;
+
+#[get("/")]
+async fn audit_list_handler(
+    opts: web::Query<AuditFilterOptions>,
+    data: web::Data<AppState>,
+    jwt: jwt_auth::JwtMiddleware
+) -> impl Responder {
+    if !role::is_admin(&data.db, jwt.user_id).await {
+        return HttpResponse::Forbidden().json(
+            json!({"status": "fail","message": "Only administrators can read the audit log"})
+        );
+    }
+
+    let limit = opts.limit.unwrap_or(50);
+    let offset = (opts.page.unwrap_or(1) - 1) * limit;
+
+    let query_result = log::search(&data.db, &opts, Some(limit as i64), offset as i64).await;
+
+    match query_result {
+        Ok(entries) => {
+            let json_response =
+                serde_json::json!({
+                "status": "success",
+                "results": entries.len(),
+                "entries": entries
+            });
+            HttpResponse::Ok().json(json_response)
+        }
+        Err(err) => {
+            let message = format!("Error: {:?}", err);
+            HttpResponse::InternalServerError().json(json!({"status": "error","message": message}))
+        }
+    }
+}
+
+#[get("/export")]
+async fn audit_export_handler(
+    opts: web::Query<AuditFilterOptions>,
+    data: web::Data<AppState>,
+    jwt: jwt_auth::JwtMiddleware
+) -> impl Responder {
+    if !role::is_admin(&data.db, jwt.user_id).await {
+        return HttpResponse::Forbidden().json(
+            json!({"status": "fail","message": "Only administrators can export the audit log"})
+        );
+    }
+
+    let query_result = log::search(&data.db, &opts, None, 0).await;
+
+    match query_result {
+        Ok(entries) => {
+            let mut body = String::new();
+            for entry in entries {
+                body.push_str(&serde_json::to_string(&entry).unwrap());
+                body.push('\n');
+            }
+
+            HttpResponse::Ok()
+                .content_type("application/x-ndjson")
+                .insert_header((
+                    header::CONTENT_DISPOSITION,
+                    "attachment; filename=\"audit-log.jsonl\"",
+                ))
+                .body(body)
+        }
+        Err(err) => {
+            let message = format!("Error: {:?}", err);
+            HttpResponse::InternalServerError().json(json!({"status": "error","message": message}))
+        }
+    }
+}
+
+pub fn config(conf: &mut web::ServiceConfig) {
+    let scope = web
+        ::scope("/audit")
+        .service(audit_list_handler)
+        .service(audit_export_handler);
+
+    conf.service(scope);
+}
@@ -0,0 +1,140 @@
+use actix_web::{http::header, HttpRequest};
+use serde_json::Value;
+use sqlx::{Pool, Postgres};
+use uuid::Uuid;
+
+use crate::audit::{config::get_config, model::AuditLogModel, schema::AuditFilterOptions};
+
+#[derive(Debug, Clone, Copy)]
+pub enum AuditEvent {
+    Login,
+    Logout,
+    PasswordChange,
+    RoleChange,
+    TokenRefresh,
+    AccountUnlock,
+    PasswordResetRequest,
+    MfaChallenge,
+    MfaEnabled,
+    MfaDisabled,
+    RecoveryCodesRegenerated,
+    DataExport,
+    AccountErasure,
+    Impersonation,
+}
+
+impl AuditEvent {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AuditEvent::Login => "login",
+            AuditEvent::Logout => "logout",
+            AuditEvent::PasswordChange => "password_change",
+            AuditEvent::RoleChange => "role_change",
+            AuditEvent::TokenRefresh => "token_refresh",
+            AuditEvent::AccountUnlock => "account_unlock",
+            AuditEvent::PasswordResetRequest => "password_reset_request",
+            AuditEvent::MfaChallenge => "mfa_challenge",
+            AuditEvent::MfaEnabled => "mfa_enabled",
+            AuditEvent::MfaDisabled => "mfa_disabled",
+            AuditEvent::RecoveryCodesRegenerated => "recovery_codes_regenerated",
+            AuditEvent::DataExport => "data_export",
+            AuditEvent::AccountErasure => "account_erasure",
+            AuditEvent::Impersonation => "impersonation",
+        }
+    }
+}
+
+/// Client details captured alongside every audit entry.
+#[derive(Debug, Clone, Default)]
+pub struct RequestMeta {
+    pub ip: Option<String>,
+    pub user_agent: Option<String>,
+}
+
+impl RequestMeta {
+    pub fn from_request(req: &HttpRequest) -> Self {
+        Self {
+            ip: client_ip(req),
+            user_agent: req
+                .headers()
+                .get(header::USER_AGENT)
+                .and_then(|h| h.to_str().ok())
+                .map(str::to_string),
+        }
+    }
+}
+
+/// The peer address, or the forwarded client address when the peer is one
+/// of the configured trusted proxies.
+fn client_ip(req: &HttpRequest) -> Option<String> {
+    let peer = req.peer_addr()?.ip();
+
+    if get_config().trusted_proxies.contains(&peer) {
+        if let Some(ip) = req.connection_info().realip_remote_addr() {
+            return Some(ip.to_string());
+        }
+    }
+
+    Some(peer.to_string())
+}
+
+pub struct AuditEntry<'a> {
+    pub event: AuditEvent,
+    pub success: bool,
+    pub user_id: Option<Uuid>,
+    pub email: Option<&'a str>,
+    pub meta: &'a RequestMeta,
+    pub metadata: Value,
+}
+
+/// Appends an entry to the audit log. Failures are logged rather than
+/// returned so that auditing never blocks the request being audited.
+pub async fn record(db: &Pool<Postgres>, entry: AuditEntry<'_>) {
+    let result = sqlx::query!(
+        "INSERT INTO audit_logs (event, success, user_id, email, ip, user_agent, metadata)
+        VALUES ($1, $2, $3, $4, $5, $6, $7)",
+        entry.event.as_str(),
+        entry.success,
+        entry.user_id,
+        entry.email,
+        entry.meta.ip,
+        entry.meta.user_agent,
+        entry.metadata
+    )
+    .execute(db)
+    .await;
+
+    if let Err(err) = result {
+        log::error!("Failed to write {} audit entry: {}", entry.event.as_str(), err);
+    }
+}
+
+pub async fn search(
+    db: &Pool<Postgres>,
+    opts: &AuditFilterOptions,
+    limit: Option<i64>,
+    offset: i64,
+) -> Result<Vec<AuditLogModel>, sqlx::Error> {
+    sqlx::query_as!(
+        AuditLogModel,
+        "SELECT * FROM audit_logs
+        WHERE ($1::text IS NULL OR event = $1)
+        AND ($2::uuid IS NULL OR user_id = $2)
+        AND ($3::text IS NULL OR email = $3)
+        AND ($4::bool IS NULL OR success = $4)
+        AND ($5::timestamptz IS NULL OR created_at >= $5)
+        AND ($6::timestamptz IS NULL OR created_at < $6)
+        ORDER BY created_at DESC
+        LIMIT $7 OFFSET $8",
+        opts.event,
+        opts.user_id,
+        opts.email,
+        opts.success,
+        opts.from,
+        opts.to,
+        limit,
+        offset
+    )
+    .fetch_all(db)
+    .await
+}
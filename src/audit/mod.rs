@@ -0,0 +1,5 @@
+pub mod config;
+pub mod handler;
+pub mod log;
+pub mod model;
+pub mod schema;
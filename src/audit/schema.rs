@@ -0,0 +1,15 @@
+use serde::Deserialize;
+use uuid::Uuid;
+
+#[derive(Deserialize, Debug)]
+pub struct AuditFilterOptions {
+    pub page: Option<usize>,
+    pub limit: Option<usize>,
+    pub event: Option<String>,
+    #[serde(rename = "userId")]
+    pub user_id: Option<Uuid>,
+    pub email: Option<String>,
+    pub success: Option<bool>,
+    pub from: Option<chrono::DateTime<chrono::Utc>>,
+    pub to: Option<chrono::DateTime<chrono::Utc>>,
+}
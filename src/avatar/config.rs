@@ -0,0 +1,45 @@
+use lazy_static::lazy_static;
+use std::env;
+
+lazy_static! {
+    static ref AVATAR_CONFIG: Config = Config::from_env();
+}
+
+pub fn get_config() -> &'static Config {
+    &AVATAR_CONFIG
+}
+
+#[derive(Debug, Clone)]
+pub struct Config {
+    pub storage_dir: String,
+    pub max_upload_bytes: usize,
+    pub max_dimension: u32,
+    /// Square sizes generated for every upload, in pixels.
+    pub sizes: Vec<u32>,
+    pub cache_max_age_secs: u64,
+}
+
+impl Config {
+    fn from_env() -> Self {
+        Self {
+            storage_dir: env::var("AVATAR_STORAGE_DIR").unwrap_or_else(|_| "./storage/avatars".to_string()),
+            max_upload_bytes: env::var("AVATAR_MAX_UPLOAD_BYTES")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(5 * 1024 * 1024),
+            max_dimension: env::var("AVATAR_MAX_DIMENSION")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(8000),
+            sizes: env::var("AVATAR_SIZES")
+                .ok()
+                .map(|v| v.split(',').filter_map(|s| s.trim().parse().ok()).collect())
+                .filter(|sizes: &Vec<u32>| !sizes.is_empty())
+                .unwrap_or_else(|| vec![64, 128, 256]),
+            cache_max_age_secs: env::var("AVATAR_CACHE_MAX_AGE_SECS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(86400),
+        }
+    }
+}
@@ -0,0 +1,263 @@
+use crate::{
+    avatar::{ config::get_config, processing },
+    jwt_auth,
+    user::model::UserModel,
+    AppState,
+};
+use actix_multipart::Multipart;
+use actix_web::{
+    delete,
+    get,
+    http::header,
+    post,
+    web,
+    HttpRequest,
+    HttpResponse,
+    Responder,
+};
+use futures_util::StreamExt;
+use serde::Deserialize;
+use serde_json::json;
+
+#[derive(Deserialize, Debug)]
+pub struct AvatarOptions {
+    pub size: Option<u32>,
+    /// The avatar version the URL was built for, see [`avatar_urls`].
+    pub v: Option<String>,
+}
+
+fn avatar_key(user_id: uuid::Uuid, version: &str, size: u32) -> String {
+    format!("{}/{}/{}.jpg", user_id, version, size)
+}
+
+fn avatar_urls(user_id: uuid::Uuid, version: &str) -> serde_json::Value {
+    let urls: serde_json::Map<String, serde_json::Value> = get_config().sizes
+        .iter()
+        .map(|size| {
+            (size.to_string(), json!(format!("/users/{}/avatar?size={}&v={}", user_id, size, version)))
+        })
+        .collect();
+    serde_json::Value::Object(urls)
+}
+
+#[post("/me/avatar")]
+pub async fn upload_avatar_handler(
+    mut payload: Multipart,
+    data: web::Data<AppState>,
+    jwt: jwt_auth::JwtMiddleware
+) -> impl Responder {
+    let max_bytes = get_config().max_upload_bytes;
+    let mut bytes: Vec<u8> = Vec::new();
+    let mut found = false;
+
+    while let Some(item) = payload.next().await {
+        let mut field = match item {
+            Ok(field) => field,
+            Err(err) => {
+                let message = format!("Invalid multipart payload: {}", err);
+                return HttpResponse::BadRequest().json(json!({"status": "fail","message": message}));
+            }
+        };
+
+        if field.name() != "avatar" {
+            continue;
+        }
+        found = true;
+
+        while let Some(chunk) = field.next().await {
+            let chunk = match chunk {
+                Ok(chunk) => chunk,
+                Err(err) => {
+                    let message = format!("Invalid multipart payload: {}", err);
+                    return HttpResponse::BadRequest().json(json!({"status": "fail","message": message}));
+                }
+            };
+
+            if bytes.len() + chunk.len() > max_bytes {
+                let message = format!("Avatar must be at most {} bytes", max_bytes);
+                return HttpResponse::PayloadTooLarge().json(json!({"status": "fail","message": message}));
+            }
+            bytes.extend_from_slice(&chunk);
+        }
+        break;
+    }
+
+    if !found || bytes.is_empty() {
+        return HttpResponse::BadRequest().json(
+            json!({"status": "fail","message": "Send the image in an `avatar` multipart field"})
+        );
+    }
+
+    let rendered = match web::block(move || processing::process(&bytes)).await {
+        Ok(Ok(rendered)) => rendered,
+        Ok(Err(message)) => {
+            return HttpResponse::UnprocessableEntity().json(json!({"status": "fail","message": message}));
+        }
+        Err(err) => {
+            let message = format!("Error: {:?}", err);
+            return HttpResponse::InternalServerError().json(json!({"status": "error","message": message}));
+        }
+    };
+
+    let previous = sqlx
+        ::query_scalar!("SELECT avatar_version FROM users WHERE id = $1 AND deleted_at IS NULL", jwt.user_id)
+        .fetch_optional(&data.db).await;
+    let previous = match previous {
+        Ok(Some(previous)) => previous,
+        Ok(None) => {
+            let message = format!("User with ID: {} not found", jwt.user_id);
+            return HttpResponse::NotFound().json(json!({"status": "fail","message": message}));
+        }
+        Err(err) => {
+            let message = format!("Error: {:?}", err);
+            return HttpResponse::InternalServerError().json(json!({"status": "error","message": message}));
+        }
+    };
+
+    let version = uuid::Uuid::new_v4().simple().to_string();
+    for (size, image) in rendered {
+        if let Err(err) = data.avatar_storage.put(&avatar_key(jwt.user_id, &version, size), image).await {
+            let message = format!("Error: {:?}", err);
+            return HttpResponse::InternalServerError().json(json!({"status": "error","message": message}));
+        }
+    }
+
+    let query_result = sqlx
+        ::query_as!(
+            UserModel,
+            "UPDATE users SET avatar_version = $1, updated_at = NOW() WHERE id = $2 RETURNING *",
+            version,
+            jwt.user_id
+        )
+        .fetch_one(&data.db).await;
+
+    match query_result {
+        Ok(user) => {
+            if let Some(previous) = previous {
+                let prefix = format!("{}/{}", jwt.user_id, previous);
+                if let Err(err) = data.avatar_storage.delete_prefix(&prefix).await {
+                    log::error!("Failed to delete previous avatar: {}", err);
+                }
+            }
+
+            HttpResponse::Ok().json(json!({
+                "status": "success",
+                "data": {"user": user, "avatar": avatar_urls(jwt.user_id, &version)}
+            }))
+        }
+        Err(err) => {
+            let message = format!("Error: {:?}", err);
+            HttpResponse::InternalServerError().json(json!({"status": "error","message": message}))
+        }
+    }
+}
+
+#[delete("/me/avatar")]
+pub async fn delete_avatar_handler(
+    data: web::Data<AppState>,
+    jwt: jwt_auth::JwtMiddleware
+) -> impl Responder {
+    let query_result = sqlx
+        ::query_scalar!(
+            "WITH previous AS (
+                SELECT id, avatar_version FROM users WHERE id = $1 AND deleted_at IS NULL FOR UPDATE
+            )
+            UPDATE users SET avatar_version = NULL, updated_at = NOW()
+            FROM previous WHERE users.id = previous.id
+            RETURNING previous.avatar_version",
+            jwt.user_id
+        )
+        .fetch_optional(&data.db).await;
+
+    match query_result {
+        Ok(Some(Some(previous))) => {
+            let prefix = format!("{}/{}", jwt.user_id, previous);
+            if let Err(err) = data.avatar_storage.delete_prefix(&prefix).await {
+                log::error!("Failed to delete avatar: {}", err);
+            }
+            HttpResponse::NoContent().finish()
+        }
+        Ok(_) => {
+            HttpResponse::NotFound().json(json!({"status": "fail","message": "No avatar to delete"}))
+        }
+        Err(err) => {
+            let message = format!("Error: {:?}", err);
+            HttpResponse::InternalServerError().json(json!({"status": "error","message": message}))
+        }
+    }
+}
+
+/// Public so avatars work in plain `<img>` tags. Each upload gets a new
+/// version, so URLs naming the current version are cached aggressively; any
+/// other URL is revalidated by ETag on every use.
+#[get("/{id}/avatar")]
+pub async fn get_avatar_handler(
+    req: HttpRequest,
+    path: web::Path<uuid::Uuid>,
+    opts: web::Query<AvatarOptions>,
+    data: web::Data<AppState>
+) -> impl Responder {
+    let config = get_config();
+    let user_id = path.into_inner();
+
+    let version = sqlx
+        ::query_scalar!("SELECT avatar_version FROM users WHERE id = $1 AND deleted_at IS NULL", user_id)
+        .fetch_optional(&data.db).await;
+    let version = match version {
+        Ok(Some(Some(version))) => version,
+        Ok(_) => {
+            return HttpResponse::NotFound().json(json!({"status": "fail","message": "Avatar not found"}));
+        }
+        Err(err) => {
+            let message = format!("Error: {:?}", err);
+            return HttpResponse::InternalServerError().json(json!({"status": "error","message": message}));
+        }
+    };
+
+    // Smallest rendered size that is at least as large as requested.
+    let requested = opts.size.unwrap_or(128);
+    let mut sizes = config.sizes.clone();
+    sizes.sort_unstable();
+    let size = sizes
+        .iter()
+        .copied()
+        .find(|size| *size >= requested)
+        .or_else(|| sizes.last().copied())
+        .unwrap_or(requested);
+
+    let etag = format!("\"{}-{}\"", version, size);
+    let cache_control = if opts.v.as_deref() == Some(version.as_str()) {
+        format!("public, max-age={}", config.cache_max_age_secs)
+    } else {
+        "no-cache".to_string()
+    };
+
+    let not_modified = req
+        .headers()
+        .get(header::IF_NONE_MATCH)
+        .and_then(|h| h.to_str().ok())
+        .is_some_and(|value| value.split(',').any(|tag| tag.trim() == etag));
+    if not_modified {
+        return HttpResponse::NotModified()
+            .insert_header((header::ETAG, etag))
+            .insert_header((header::CACHE_CONTROL, cache_control))
+            .finish();
+    }
+
+    match data.avatar_storage.get(&avatar_key(user_id, &version, size)).await {
+        Ok(Some(image)) => {
+            HttpResponse::Ok()
+                .content_type("image/jpeg")
+                .insert_header((header::ETAG, etag))
+                .insert_header((header::CACHE_CONTROL, cache_control))
+                .body(image)
+        }
+        Ok(None) => {
+            HttpResponse::NotFound().json(json!({"status": "fail","message": "Avatar not found"}))
+        }
+        Err(err) => {
+            let message = format!("Error: {:?}", err);
+            HttpResponse::InternalServerError().json(json!({"status": "error","message": message}))
+        }
+    }
+}
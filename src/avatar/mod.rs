@@ -0,0 +1,4 @@
+pub mod config;
+pub mod handler;
+pub mod processing;
+pub mod storage;
@@ -0,0 +1,99 @@
+use std::io::Cursor;
+
+use image::{imageops::FilterType, io::Reader as ImageReader, DynamicImage, ImageFormat, ImageOutputFormat};
+
+use crate::avatar::config::get_config;
+
+const ACCEPTED_FORMATS: [ImageFormat; 4] = [ImageFormat::Png, ImageFormat::Jpeg, ImageFormat::WebP, ImageFormat::Gif];
+
+/// Validates an upload and renders it as square JPEGs, one per configured
+/// size. Re-encoding from decoded pixels drops EXIF and any other metadata.
+pub fn process(bytes: &[u8]) -> Result<Vec<(u32, Vec<u8>)>, String> {
+    let config = get_config();
+
+    let format = image::guess_format(bytes).map_err(|_| "Unrecognized image format".to_string())?;
+    if !ACCEPTED_FORMATS.contains(&format) {
+        return Err("Avatar must be a PNG, JPEG, WebP or GIF image".to_string());
+    }
+
+    // Check dimensions from the header before decoding so oversized images
+    // are rejected without allocating their pixels.
+    let (width, height) = ImageReader::with_format(Cursor::new(bytes), format)
+        .into_dimensions()
+        .map_err(|_| "Unable to read image dimensions".to_string())?;
+    if width > config.max_dimension || height > config.max_dimension {
+        return Err(format!("Avatar must be at most {0}x{0} pixels", config.max_dimension));
+    }
+
+    let image = image::load_from_memory_with_format(bytes, format)
+        .map_err(|_| "Unable to decode image".to_string())?;
+
+    let side = width.min(height);
+    let square = image.crop_imm((width - side) / 2, (height - side) / 2, side, side);
+    let square = DynamicImage::ImageRgb8(square.to_rgb8());
+
+    config
+        .sizes
+        .iter()
+        .map(|size| {
+            let mut encoded = Vec::new();
+            square
+                .resize_exact(*size, *size, FilterType::Lanczos3)
+                .write_to(&mut Cursor::new(&mut encoded), ImageOutputFormat::Jpeg(85))
+                .map_err(|err| format!("Unable to encode avatar: {}", err))?;
+            Ok((*size, encoded))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encode(image: DynamicImage, format: ImageOutputFormat) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        image.write_to(&mut Cursor::new(&mut bytes), format).unwrap();
+        bytes
+    }
+
+    #[test]
+    fn renders_a_square_jpeg_per_configured_size() {
+        let upload = encode(DynamicImage::new_rgb8(300, 200), ImageOutputFormat::Png);
+
+        let rendered = process(&upload).unwrap();
+
+        let sizes: Vec<u32> = rendered.iter().map(|(size, _)| *size).collect();
+        assert_eq!(sizes, get_config().sizes);
+        for (size, bytes) in rendered {
+            assert_eq!(image::guess_format(&bytes).unwrap(), ImageFormat::Jpeg);
+            let image = image::load_from_memory(&bytes).unwrap();
+            assert_eq!((image.width(), image.height()), (size, size));
+        }
+    }
+
+    #[test]
+    fn rejects_unrecognized_bytes() {
+        assert_eq!(process(b"not an image"), Err("Unrecognized image format".to_string()));
+    }
+
+    #[test]
+    fn rejects_formats_outside_the_allow_list() {
+        let upload = encode(DynamicImage::new_rgb8(16, 16), ImageOutputFormat::Bmp);
+
+        assert_eq!(
+            process(&upload),
+            Err("Avatar must be a PNG, JPEG, WebP or GIF image".to_string())
+        );
+    }
+
+    #[test]
+    fn rejects_images_larger_than_the_maximum_dimension() {
+        let max = get_config().max_dimension;
+        let upload = encode(DynamicImage::new_luma8(max + 1, 1), ImageOutputFormat::Png);
+
+        assert_eq!(
+            process(&upload),
+            Err(format!("Avatar must be at most {0}x{0} pixels", max))
+        );
+    }
+}
@@ -0,0 +1,62 @@
+use std::{error::Error, io::ErrorKind, path::PathBuf, sync::Arc};
+
+use async_trait::async_trait;
+
+use crate::avatar::config::get_config;
+
+/// Where processed avatar images live. Keys are `/`-separated relative paths.
+#[async_trait]
+pub trait AvatarStorage: Send + Sync {
+    async fn put(&self, key: &str, bytes: Vec<u8>) -> Result<(), Box<dyn Error + Send + Sync>>;
+    async fn get(&self, key: &str) -> Result<Option<Vec<u8>>, Box<dyn Error + Send + Sync>>;
+    /// Removes every object under `prefix`.
+    async fn delete_prefix(&self, prefix: &str) -> Result<(), Box<dyn Error + Send + Sync>>;
+}
+
+pub fn from_config() -> Arc<dyn AvatarStorage> {
+    Arc::new(LocalDiskStorage::new(get_config().storage_dir.clone()))
+}
+
+pub struct LocalDiskStorage {
+    root: PathBuf,
+}
+
+impl LocalDiskStorage {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    fn path(&self, key: &str) -> Result<PathBuf, Box<dyn Error + Send + Sync>> {
+        if key.split('/').any(|part| part.is_empty() || part == "." || part == "..") {
+            return Err(format!("Invalid storage key: {}", key).into());
+        }
+        Ok(self.root.join(key))
+    }
+}
+
+#[async_trait]
+impl AvatarStorage for LocalDiskStorage {
+    async fn put(&self, key: &str, bytes: Vec<u8>) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let path = self.path(key)?;
+        if let Some(parent) = path.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        tokio::fs::write(path, bytes).await?;
+        Ok(())
+    }
+
+    async fn get(&self, key: &str) -> Result<Option<Vec<u8>>, Box<dyn Error + Send + Sync>> {
+        match tokio::fs::read(self.path(key)?).await {
+            Ok(bytes) => Ok(Some(bytes)),
+            Err(err) if err.kind() == ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    async fn delete_prefix(&self, prefix: &str) -> Result<(), Box<dyn Error + Send + Sync>> {
+        match tokio::fs::remove_dir_all(self.path(prefix)?).await {
+            Err(err) if err.kind() != ErrorKind::NotFound => Err(err.into()),
+            _ => Ok(()),
+        }
+    }
+}
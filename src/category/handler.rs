@@ -1,17 +1,18 @@
 use crate::{
+    history::recorder::{ self, Entity },
+    household::access::{ self, Access, MemberRole },
     jwt_auth,
     category::model::CategoryModel,
+    category::service::{ self, CategoryQuery },
     category::schema::{
-        LoginCategorySchema,
-        TokenClaims,
         CreateCategorySchema,
         FilterOptions,
         UpdateCategorySchema,
+        VisibilityOptions,
     },
     AppState,
 };
 use actix_web::{
-    cookie::{ time::Duration as ActixWebDuration, Cookie },
     delete,
     get,
     patch,
@@ -20,26 +21,25 @@ use actix_web::{
     HttpResponse,
     Responder,
 };
-use chrono::prelude::*;
 use serde_json::json;
-use sqlx::Row;
 
 #[get("/")]
 pub async fn category_list_handler(
     opts: web::Query<FilterOptions>,
     data: web::Data<AppState>,
-    _: jwt_auth::JwtMiddleware
+    jwt: jwt_auth::JwtMiddleware
 ) -> impl Responder {
     let limit = opts.limit.unwrap_or(10);
     let offset = (opts.page.unwrap_or(1) - 1) * limit;
+    let with_deleted = opts.with_deleted.unwrap_or(false);
 
-    let query_result = sqlx::query_as!(
-            CategoryModel,
-            "SELECT * FROM categories ORDER by id LIMIT $1 OFFSET $2",
-            limit as i32,
-            offset as i32
-        )
-        .fetch_all(&data.db).await;
+    let query = CategoryQuery {
+        limit: limit as i64,
+        offset: offset as i64,
+        with_deleted,
+        household_id: opts.household_id,
+    };
+    let query_result = service::list_categories(&data.db, jwt.user_id, &query).await;
 
     if query_result.is_err() {
         let message = "Something bad happened while fetching all category items";
@@ -62,17 +62,22 @@ pub async fn category_list_handler(
 #[post("/")]
 async fn create_category_handler(
     body: web::Json<CreateCategorySchema>,
-    data: web::Data<AppState>
+    data: web::Data<AppState>,
+    jwt: jwt_auth::JwtMiddleware
 ) -> impl Responder {
-    let query_result = sqlx
-        ::query_as!(
-            CategoryModel,
-            "INSERT INTO categories (name,description,user_id) VALUES ($1, $2, $3) RETURNING *",
-            body.name,
-            body.description,
-            body.userId
-        )
-        .fetch_one(&data.db).await;
+    if body.userId != jwt.user_id {
+        return HttpResponse::Forbidden().json(
+            serde_json::json!({"status": "fail","message": "Categories can only be created for yourself"})
+        );
+    }
+
+    if let Some(household_id) = body.householdId {
+        if let Err(response) = access::require_member(&data.db, household_id, jwt.user_id, MemberRole::Editor).await {
+            return response;
+        }
+    }
+
+    let query_result = service::create_category(&data.db, &body, jwt.user_id).await;
 
     match query_result {
         Ok(category) => {
@@ -81,41 +86,92 @@ async fn create_category_handler(
                 "category": category
             })});
 
-            return HttpResponse::Ok().json(category_response);
+            HttpResponse::Ok().json(category_response)
         }
         Err(e) => {
-            return HttpResponse::InternalServerError().json(
+            HttpResponse::InternalServerError().json(
                 serde_json::json!({"status": "error","message": format!("{:?}", e)})
-            );
+            )
         }
     }
 }
 
+#[get("/trash")]
+async fn category_trash_handler(
+    opts: web::Query<FilterOptions>,
+    data: web::Data<AppState>,
+    jwt: jwt_auth::JwtMiddleware
+) -> impl Responder {
+    let limit = opts.limit.unwrap_or(10);
+    let offset = (opts.page.unwrap_or(1) - 1) * limit;
+
+    let query_result = sqlx::query_as!(
+            CategoryModel,
+            "SELECT * FROM categories WHERE deleted_at IS NOT NULL
+            AND ((household_id IS NULL AND user_id = $1)
+                OR household_id IN (SELECT household_id FROM household_members WHERE user_id = $1))
+            AND ($4::uuid IS NULL OR household_id = $4)
+            ORDER by deleted_at DESC LIMIT $2 OFFSET $3",
+            jwt.user_id,
+            limit as i32,
+            offset as i32,
+            opts.household_id
+        )
+        .fetch_all(&data.db).await;
+
+    if query_result.is_err() {
+        let message = "Something bad happened while fetching trashed category items";
+        return HttpResponse::InternalServerError().json(
+            json!({"status": "error","message": message})
+        );
+    }
+
+    let categories = query_result.unwrap();
+
+    let json_response =
+        serde_json::json!({
+        "status": "success",
+        "results": categories.len(),
+        "categories": categories
+    });
+    HttpResponse::Ok().json(json_response)
+}
+
 #[get("/{id}")]
 async fn get_category_handler(
     path: web::Path<uuid::Uuid>,
+    opts: web::Query<VisibilityOptions>,
     data: web::Data<AppState>,
-    _: jwt_auth::JwtMiddleware
+    jwt: jwt_auth::JwtMiddleware
 ) -> impl Responder {
     let category_id = path.into_inner();
-    let query_result = sqlx
-        ::query_as!(CategoryModel, "SELECT * FROM categories WHERE id = $1", category_id)
-        .fetch_one(&data.db).await;
+    if let Err(response) = access::require(&data.db, Entity::Category, category_id, jwt.user_id, Access::Read).await {
+        return response;
+    }
+
+    let with_deleted = opts.with_deleted.unwrap_or(false);
+    let query_result = service::find_category(&data.db, category_id, with_deleted).await;
 
     match query_result {
-        Ok(category) => {
+        Ok(Some(category)) => {
             let category_response =
                 serde_json::json!({"status": "success","data": serde_json::json!({
                 "category": category
             })});
 
-            return HttpResponse::Ok().json(category_response);
+            HttpResponse::Ok().json(category_response)
         }
-        Err(_) => {
+        Ok(None) => {
             let message = format!("Category with ID: {} not found", category_id);
-            return HttpResponse::NotFound().json(
+            HttpResponse::NotFound().json(
                 serde_json::json!({"status": "fail","message": message})
-            );
+            )
+        }
+        Err(err) => {
+            let message = format!("Error: {:?}", err);
+            HttpResponse::InternalServerError().json(
+                serde_json::json!({"status": "error","message": message})
+            )
         }
     }
 }
@@ -125,47 +181,35 @@ async fn edit_category_handler(
     path: web::Path<uuid::Uuid>,
     body: web::Json<UpdateCategorySchema>,
     data: web::Data<AppState>,
-    _: jwt_auth::JwtMiddleware
+    jwt: jwt_auth::JwtMiddleware
 ) -> impl Responder {
     let category_id = path.into_inner();
-    let query_result = sqlx
-        ::query_as!(CategoryModel, "SELECT * FROM categories WHERE id = $1", category_id)
-        .fetch_one(&data.db).await;
-
-    if query_result.is_err() {
-        let message = format!("Category with ID: {} not found", category_id);
-        return HttpResponse::NotFound().json(
-            serde_json::json!({"status": "fail","message": message})
-        );
+    if let Err(response) = access::require(&data.db, Entity::Category, category_id, jwt.user_id, Access::Write).await {
+        return response;
     }
 
-    let now = Utc::now();
-    let query_result = sqlx
-        ::query_as!(
-            CategoryModel,
-            "UPDATE categories SET name = $1, description = $2, user_id = $3 updated_at = 4$ WHERE id = $5 RETURNING *",
-            body.name,
-            body.description,
-            body.userId,
-            now,
-            category_id
-        )
-        .fetch_one(&data.db).await;
+    let query_result = service::update_category(&data.db, category_id, &body, jwt.user_id).await;
 
     match query_result {
-        Ok(category) => {
+        Ok(Some(category)) => {
             let category_response =
                 serde_json::json!({"status": "success","data": serde_json::json!({
                 "category": category
             })});
 
-            return HttpResponse::Ok().json(category_response);
+            HttpResponse::Ok().json(category_response)
+        }
+        Ok(None) => {
+            let message = format!("Category with ID: {} not found", category_id);
+            HttpResponse::NotFound().json(
+                serde_json::json!({"status": "fail","message": message})
+            )
         }
         Err(err) => {
             let message = format!("Error: {:?}", err);
-            return HttpResponse::InternalServerError().json(
+            HttpResponse::InternalServerError().json(
                 serde_json::json!({"status": "error","message": message})
-            );
+            )
         }
     }
 }
@@ -174,21 +218,99 @@ async fn edit_category_handler(
 async fn delete_category_handler(
     path: web::Path<uuid::Uuid>,
     data: web::Data<AppState>,
-    _: jwt_auth::JwtMiddleware
+    jwt: jwt_auth::JwtMiddleware
 ) -> impl Responder {
     let category_id = path.into_inner();
-    let rows_affected = sqlx
-        ::query!("DELETE FROM categories WHERE id = $1", category_id)
-        .execute(&data.db).await
-        .unwrap()
-        .rows_affected();
-
-    if rows_affected == 0 {
-        let message = format!("Category with ID: {} not found", category_id);
-        return HttpResponse::NotFound().json(json!({"status": "fail","message": message}));
+    if let Err(response) = access::require(&data.db, Entity::Category, category_id, jwt.user_id, Access::Write).await {
+        return response;
     }
 
-    HttpResponse::NoContent().finish()
+    let query_result = service::set_category_deleted(&data.db, category_id, true, jwt.user_id).await;
+
+    match query_result {
+        Ok(None) => {
+            let message = format!("Category with ID: {} not found", category_id);
+            HttpResponse::NotFound().json(json!({"status": "fail","message": message}))
+        }
+        Ok(Some(_)) => HttpResponse::NoContent().finish(),
+        Err(err) => {
+            let message = format!("Error: {:?}", err);
+            HttpResponse::InternalServerError().json(json!({"status": "error","message": message}))
+        }
+    }
+}
+
+#[post("/{id}/restore")]
+async fn restore_category_handler(
+    path: web::Path<uuid::Uuid>,
+    data: web::Data<AppState>,
+    jwt: jwt_auth::JwtMiddleware
+) -> impl Responder {
+    let category_id = path.into_inner();
+    if let Err(response) = access::require(&data.db, Entity::Category, category_id, jwt.user_id, Access::Write).await {
+        return response;
+    }
+
+    let query_result = service::set_category_deleted(&data.db, category_id, false, jwt.user_id).await;
+
+    match query_result {
+        Ok(Some(category)) => {
+            let category_response =
+                serde_json::json!({"status": "success","data": serde_json::json!({
+                "category": category
+            })});
+
+            HttpResponse::Ok().json(category_response)
+        }
+        Ok(None) => {
+            let message = format!("Trashed category with ID: {} not found", category_id);
+            HttpResponse::NotFound().json(json!({"status": "fail","message": message}))
+        }
+        Err(err) => {
+            let message = format!("Error: {:?}", err);
+            HttpResponse::InternalServerError().json(json!({"status": "error","message": message}))
+        }
+    }
+}
+
+#[get("/{id}/history")]
+async fn category_history_handler(
+    path: web::Path<uuid::Uuid>,
+    opts: web::Query<FilterOptions>,
+    data: web::Data<AppState>,
+    jwt: jwt_auth::JwtMiddleware
+) -> impl Responder {
+    let category_id = path.into_inner();
+    if let Err(response) = access::require(&data.db, Entity::Category, category_id, jwt.user_id, Access::Read).await {
+        return response;
+    }
+
+    let limit = opts.limit.unwrap_or(10);
+    let offset = (opts.page.unwrap_or(1) - 1) * limit;
+
+    let query_result = recorder::list(
+        &data.db,
+        Entity::Category,
+        category_id,
+        limit as i64,
+        offset as i64
+    ).await;
+
+    match query_result {
+        Ok(history) => {
+            let json_response =
+                serde_json::json!({
+                "status": "success",
+                "results": history.len(),
+                "history": history
+            });
+            HttpResponse::Ok().json(json_response)
+        }
+        Err(err) => {
+            let message = format!("Error: {:?}", err);
+            HttpResponse::InternalServerError().json(json!({"status": "error","message": message}))
+        }
+    }
 }
 
 pub fn config(conf: &mut web::ServiceConfig) {
@@ -196,9 +318,12 @@ pub fn config(conf: &mut web::ServiceConfig) {
         ::scope("/categories")
         .service(category_list_handler)
         .service(create_category_handler)
+        .service(category_trash_handler)
         .service(get_category_handler)
+        .service(category_history_handler)
         .service(edit_category_handler)
         .service(delete_category_handler)
+        .service(restore_category_handler);
 
     conf.service(scope);
 }
@@ -1,3 +1,4 @@
 pub mod handler;
 pub mod model;
-pub mod schema;
\ No newline at end of file
+pub mod schema;
+pub mod service;
\ No newline at end of file
@@ -10,8 +10,12 @@ pub struct CategoryModel {
     pub description: String,
     #[serde(rename = "userId")]
     pub user_id: Uuid,
+    #[serde(rename = "householdId")]
+    pub household_id: Option<Uuid>,
     #[serde(rename = "createdAt")]
     pub created_at: Option<chrono::DateTime<chrono::Utc>>,
     #[serde(rename = "updatedAt")]
-    pub updated_at: Option<chrono::DateTime<chrono::Utc>>
+    pub updated_at: Option<chrono::DateTime<chrono::Utc>>,
+    #[serde(rename = "deletedAt")]
+    pub deleted_at: Option<chrono::DateTime<chrono::Utc>>
 }
\ No newline at end of file
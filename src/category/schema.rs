@@ -5,6 +5,17 @@ use uuid::Uuid;
 pub struct FilterOptions {
     pub page: Option<usize>,
     pub limit: Option<usize>,
+    #[serde(rename = "withDeleted")]
+    pub with_deleted: Option<bool>,
+    /// Narrows the list to one household's ledger.
+    #[serde(rename = "householdId")]
+    pub household_id: Option<Uuid>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct VisibilityOptions {
+    #[serde(rename = "withDeleted")]
+    pub with_deleted: Option<bool>,
 }
 
 #[derive(Deserialize, Debug)]
@@ -18,6 +29,8 @@ pub struct CreateCategorySchema {
     pub name: String,
     pub description: String,
     pub userId: Uuid,
+    /// Creates the category in a household instead of the personal ledger.
+    pub householdId: Option<Uuid>,
 }
 
 #[allow(non_snake_case)]
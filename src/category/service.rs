@@ -0,0 +1,221 @@
+//! Category queries and writes shared by the REST handlers, AMQP and gRPC.
+//! Callers check access first; the `conn` variants run inside the caller's
+//! transaction.
+
+use chrono::Utc;
+use sqlx::{PgConnection, Pool, Postgres};
+use uuid::Uuid;
+
+use crate::{
+    category::{
+        model::CategoryModel,
+        schema::{CreateCategorySchema, UpdateCategorySchema},
+    },
+    history::recorder::{self, Action, Entity},
+};
+
+/// Filters for [`list_categories`], shared by the REST list and gRPC.
+#[derive(Debug)]
+pub struct CategoryQuery {
+    pub limit: i64,
+    pub offset: i64,
+    pub with_deleted: bool,
+    pub household_id: Option<Uuid>,
+}
+
+/// Categories in `user_id`'s personal ledger and the households they belong to.
+pub async fn list_categories(
+    db: &Pool<Postgres>,
+    user_id: Uuid,
+    query: &CategoryQuery,
+) -> Result<Vec<CategoryModel>, sqlx::Error> {
+    sqlx::query_as!(
+        CategoryModel,
+        "SELECT * FROM categories WHERE ($3 OR deleted_at IS NULL)
+        AND ((household_id IS NULL AND user_id = $4)
+            OR household_id IN (SELECT household_id FROM household_members WHERE user_id = $4))
+        AND ($5::uuid IS NULL OR household_id = $5)
+        ORDER by id LIMIT $1 OFFSET $2",
+        query.limit,
+        query.offset,
+        query.with_deleted,
+        user_id,
+        query.household_id
+    )
+    .fetch_all(db)
+    .await
+}
+
+pub async fn find_category(
+    db: &Pool<Postgres>,
+    category_id: Uuid,
+    with_deleted: bool,
+) -> Result<Option<CategoryModel>, sqlx::Error> {
+    sqlx::query_as!(
+        CategoryModel,
+        "SELECT * FROM categories WHERE id = $1 AND ($2 OR deleted_at IS NULL)",
+        category_id,
+        with_deleted
+    )
+    .fetch_optional(db)
+    .await
+}
+
+pub async fn create_category(
+    db: &Pool<Postgres>,
+    input: &CreateCategorySchema,
+    actor_id: Uuid,
+) -> Result<CategoryModel, sqlx::Error> {
+    let mut tx = db.begin().await?;
+    let category = insert(&mut tx, input, actor_id).await?;
+    tx.commit().await?;
+
+    Ok(category)
+}
+
+pub async fn insert(
+    conn: &mut PgConnection,
+    input: &CreateCategorySchema,
+    actor_id: Uuid,
+) -> Result<CategoryModel, sqlx::Error> {
+    let category = sqlx::query_as!(
+        CategoryModel,
+        "INSERT INTO categories (name,description,user_id,household_id) VALUES ($1, $2, $3, $4) RETURNING *",
+        input.name,
+        input.description,
+        input.userId,
+        input.householdId
+    )
+    .fetch_one(&mut *conn)
+    .await?;
+
+    recorder::record(
+        conn,
+        Entity::Category,
+        category.id,
+        Action::Create,
+        Some(actor_id),
+        None,
+        Some(&category),
+    )
+    .await?;
+
+    Ok(category)
+}
+
+pub async fn update_category(
+    db: &Pool<Postgres>,
+    category_id: Uuid,
+    input: &UpdateCategorySchema,
+    actor_id: Uuid,
+) -> Result<Option<CategoryModel>, sqlx::Error> {
+    let mut tx = db.begin().await?;
+    let category = update(&mut tx, category_id, input, actor_id).await?;
+    tx.commit().await?;
+
+    Ok(category)
+}
+
+/// Returns `None` when there is no live category with that ID.
+pub async fn update(
+    conn: &mut PgConnection,
+    category_id: Uuid,
+    input: &UpdateCategorySchema,
+    actor_id: Uuid,
+) -> Result<Option<CategoryModel>, sqlx::Error> {
+    let before = sqlx::query_as!(
+        CategoryModel,
+        "SELECT * FROM categories WHERE id = $1 AND deleted_at IS NULL FOR UPDATE",
+        category_id
+    )
+    .fetch_optional(&mut *conn)
+    .await?;
+
+    let before = match before {
+        Some(category) => category,
+        None => return Ok(None),
+    };
+
+    let category = sqlx::query_as!(
+        CategoryModel,
+        "UPDATE categories SET name = $1, description = $2, updated_at = $3 WHERE id = $4 RETURNING *",
+        input.name,
+        input.description,
+        Utc::now(),
+        category_id
+    )
+    .fetch_one(&mut *conn)
+    .await?;
+
+    recorder::record(
+        conn,
+        Entity::Category,
+        category_id,
+        Action::Update,
+        Some(actor_id),
+        Some(&before),
+        Some(&category),
+    )
+    .await?;
+
+    Ok(Some(category))
+}
+
+pub async fn set_category_deleted(
+    db: &Pool<Postgres>,
+    category_id: Uuid,
+    deleted: bool,
+    actor_id: Uuid,
+) -> Result<Option<CategoryModel>, sqlx::Error> {
+    let mut tx = db.begin().await?;
+    let category = set_deleted(&mut tx, category_id, deleted, actor_id).await?;
+    tx.commit().await?;
+
+    Ok(category)
+}
+
+/// Moves a category to (`deleted == true`) or out of the trash, returning
+/// `None` when there was no category in the opposite state to change.
+pub async fn set_deleted(
+    conn: &mut PgConnection,
+    category_id: Uuid,
+    deleted: bool,
+    actor_id: Uuid,
+) -> Result<Option<CategoryModel>, sqlx::Error> {
+    let before = sqlx::query_as!(
+        CategoryModel,
+        "SELECT * FROM categories WHERE id = $1 AND (deleted_at IS NULL) = $2 FOR UPDATE",
+        category_id,
+        deleted
+    )
+    .fetch_optional(&mut *conn)
+    .await?;
+
+    let before = match before {
+        Some(category) => category,
+        None => return Ok(None),
+    };
+
+    let category = sqlx::query_as!(
+        CategoryModel,
+        "UPDATE categories SET deleted_at = CASE WHEN $2 THEN NOW() ELSE NULL END WHERE id = $1 RETURNING *",
+        category_id,
+        deleted
+    )
+    .fetch_one(&mut *conn)
+    .await?;
+
+    let action = if deleted { Action::Delete } else { Action::Restore };
+    recorder::record(
+        conn,
+        Entity::Category,
+        category_id,
+        action,
+        Some(actor_id),
+        Some(&before),
+        Some(&category),
+    )
+    .await?;
+
+    Ok(Some(category))
+}
@@ -0,0 +1,132 @@
+use std::{error::Error, sync::Arc};
+
+use sqlx::{Pool, Postgres};
+use uuid::Uuid;
+
+use crate::{avatar::storage::AvatarStorage, token};
+
+/// Removes a user's personal data.
+///
+/// Personal payments are always deleted. Personal categories are deleted too
+/// unless another user's payment still points at them (the `fk_category`
+/// constraint), in which case they are kept with their text scrubbed. Rows in
+/// a household ledger belong to the household and are left alone. Once
+/// nothing references the user row through `fk_user` it is deleted outright;
+/// otherwise it is anonymized in place. Audit entries are kept but lose their
+/// personal fields.
+///
+/// Returns `false` if the user does not exist.
+pub async fn erase_user(
+    db: &Pool<Postgres>,
+    storage: &Arc<dyn AvatarStorage>,
+    user_id: Uuid,
+) -> Result<bool, Box<dyn Error + Send + Sync>> {
+    let mut tx = db.begin().await?;
+
+    let email = sqlx::query_scalar!("SELECT email FROM users WHERE id = $1 FOR UPDATE", user_id)
+        .fetch_optional(&mut *tx)
+        .await?;
+    let email = match email {
+        Some(email) => email,
+        None => return Ok(false),
+    };
+
+    let payment_ids = sqlx::query_scalar!("DELETE FROM payments WHERE user_id = $1 AND household_id IS NULL RETURNING id", user_id)
+        .fetch_all(&mut *tx)
+        .await?;
+
+    let category_ids = sqlx::query_scalar!(
+        "DELETE FROM categories c WHERE c.user_id = $1 AND c.household_id IS NULL
+        AND NOT EXISTS (SELECT 1 FROM payments p WHERE p.category_id = c.id)
+        RETURNING c.id",
+        user_id
+    )
+    .fetch_all(&mut *tx)
+    .await?;
+
+    sqlx::query!(
+        "UPDATE categories SET name = 'Deleted category', description = '', updated_at = NOW()
+        WHERE user_id = $1 AND household_id IS NULL",
+        user_id
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    let still_referenced = sqlx::query_scalar!(
+        r#"SELECT EXISTS(SELECT 1 FROM categories WHERE user_id = $1)
+        OR EXISTS(SELECT 1 FROM payments WHERE user_id = $1) AS "referenced!""#,
+        user_id
+    )
+    .fetch_one(&mut *tx)
+    .await?;
+
+    let erased_ids: Vec<Uuid> = payment_ids.into_iter().chain(category_ids).collect();
+    sqlx::query!("DELETE FROM change_history WHERE entity_id = ANY($1)", &erased_ids)
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query!("UPDATE change_history SET actor_id = NULL WHERE actor_id = $1", user_id)
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query!("DELETE FROM outbox_events WHERE aggregate_id = ANY($1)", &erased_ids)
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query!(
+        "UPDATE outbox_events SET payload = payload || '{\"actorId\": null}'::jsonb WHERE payload->>'actorId' = $1",
+        user_id.to_string()
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    sqlx::query!("SET LOCAL app.gdpr_erasure = 'on'")
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query!(
+        "UPDATE audit_logs SET user_id = NULL, email = NULL, ip = NULL, user_agent = NULL
+        WHERE user_id = $1 OR email = $2",
+        user_id,
+        email
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    if !still_referenced {
+        // Tokens, keys, memberships and preferences cascade with the row.
+        sqlx::query!("DELETE FROM users WHERE id = $1", user_id)
+            .execute(&mut *tx)
+            .await?;
+    } else {
+        for table in [
+            "password_reset_tokens",
+            "email_verification_tokens",
+            "mfa_recovery_codes",
+            "api_keys",
+            "user_preferences",
+            "household_members",
+        ] {
+            sqlx::query(&format!("DELETE FROM {} WHERE user_id = $1", table))
+                .bind(user_id)
+                .execute(&mut *tx)
+                .await?;
+        }
+
+        // The password is replaced with an unguessable value so the row can
+        // never be logged into again.
+        sqlx::query!(
+            "UPDATE users SET name = 'Deleted user', email = $2, password = $3,
+            verified_at = NULL, totp_secret = NULL, totp_enabled_at = NULL, avatar_version = NULL,
+            updated_at = NOW(), deleted_at = COALESCE(deleted_at, NOW())
+            WHERE id = $1",
+            user_id,
+            format!("deleted-{}@invalid", user_id),
+            token::generate()
+        )
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    tx.commit().await?;
+
+    storage.delete_prefix(&user_id.to_string()).await?;
+
+    Ok(true)
+}
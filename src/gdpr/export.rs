@@ -0,0 +1,209 @@
+use std::{
+    error::Error,
+    io::{Cursor, Write},
+    sync::Arc,
+};
+
+use serde::Serialize;
+use sqlx::{Pool, Postgres};
+use uuid::Uuid;
+use zip::{write::FileOptions, ZipWriter};
+
+use crate::{
+    api_key::model::ApiKeyModel,
+    audit::model::AuditLogModel,
+    avatar::{config::get_config as get_avatar_config, storage::AvatarStorage},
+    category::model::CategoryModel,
+    history::model::HistoryModel,
+    payment::model::PaymentModel,
+    preferences::{format, model::PreferencesModel},
+    user::model::UserModel,
+};
+
+type BoxError = Box<dyn Error + Send + Sync>;
+
+/// Everything stored about one user, gathered for a data subject access request.
+pub struct ExportData {
+    pub user: UserModel,
+    pub preferences: PreferencesModel,
+    pub categories: Vec<CategoryModel>,
+    pub payments: Vec<PaymentModel>,
+    pub history: Vec<HistoryModel>,
+    pub audit_logs: Vec<AuditLogModel>,
+    pub api_keys: Vec<ApiKeyModel>,
+    pub avatar: Option<Vec<u8>>,
+}
+
+pub async fn collect(
+    db: &Pool<Postgres>,
+    storage: &Arc<dyn AvatarStorage>,
+    user_id: Uuid,
+) -> Result<Option<ExportData>, BoxError> {
+    let user = match sqlx::query_as!(UserModel, "SELECT * FROM users WHERE id = $1", user_id)
+        .fetch_optional(db)
+        .await?
+    {
+        Some(user) => user,
+        None => return Ok(None),
+    };
+
+    let preferences = format::load(db, user_id).await?;
+
+    let categories = sqlx::query_as!(
+        CategoryModel,
+        "SELECT * FROM categories WHERE user_id = $1 ORDER BY created_at",
+        user_id
+    )
+    .fetch_all(db)
+    .await?;
+
+    let payments = sqlx::query_as!(
+        PaymentModel,
+        "SELECT * FROM payments WHERE user_id = $1 ORDER BY created_at",
+        user_id
+    )
+    .fetch_all(db)
+    .await?;
+
+    let entity_ids: Vec<Uuid> = categories
+        .iter()
+        .map(|c| c.id)
+        .chain(payments.iter().map(|p| p.id))
+        .collect();
+    let history = sqlx::query_as!(
+        HistoryModel,
+        "SELECT * FROM change_history WHERE actor_id = $1 OR entity_id = ANY($2) ORDER BY created_at",
+        user_id,
+        &entity_ids
+    )
+    .fetch_all(db)
+    .await?;
+
+    // Only entries tied to the account: ones matching just the email (failed
+    // logins, reset requests) may carry a stranger's IP and user agent.
+    let audit_logs = sqlx::query_as!(
+        AuditLogModel,
+        "SELECT * FROM audit_logs WHERE user_id = $1 ORDER BY created_at",
+        user_id
+    )
+    .fetch_all(db)
+    .await?;
+
+    let api_keys = sqlx::query_as!(
+        ApiKeyModel,
+        "SELECT * FROM api_keys WHERE user_id = $1 ORDER BY created_at",
+        user_id
+    )
+    .fetch_all(db)
+    .await?;
+
+    let avatar = match (&user.avatar_version, get_avatar_config().sizes.iter().max()) {
+        (Some(version), Some(size)) => {
+            storage
+                .get(&format!("{}/{}/{}.jpg", user_id, version, size))
+                .await?
+        }
+        _ => None,
+    };
+
+    Ok(Some(ExportData {
+        user,
+        preferences,
+        categories,
+        payments,
+        history,
+        audit_logs,
+        api_keys,
+        avatar,
+    }))
+}
+
+fn write_json<W: Write + std::io::Seek, T: Serialize>(
+    zip: &mut ZipWriter<W>,
+    name: &str,
+    value: &T,
+) -> Result<(), BoxError> {
+    zip.start_file(name, FileOptions::default())?;
+    zip.write_all(&serde_json::to_vec_pretty(value)?)?;
+    Ok(())
+}
+
+fn write_csv<W: Write + std::io::Seek>(
+    zip: &mut ZipWriter<W>,
+    name: &str,
+    header: &[&str],
+    rows: Vec<Vec<String>>,
+) -> Result<(), BoxError> {
+    let mut csv = csv::Writer::from_writer(Vec::new());
+    csv.write_record(header)?;
+    for row in rows {
+        csv.write_record(&row)?;
+    }
+
+    zip.start_file(name, FileOptions::default())?;
+    zip.write_all(&csv.into_inner()?)?;
+    Ok(())
+}
+
+/// Packs the export as a ZIP of JSON files, CSV copies of the ledger
+/// formatted with the user's preferences, and the avatar if one exists.
+pub fn build_zip(data: &ExportData) -> Result<Vec<u8>, BoxError> {
+    let prefs = &data.preferences;
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+
+    write_json(&mut zip, "profile.json", &data.user)?;
+    write_json(&mut zip, "preferences.json", prefs)?;
+    write_json(&mut zip, "categories.json", &data.categories)?;
+    write_json(&mut zip, "payments.json", &data.payments)?;
+    write_json(&mut zip, "history.json", &data.history)?;
+    write_json(&mut zip, "audit_logs.json", &data.audit_logs)?;
+    write_json(&mut zip, "api_keys.json", &data.api_keys)?;
+
+    let format_optional = |value: Option<chrono::DateTime<chrono::Utc>>| {
+        value.map(|v| format::format_datetime(v, prefs)).unwrap_or_default()
+    };
+
+    write_csv(
+        &mut zip,
+        "categories.csv",
+        &["id", "name", "description", "createdAt", "updatedAt", "deletedAt"],
+        data.categories
+            .iter()
+            .map(|c| vec![
+                c.id.to_string(),
+                c.name.clone(),
+                c.description.clone(),
+                format_optional(c.created_at),
+                format_optional(c.updated_at),
+                format_optional(c.deleted_at),
+            ])
+            .collect(),
+    )?;
+
+    write_csv(
+        &mut zip,
+        "payments.csv",
+        &["id", "name", "description", "price", "formattedPrice", "categoryId", "createdAt", "updatedAt", "deletedAt"],
+        data.payments
+            .iter()
+            .map(|p| vec![
+                p.id.to_string(),
+                p.name.clone(),
+                p.description.clone(),
+                p.price.to_string(),
+                format::format_amount(p.price, prefs),
+                p.category_id.to_string(),
+                format_optional(p.created_at),
+                format_optional(p.updated_at),
+                format_optional(p.deleted_at),
+            ])
+            .collect(),
+    )?;
+
+    if let Some(avatar) = &data.avatar {
+        zip.start_file("attachments/avatar.jpg", FileOptions::default())?;
+        zip.write_all(avatar)?;
+    }
+
+    Ok(zip.finish()?.into_inner())
+}
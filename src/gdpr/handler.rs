@@ -0,0 +1,171 @@
+use crate::{
+    audit::log::{ self as audit, AuditEntry, AuditEvent, RequestMeta },
+    gdpr::{ erasure, export, schema::EraseAccountSchema },
+    jwt_auth,
+    user::model::UserModel,
+    user::role,
+    AppState,
+};
+use actix_web::{
+    cookie::{ time::Duration as ActixWebDuration, Cookie },
+    get,
+    http::header,
+    post,
+    web,
+    HttpRequest,
+    HttpResponse,
+    Responder,
+};
+use argon2::{ password_hash::{ PasswordHash, PasswordVerifier }, Argon2 };
+use serde_json::json;
+
+#[get("/me/export")]
+pub async fn export_me_handler(
+    req: HttpRequest,
+    data: web::Data<AppState>,
+    jwt: jwt_auth::JwtMiddleware
+) -> impl Responder {
+    let export_data = match export::collect(&data.db, &data.avatar_storage, jwt.user_id).await {
+        Ok(Some(export_data)) => export_data,
+        Ok(None) => {
+            let message = format!("User with ID: {} not found", jwt.user_id);
+            return HttpResponse::NotFound().json(json!({"status": "fail","message": message}));
+        }
+        Err(err) => {
+            let message = format!("Error: {:?}", err);
+            return HttpResponse::InternalServerError().json(json!({"status": "error","message": message}));
+        }
+    };
+
+    let archive = match web::block(move || export::build_zip(&export_data)).await {
+        Ok(Ok(archive)) => archive,
+        Ok(Err(err)) => {
+            let message = format!("Error: {:?}", err);
+            return HttpResponse::InternalServerError().json(json!({"status": "error","message": message}));
+        }
+        Err(err) => {
+            let message = format!("Error: {:?}", err);
+            return HttpResponse::InternalServerError().json(json!({"status": "error","message": message}));
+        }
+    };
+
+    audit::record(&data.db, AuditEntry {
+        event: AuditEvent::DataExport,
+        success: true,
+        user_id: Some(jwt.user_id),
+        email: None,
+        meta: &RequestMeta::from_request(&req),
+        metadata: json!({}),
+    }).await;
+
+    HttpResponse::Ok()
+        .content_type("application/zip")
+        .insert_header((
+            header::CONTENT_DISPOSITION,
+            format!("attachment; filename=\"export-{}.zip\"", jwt.user_id),
+        ))
+        .body(archive)
+}
+
+/// Erases the caller's own account. The password is asked for again since
+/// this can't be undone.
+#[post("/me/erase")]
+pub async fn erase_me_handler(
+    body: web::Json<EraseAccountSchema>,
+    data: web::Data<AppState>,
+    jwt: jwt_auth::JwtMiddleware
+) -> impl Responder {
+    if !jwt.is_session() {
+        return HttpResponse::Forbidden().json(
+            json!({"status": "fail","message": "Accounts can only be erased with your own session token"})
+        );
+    }
+
+    let query_result = sqlx
+        ::query_as!(UserModel, "SELECT * FROM users WHERE id = $1 AND deleted_at IS NULL", jwt.user_id)
+        .fetch_one(&data.db).await;
+
+    let user = match query_result {
+        Ok(user) => user,
+        Err(_) => {
+            let message = format!("User with ID: {} not found", jwt.user_id);
+            return HttpResponse::NotFound().json(json!({"status": "fail","message": message}));
+        }
+    };
+
+    let password_matches = PasswordHash::new(&user.password)
+        .map(|hash| Argon2::default().verify_password(body.password.as_bytes(), &hash).is_ok())
+        .unwrap_or(false);
+
+    if !password_matches {
+        return HttpResponse::BadRequest().json(
+            json!({"status": "fail","message": "Password is incorrect"})
+        );
+    }
+
+    erase(&data, jwt.user_id, jwt.user_id).await
+}
+
+#[post("/{id}/erase")]
+pub async fn erase_user_handler(
+    path: web::Path<uuid::Uuid>,
+    data: web::Data<AppState>,
+    jwt: jwt_auth::JwtMiddleware
+) -> impl Responder {
+    // Erasure can't be undone, so it needs the admin's own session.
+    if !jwt.is_session() {
+        return HttpResponse::Forbidden().json(
+            json!({"status": "fail","message": "Users can only be erased from your own session"})
+        );
+    }
+
+    if !role::is_admin(&data.db, jwt.user_id).await {
+        return HttpResponse::Forbidden().json(
+            json!({"status": "fail","message": "Only administrators can erase other users"})
+        );
+    }
+
+    let user_id = path.into_inner();
+    if role::is_super_admin(&data.db, user_id).await && !role::is_super_admin(&data.db, jwt.user_id).await {
+        return HttpResponse::Forbidden().json(
+            json!({"status": "fail","message": "Only super administrators can erase a super administrator"})
+        );
+    }
+
+    erase(&data, user_id, jwt.user_id).await
+}
+
+async fn erase(data: &web::Data<AppState>, user_id: uuid::Uuid, actor_id: uuid::Uuid) -> HttpResponse {
+    match erasure::erase_user(&data.db, &data.avatar_storage, user_id).await {
+        Ok(true) => {
+            // Only ids are kept; the erased user's id no longer resolves to anyone.
+            audit::record(&data.db, AuditEntry {
+                event: AuditEvent::AccountErasure,
+                success: true,
+                user_id: None,
+                email: None,
+                meta: &RequestMeta::default(),
+                metadata: json!({"erasedUserId": user_id, "actorId": (actor_id != user_id).then_some(actor_id)}),
+            }).await;
+
+            let mut response = HttpResponse::NoContent();
+            if actor_id == user_id {
+                let cookie = Cookie::build("token", "")
+                    .path("/")
+                    .max_age(ActixWebDuration::new(-1, 0))
+                    .http_only(true)
+                    .finish();
+                response.cookie(cookie);
+            }
+            response.finish()
+        }
+        Ok(false) => {
+            let message = format!("User with ID: {} not found", user_id);
+            HttpResponse::NotFound().json(json!({"status": "fail","message": message}))
+        }
+        Err(err) => {
+            let message = format!("Error: {:?}", err);
+            HttpResponse::InternalServerError().json(json!({"status": "error","message": message}))
+        }
+    }
+}
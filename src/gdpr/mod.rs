@@ -0,0 +1,4 @@
+pub mod erasure;
+pub mod export;
+pub mod handler;
+pub mod schema;
@@ -0,0 +1,239 @@
+use sqlx::{Pool, Postgres};
+use tonic::{Request, Response, Status};
+use uuid::Uuid;
+
+use crate::category::{
+    model::CategoryModel,
+    schema::{CreateCategorySchema, UpdateCategorySchema},
+    service::{self, CategoryQuery},
+};
+use crate::grpc::{parse_optional_uuid, parse_uuid, timestamp, InvalidArgument};
+use crate::history::recorder::Entity;
+use crate::household::access::{self, MemberRole};
+use proto::category_service_server::{CategoryService, CategoryServiceServer};
+use proto::{
+    CreateCategoryRequest, CreateCategoryResponse, DeleteCategoryRequest, DeleteCategoryResponse,
+    GetCategoryRequest, GetCategoryResponse, ListCategoriesRequest, ListCategoriesResponse,
+    UpdateCategoryRequest, UpdateCategoryResponse,
+};
+
+pub mod proto {
+    tonic::include_proto!("category");
+}
+
+const DEFAULT_PAGE_SIZE: u32 = 10;
+const MAX_PAGE_SIZE: u32 = 100;
+
+pub struct CategoryGrpcService {
+    db: Pool<Postgres>,
+}
+
+pub fn server(db: Pool<Postgres>) -> CategoryServiceServer<CategoryGrpcService> {
+    CategoryServiceServer::new(CategoryGrpcService { db })
+}
+
+impl From<CategoryModel> for proto::CategoryModel {
+    fn from(category: CategoryModel) -> Self {
+        proto::CategoryModel {
+            id: category.id.to_string(),
+            name: category.name,
+            description: category.description,
+            user_id: category.user_id.to_string(),
+            household_id: category.household_id.map(|id| id.to_string()).unwrap_or_default(),
+            created_at: timestamp(category.created_at),
+            updated_at: timestamp(category.updated_at),
+            deleted_at: timestamp(category.deleted_at),
+        }
+    }
+}
+
+/// Unique violations (two categories can't share a description) are the
+/// caller's to fix; anything else is on us.
+fn database_error(err: sqlx::Error) -> Status {
+    match &err {
+        sqlx::Error::Database(db_err) if db_err.code().as_deref() == Some("23505") => {
+            Status::already_exists("A category with this description already exists")
+        }
+        _ => Status::internal(format!("Error: {:?}", err)),
+    }
+}
+
+fn not_found(category_id: Uuid) -> Status {
+    Status::not_found(format!("Category with ID: {} not found", category_id))
+}
+
+fn validate(name: &str, description: &str) -> Result<(), InvalidArgument> {
+    if name.trim().is_empty() {
+        return Err(InvalidArgument("name is required".to_string()));
+    }
+    if name.chars().count() > 255 {
+        return Err(InvalidArgument("name must be at most 255 characters".to_string()));
+    }
+    if description.chars().count() > 510 {
+        return Err(InvalidArgument("description must be at most 510 characters".to_string()));
+    }
+    Ok(())
+}
+
+/// Rows the user can't see are reported as missing, as over REST.
+async fn require(db: &Pool<Postgres>, category_id: Uuid, user_id: Uuid, minimum: MemberRole) -> Result<(), Status> {
+    match access::entity_role(db, Entity::Category, category_id, user_id)
+        .await
+        .map_err(database_error)?
+    {
+        Some(role) if role >= minimum => Ok(()),
+        Some(_) => Err(Status::permission_denied("Viewers can't change this household's ledger")),
+        None => Err(not_found(category_id)),
+    }
+}
+
+#[tonic::async_trait]
+impl CategoryService for CategoryGrpcService {
+    async fn get_category(
+        &self,
+        request: Request<GetCategoryRequest>,
+    ) -> Result<Response<GetCategoryResponse>, Status> {
+        let request = request.into_inner();
+        let category_id = parse_uuid(&request.id, "id")?;
+
+        if let Some(user_id) = parse_optional_uuid(&request.user_id, "userId")? {
+            require(&self.db, category_id, user_id, MemberRole::Viewer).await?;
+        }
+
+        let category = service::find_category(&self.db, category_id, request.with_deleted)
+            .await
+            .map_err(database_error)?
+            .ok_or_else(|| not_found(category_id))?;
+
+        Ok(Response::new(GetCategoryResponse {
+            category: Some(category.into()),
+            message: "Category found successfully".to_string(),
+        }))
+    }
+
+    async fn list_categories(
+        &self,
+        request: Request<ListCategoriesRequest>,
+    ) -> Result<Response<ListCategoriesResponse>, Status> {
+        let request = request.into_inner();
+        let user_id = parse_uuid(&request.user_id, "userId")?;
+
+        let page = request.page.max(1);
+        let page_size = match request.page_size {
+            0 => DEFAULT_PAGE_SIZE,
+            size => size.min(MAX_PAGE_SIZE),
+        };
+
+        let out_of_range = || Status::invalid_argument("page is out of range");
+        let offset = i64::from(page - 1)
+            .checked_mul(i64::from(page_size))
+            .ok_or_else(out_of_range)?;
+
+        // One extra row tells whether another page follows.
+        let query = CategoryQuery {
+            limit: i64::from(page_size) + 1,
+            offset,
+            with_deleted: request.with_deleted,
+            household_id: parse_optional_uuid(&request.household_id, "householdId")?,
+        };
+        let mut categories = service::list_categories(&self.db, user_id, &query)
+            .await
+            .map_err(database_error)?;
+
+        let next_page = if categories.len() > page_size as usize {
+            categories.truncate(page_size as usize);
+            page.checked_add(1).ok_or_else(out_of_range)?
+        } else {
+            0
+        };
+
+        Ok(Response::new(ListCategoriesResponse {
+            categories: categories.into_iter().map(Into::into).collect(),
+            next_page,
+        }))
+    }
+
+    async fn create_category(
+        &self,
+        request: Request<CreateCategoryRequest>,
+    ) -> Result<Response<CreateCategoryResponse>, Status> {
+        let request = request.into_inner();
+        let user_id = parse_uuid(&request.user_id, "userId")?;
+        let household_id = parse_optional_uuid(&request.household_id, "householdId")?;
+        validate(&request.name, &request.description)?;
+
+        if let Some(household_id) = household_id {
+            match access::member_role(&self.db, household_id, user_id)
+                .await
+                .map_err(database_error)?
+            {
+                Some(role) if role.can_write() => {}
+                Some(_) => return Err(Status::permission_denied("Viewers can't change this household's ledger")),
+                None => {
+                    return Err(Status::not_found(format!("Household with ID: {} not found", household_id)))
+                }
+            }
+        }
+
+        let input = CreateCategorySchema {
+            name: request.name,
+            description: request.description,
+            userId: user_id,
+            householdId: household_id,
+        };
+        let category = service::create_category(&self.db, &input, user_id)
+            .await
+            .map_err(database_error)?;
+
+        Ok(Response::new(CreateCategoryResponse {
+            category: Some(category.into()),
+            message: "Category created successfully".to_string(),
+        }))
+    }
+
+    async fn update_category(
+        &self,
+        request: Request<UpdateCategoryRequest>,
+    ) -> Result<Response<UpdateCategoryResponse>, Status> {
+        let request = request.into_inner();
+        let category_id = parse_uuid(&request.id, "id")?;
+        let user_id = parse_uuid(&request.user_id, "userId")?;
+        validate(&request.name, &request.description)?;
+
+        require(&self.db, category_id, user_id, MemberRole::Editor).await?;
+
+        let input = UpdateCategorySchema {
+            name: request.name,
+            description: request.description,
+        };
+        let category = service::update_category(&self.db, category_id, &input, user_id)
+            .await
+            .map_err(database_error)?
+            .ok_or_else(|| not_found(category_id))?;
+
+        Ok(Response::new(UpdateCategoryResponse {
+            category: Some(category.into()),
+            message: "Category updated successfully".to_string(),
+        }))
+    }
+
+    async fn delete_category(
+        &self,
+        request: Request<DeleteCategoryRequest>,
+    ) -> Result<Response<DeleteCategoryResponse>, Status> {
+        let request = request.into_inner();
+        let category_id = parse_uuid(&request.id, "id")?;
+        let user_id = parse_uuid(&request.user_id, "userId")?;
+
+        require(&self.db, category_id, user_id, MemberRole::Editor).await?;
+
+        service::set_category_deleted(&self.db, category_id, true, user_id)
+            .await
+            .map_err(database_error)?
+            .ok_or_else(|| not_found(category_id))?;
+
+        Ok(Response::new(DeleteCategoryResponse {
+            message: "Category moved to the trash".to_string(),
+        }))
+    }
+}
@@ -1,25 +1,45 @@
-use category::category_client::CategoryClient;
-use category::GetCategoryRequest;
-use uuid::Uuid;
+//! Calls the category gRPC service from a terminal.
+//!
+//! ```text
+//! category-client get <id> [user-id]
+//! category-client list <user-id> [page]
+//! ```
 
-pub mod category {
-    tonic::include_proto!("category");
+use backend::grpc::category::proto::category_service_client::CategoryServiceClient;
+use backend::grpc::category::proto::{GetCategoryRequest, ListCategoriesRequest};
+
+fn usage() -> ! {
+    eprintln!("usage: category-client <get ID [USER_ID] | list USER_ID [PAGE]>");
+    std::process::exit(2)
 }
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let mut client = CategoryClient::connect("http://[::1]:50051").await?;
-
-    let category_id = Uuid::parse_str("any_id")?;
-
-    let request = tonic::Request::new(GetCategoryRequest {
-        id: category_id.to_string(),
-    });
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let mut client = CategoryServiceClient::connect("http://[::1]:50051").await?;
 
     println!("Sending request to gRPC Server...");
-    let response = client.get_category(request).await?;
-
-    println!("RESPONSE={:?}", response);
+    match args.iter().map(String::as_str).collect::<Vec<_>>().as_slice() {
+        ["get", id, rest @ ..] => {
+            let request = tonic::Request::new(GetCategoryRequest {
+                id: id.to_string(),
+                user_id: rest.first().map(|id| id.to_string()).unwrap_or_default(),
+                with_deleted: false,
+            });
+            let response = client.get_category(request).await?;
+            println!("RESPONSE={:?}", response);
+        }
+        ["list", user_id, rest @ ..] => {
+            let request = tonic::Request::new(ListCategoriesRequest {
+                user_id: user_id.to_string(),
+                page: rest.first().and_then(|page| page.parse().ok()).unwrap_or(1),
+                ..Default::default()
+            });
+            let response = client.list_categories(request).await?;
+            println!("RESPONSE={:?}", response);
+        }
+        _ => usage(),
+    }
 
     Ok(())
 }
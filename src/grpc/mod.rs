@@ -0,0 +1,35 @@
+pub mod category;
+pub mod payment;
+
+use tonic::Status;
+use uuid::Uuid;
+
+pub(crate) fn timestamp(value: Option<chrono::DateTime<chrono::Utc>>) -> Option<prost_types::Timestamp> {
+    value.map(|v| prost_types::Timestamp {
+        seconds: v.timestamp(),
+        nanos: v.timestamp_subsec_nanos() as i32,
+    })
+}
+
+/// A malformed request field. Helpers return this instead of a whole
+/// `Status`, which is large; `?` turns it into `invalid_argument` at the RPC.
+#[derive(Debug)]
+pub(crate) struct InvalidArgument(String);
+
+impl From<InvalidArgument> for Status {
+    fn from(err: InvalidArgument) -> Self {
+        Status::invalid_argument(err.0)
+    }
+}
+
+pub(crate) fn parse_uuid(value: &str, field: &str) -> Result<Uuid, InvalidArgument> {
+    Uuid::parse_str(value).map_err(|_| InvalidArgument(format!("{} must be a UUID", field)))
+}
+
+/// Empty strings stand for "not set" in proto3.
+pub(crate) fn parse_optional_uuid(value: &str, field: &str) -> Result<Option<Uuid>, InvalidArgument> {
+    if value.is_empty() {
+        return Ok(None);
+    }
+    parse_uuid(value, field).map(Some)
+}
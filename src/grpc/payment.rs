@@ -0,0 +1,75 @@
+use sqlx::{Pool, Postgres};
+use tonic::{Request, Response, Status};
+
+use crate::grpc::{parse_uuid, timestamp};
+use crate::payment::{
+    model::PaymentModel,
+    schema::CreatePaymentSchema,
+    service::{self, PaymentError},
+};
+use proto::payment_service_server::{PaymentService, PaymentServiceServer};
+use proto::{CreatePaymentRequest, CreatePaymentResponse};
+
+pub mod proto {
+    tonic::include_proto!("payment");
+}
+
+pub struct PaymentGrpcService {
+    db: Pool<Postgres>,
+}
+
+pub fn server(db: Pool<Postgres>) -> PaymentServiceServer<PaymentGrpcService> {
+    PaymentServiceServer::new(PaymentGrpcService { db })
+}
+
+impl From<PaymentModel> for proto::PaymentModel {
+    fn from(payment: PaymentModel) -> Self {
+        proto::PaymentModel {
+            id: payment.id.to_string(),
+            name: payment.name,
+            description: payment.description,
+            price: payment.price,
+            user_id: payment.user_id.to_string(),
+            category_id: payment.category_id.to_string(),
+            household_id: payment.household_id.map(|id| id.to_string()).unwrap_or_default(),
+            created_at: timestamp(payment.created_at),
+            updated_at: timestamp(payment.updated_at),
+        }
+    }
+}
+
+impl From<PaymentError> for Status {
+    fn from(err: PaymentError) -> Self {
+        match err {
+            PaymentError::Invalid(message) => Status::invalid_argument(message),
+            PaymentError::Forbidden(message) => Status::permission_denied(message),
+            PaymentError::NotFound(message) => Status::not_found(message),
+            PaymentError::Database(err) => Status::internal(format!("Error: {:?}", err)),
+        }
+    }
+}
+
+#[tonic::async_trait]
+impl PaymentService for PaymentGrpcService {
+    async fn create_payment(
+        &self,
+        request: Request<CreatePaymentRequest>,
+    ) -> Result<Response<CreatePaymentResponse>, Status> {
+        let request = request.into_inner();
+
+        let create_payment_schema = CreatePaymentSchema {
+            userId: parse_uuid(&request.user_id, "userId")?,
+            categoryId: parse_uuid(&request.category_id, "categoryId")?,
+            name: request.name,
+            description: request.description,
+            price: request.price,
+        };
+
+        let payment = service::create_payment(&self.db, &create_payment_schema).await?;
+
+        Ok(Response::new(CreatePaymentResponse {
+            payment: Some(payment.into()),
+            message: "Payment created successfully".to_string(),
+        }))
+    }
+}
@@ -0,0 +1,221 @@
+use serde::Serialize;
+use serde_json::{json, Map, Value};
+use sqlx::{PgConnection, Pool, Postgres};
+use uuid::Uuid;
+
+use crate::{history::model::HistoryModel, outbox};
+
+#[derive(Debug, Clone, Copy)]
+pub enum Entity {
+    Category,
+    Payment,
+}
+
+impl Entity {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Entity::Category => "category",
+            Entity::Payment => "payment",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum Action {
+    Create,
+    Update,
+    Delete,
+    Restore,
+}
+
+impl Action {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Action::Create => "create",
+            Action::Update => "update",
+            Action::Delete => "delete",
+            Action::Restore => "restore",
+        }
+    }
+}
+
+/// Records a change inside the caller's transaction so the history row is
+/// committed (or rolled back) together with the change itself. The matching
+/// domain event goes to the outbox in the same transaction.
+pub async fn record<T: Serialize>(
+    conn: &mut PgConnection,
+    entity: Entity,
+    entity_id: Uuid,
+    action: Action,
+    actor_id: Option<Uuid>,
+    before: Option<&T>,
+    after: Option<&T>,
+) -> Result<(), sqlx::Error> {
+    let before = before.map(|b| serde_json::to_value(b).unwrap_or(Value::Null));
+    let after = after.map(|a| serde_json::to_value(a).unwrap_or(Value::Null));
+    let diff = diff(before.as_ref(), after.as_ref());
+
+    sqlx::query!(
+        "INSERT INTO change_history (entity_type, entity_id, action, actor_id, before, after, diff)
+        VALUES ($1, $2, $3, $4, $5, $6, $7)",
+        entity.as_str(),
+        entity_id,
+        action.as_str(),
+        actor_id,
+        before,
+        after,
+        diff
+    )
+    .execute(&mut *conn)
+    .await?;
+
+    outbox::event::enqueue(
+        conn,
+        entity,
+        entity_id,
+        action,
+        actor_id,
+        after.as_ref().or(before.as_ref()),
+        &diff,
+    )
+    .await?;
+
+    Ok(())
+}
+
+/// Records the creation of many rows at once, for bulk inserts. `rows` holds
+/// each new row's ID, the actor and the row itself. Like [`record`], the
+/// matching outbox events are written in the same transaction.
+pub async fn record_created<T: Serialize>(
+    conn: &mut PgConnection,
+    entity: Entity,
+    rows: &[(Uuid, Uuid, &T)],
+) -> Result<(), sqlx::Error> {
+    let entity_ids: Vec<Uuid> = rows.iter().map(|row| row.0).collect();
+    let actor_ids: Vec<Uuid> = rows.iter().map(|row| row.1).collect();
+    let afters: Vec<Value> = rows
+        .iter()
+        .map(|row| serde_json::to_value(row.2).unwrap_or(Value::Null))
+        .collect();
+    let diffs: Vec<Value> = afters.iter().map(|after| diff(None, Some(after))).collect();
+
+    sqlx::query!(
+        "INSERT INTO change_history (entity_type, entity_id, action, actor_id, after, diff)
+        SELECT $1, t.entity_id, $2, t.actor_id, t.after, t.diff
+        FROM UNNEST($3::uuid[], $4::uuid[], $5::jsonb[], $6::jsonb[]) AS t(entity_id, actor_id, after, diff)",
+        entity.as_str(),
+        Action::Create.as_str(),
+        &entity_ids,
+        &actor_ids,
+        &afters,
+        &diffs
+    )
+    .execute(&mut *conn)
+    .await?;
+
+    let events: Vec<(Uuid, Option<Uuid>, &Value, &Value)> = rows
+        .iter()
+        .zip(afters.iter().zip(diffs.iter()))
+        .map(|(row, (after, diff))| (row.0, Some(row.1), after, diff))
+        .collect();
+    outbox::event::enqueue_many(conn, entity, Action::Create, &events).await?;
+
+    Ok(())
+}
+
+pub async fn list(
+    pool: &Pool<Postgres>,
+    entity: Entity,
+    entity_id: Uuid,
+    limit: i64,
+    offset: i64,
+) -> Result<Vec<HistoryModel>, sqlx::Error> {
+    sqlx::query_as!(
+        HistoryModel,
+        "SELECT * FROM change_history WHERE entity_type = $1 AND entity_id = $2
+        ORDER BY created_at DESC LIMIT $3 OFFSET $4",
+        entity.as_str(),
+        entity_id,
+        limit,
+        offset
+    )
+    .fetch_all(pool)
+    .await
+}
+
+/// Field-level diff of two JSON objects: `{"field": {"from": .., "to": ..}}`
+/// for every top-level field whose value differs.
+fn diff(before: Option<&Value>, after: Option<&Value>) -> Value {
+    let empty = Map::new();
+    let before = before.and_then(Value::as_object).unwrap_or(&empty);
+    let after = after.and_then(Value::as_object).unwrap_or(&empty);
+
+    let mut changes = Map::new();
+    for key in before.keys().chain(after.keys()) {
+        if changes.contains_key(key) {
+            continue;
+        }
+        let from = before.get(key).unwrap_or(&Value::Null);
+        let to = after.get(key).unwrap_or(&Value::Null);
+        if from != to {
+            changes.insert(key.clone(), json!({"from": from, "to": to}));
+        }
+    }
+
+    Value::Object(changes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lists_only_changed_fields() {
+        let before = json!({"name": "Coffee", "price": 3.5, "deleted_at": null});
+        let after = json!({"name": "Coffee", "price": 4.0, "deleted_at": null});
+
+        assert_eq!(
+            diff(Some(&before), Some(&after)),
+            json!({"price": {"from": 3.5, "to": 4.0}})
+        );
+    }
+
+    #[test]
+    fn treats_missing_fields_as_null() {
+        let before = json!({"name": "Coffee", "description": "Morning"});
+        let after = json!({"name": "Coffee", "householdId": "h"});
+
+        assert_eq!(
+            diff(Some(&before), Some(&after)),
+            json!({
+                "description": {"from": "Morning", "to": null},
+                "householdId": {"from": null, "to": "h"},
+            })
+        );
+    }
+
+    #[test]
+    fn diffs_creations_and_deletions_against_nothing() {
+        let row = json!({"name": "Coffee", "price": 3.5});
+        let fields = json!({"name": {"from": null, "to": "Coffee"}, "price": {"from": null, "to": 3.5}});
+
+        assert_eq!(diff(None, Some(&row)), fields);
+        assert_eq!(
+            diff(Some(&row), None),
+            json!({"name": {"from": "Coffee", "to": null}, "price": {"from": 3.5, "to": null}})
+        );
+        assert_eq!(diff(Some(&row), Some(&row)), json!({}));
+        assert_eq!(diff(None, None), json!({}));
+    }
+
+    #[test]
+    fn compares_nested_values_as_a_whole() {
+        let before = json!({"tags": ["a", "b"]});
+        let after = json!({"tags": ["a", "c"]});
+
+        assert_eq!(
+            diff(Some(&before), Some(&after)),
+            json!({"tags": {"from": ["a", "b"], "to": ["a", "c"]}})
+        );
+    }
+}
@@ -0,0 +1,155 @@
+use actix_web::HttpResponse;
+use serde_json::json;
+use sqlx::{Pool, Postgres};
+use uuid::Uuid;
+
+use crate::history::recorder::Entity;
+
+/// A member's role in a household, ordered from least to most privileged.
+/// The creator of a personal (household-less) row is treated as its owner.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum MemberRole {
+    Viewer,
+    Editor,
+    Owner,
+}
+
+impl MemberRole {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MemberRole::Viewer => "viewer",
+            MemberRole::Editor => "editor",
+            MemberRole::Owner => "owner",
+        }
+    }
+
+    pub fn parse(role: &str) -> Option<Self> {
+        match role {
+            "viewer" => Some(MemberRole::Viewer),
+            "editor" => Some(MemberRole::Editor),
+            "owner" => Some(MemberRole::Owner),
+            _ => None,
+        }
+    }
+
+    pub fn can_write(&self) -> bool {
+        *self >= MemberRole::Editor
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Access {
+    Read,
+    Write,
+}
+
+pub async fn member_role(
+    db: &Pool<Postgres>,
+    household_id: Uuid,
+    user_id: Uuid,
+) -> Result<Option<MemberRole>, sqlx::Error> {
+    let role = sqlx::query_scalar!(
+        "SELECT role FROM household_members WHERE household_id = $1 AND user_id = $2",
+        household_id,
+        user_id
+    )
+    .fetch_optional(db)
+    .await?;
+
+    Ok(role.as_deref().and_then(MemberRole::parse))
+}
+
+/// The caller's role over a category or payment, whether or not it is in
+/// the trash. `None` when the row doesn't exist or isn't visible to them.
+pub async fn entity_role(
+    db: &Pool<Postgres>,
+    entity: Entity,
+    entity_id: Uuid,
+    user_id: Uuid,
+) -> Result<Option<MemberRole>, sqlx::Error> {
+    let role = match entity {
+        Entity::Category => {
+            sqlx::query_scalar!(
+                "SELECT CASE WHEN c.household_id IS NULL AND c.user_id = $2 THEN 'owner' ELSE m.role END
+                FROM categories c
+                LEFT JOIN household_members m ON m.household_id = c.household_id AND m.user_id = $2
+                WHERE c.id = $1",
+                entity_id,
+                user_id
+            )
+            .fetch_optional(db)
+            .await?
+        }
+        Entity::Payment => {
+            sqlx::query_scalar!(
+                "SELECT CASE WHEN p.household_id IS NULL AND p.user_id = $2 THEN 'owner' ELSE m.role END
+                FROM payments p
+                LEFT JOIN household_members m ON m.household_id = p.household_id AND m.user_id = $2
+                WHERE p.id = $1",
+                entity_id,
+                user_id
+            )
+            .fetch_optional(db)
+            .await?
+        }
+    };
+
+    Ok(role.flatten().as_deref().and_then(MemberRole::parse))
+}
+
+/// Checks the caller may read or change a category or payment, returning the
+/// response to send when they may not. Rows the caller can't see at all are
+/// reported as missing so their existence isn't leaked.
+pub async fn require(
+    db: &Pool<Postgres>,
+    entity: Entity,
+    entity_id: Uuid,
+    user_id: Uuid,
+    access: Access,
+) -> Result<MemberRole, HttpResponse> {
+    match entity_role(db, entity, entity_id, user_id).await {
+        Ok(Some(role)) if access == Access::Read || role.can_write() => Ok(role),
+        Ok(Some(_)) => Err(HttpResponse::Forbidden().json(
+            json!({"status": "fail","message": "Viewers can't change this household's ledger"})
+        )),
+        Ok(None) => {
+            let name = match entity {
+                Entity::Category => "Category",
+                Entity::Payment => "Payment",
+            };
+            let message = format!("{} with ID: {} not found", name, entity_id);
+            Err(HttpResponse::NotFound().json(json!({"status": "fail","message": message})))
+        }
+        Err(err) => {
+            let message = format!("Error: {:?}", err);
+            Err(HttpResponse::InternalServerError().json(json!({"status": "error","message": message})))
+        }
+    }
+}
+
+/// Like [`require`], for a household as a whole.
+pub async fn require_member(
+    db: &Pool<Postgres>,
+    household_id: Uuid,
+    user_id: Uuid,
+    minimum: MemberRole,
+) -> Result<MemberRole, HttpResponse> {
+    match member_role(db, household_id, user_id).await {
+        Ok(Some(role)) if role >= minimum => Ok(role),
+        Ok(Some(_)) => {
+            let message = match minimum {
+                MemberRole::Owner => "Only household owners can do this",
+                _ => "Viewers can't change this household's ledger",
+            };
+            Err(HttpResponse::Forbidden().json(json!({"status": "fail","message": message})))
+        }
+        Ok(None) => {
+            let message = format!("Household with ID: {} not found", household_id);
+            Err(HttpResponse::NotFound().json(json!({"status": "fail","message": message})))
+        }
+        Err(err) => {
+            let message = format!("Error: {:?}", err);
+            Err(HttpResponse::InternalServerError().json(json!({"status": "error","message": message})))
+        }
+    }
+}
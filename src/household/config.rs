@@ -0,0 +1,26 @@
+use lazy_static::lazy_static;
+use std::env;
+
+lazy_static! {
+    static ref HOUSEHOLD_CONFIG: Config = Config::from_env();
+}
+
+pub fn get_config() -> &'static Config {
+    &HOUSEHOLD_CONFIG
+}
+
+#[derive(Debug, Clone)]
+pub struct Config {
+    pub invitation_ttl_hours: i64,
+}
+
+impl Config {
+    fn from_env() -> Self {
+        Self {
+            invitation_ttl_hours: env::var("HOUSEHOLD_INVITATION_TTL_HOURS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(72),
+        }
+    }
+}
@@ -0,0 +1,749 @@
+use std::collections::HashMap;
+
+use crate::{
+    history::recorder::{ self, Action, Entity },
+    household::access::{ self, MemberRole },
+    household::config::get_config,
+    household::model::{ HouseholdModel, InvitationModel, MemberModel },
+    household::schema::{
+        AcceptInvitationSchema,
+        CreateInvitationSchema,
+        HouseholdSchema,
+        InvitationPath,
+        MemberPath,
+        UpdateMemberSchema,
+    },
+    category::model::CategoryModel,
+    jwt_auth,
+    mailer::{ self, Message },
+    payment::model::PaymentModel,
+    token,
+    AppState,
+};
+use actix_web::{ delete, get, patch, post, web, HttpResponse, Responder };
+use serde_json::json;
+use sqlx::{ Pool, Postgres };
+
+#[get("/")]
+async fn household_list_handler(
+    data: web::Data<AppState>,
+    jwt: jwt_auth::JwtMiddleware
+) -> impl Responder {
+    let query_result = sqlx
+        ::query!(
+            "SELECT h.id, h.name, h.created_at, h.updated_at, m.role
+            FROM households h JOIN household_members m ON m.household_id = h.id
+            WHERE m.user_id = $1 ORDER BY h.created_at",
+            jwt.user_id
+        )
+        .fetch_all(&data.db).await;
+
+    match query_result {
+        Ok(rows) => {
+            let households: Vec<serde_json::Value> = rows
+                .iter()
+                .map(|row| json!({
+                    "id": row.id,
+                    "name": row.name,
+                    "role": row.role,
+                    "createdAt": row.created_at,
+                    "updatedAt": row.updated_at
+                }))
+                .collect();
+
+            HttpResponse::Ok().json(json!({
+                "status": "success",
+                "results": households.len(),
+                "households": households
+            }))
+        }
+        Err(err) => {
+            let message = format!("Error: {:?}", err);
+            HttpResponse::InternalServerError().json(json!({"status": "error","message": message}))
+        }
+    }
+}
+
+#[post("/")]
+async fn create_household_handler(
+    body: web::Json<HouseholdSchema>,
+    data: web::Data<AppState>,
+    jwt: jwt_auth::JwtMiddleware
+) -> impl Responder {
+    let name = body.name.trim();
+    if name.is_empty() {
+        return HttpResponse::BadRequest().json(json!({"status": "fail","message": "name is required"}));
+    }
+
+    match insert_household(&data.db, name, jwt.user_id).await {
+        Ok(household) => {
+            let household_response =
+                serde_json::json!({"status": "success","data": serde_json::json!({
+                "household": household
+            })});
+
+            HttpResponse::Ok().json(household_response)
+        }
+        Err(err) => {
+            let message = format!("Error: {:?}", err);
+            HttpResponse::InternalServerError().json(json!({"status": "error","message": message}))
+        }
+    }
+}
+
+/// Accepts an invitation sent to the caller's email address.
+#[post("/invitations/accept")]
+async fn accept_invitation_handler(
+    body: web::Json<AcceptInvitationSchema>,
+    data: web::Data<AppState>,
+    jwt: jwt_auth::JwtMiddleware
+) -> impl Responder {
+    match accept_invitation(&data.db, &body.token, jwt.user_id).await {
+        Ok(Some(household)) => {
+            let household_response =
+                serde_json::json!({"status": "success","data": serde_json::json!({
+                "household": household
+            })});
+
+            HttpResponse::Ok().json(household_response)
+        }
+        Ok(None) => {
+            HttpResponse::BadRequest().json(
+                json!({"status": "fail","message": "Invitation is invalid, expired or meant for another email address"})
+            )
+        }
+        Err(err) => {
+            let message = format!("Error: {:?}", err);
+            HttpResponse::InternalServerError().json(json!({"status": "error","message": message}))
+        }
+    }
+}
+
+#[get("/{id}")]
+async fn get_household_handler(
+    path: web::Path<uuid::Uuid>,
+    data: web::Data<AppState>,
+    jwt: jwt_auth::JwtMiddleware
+) -> impl Responder {
+    let household_id = path.into_inner();
+    let role = match access::require_member(&data.db, household_id, jwt.user_id, MemberRole::Viewer).await {
+        Ok(role) => role,
+        Err(response) => return response,
+    };
+
+    let household = sqlx
+        ::query_as!(HouseholdModel, "SELECT * FROM households WHERE id = $1", household_id)
+        .fetch_one(&data.db).await;
+    let members = sqlx
+        ::query_as!(
+            MemberModel,
+            "SELECT m.household_id, m.user_id, u.name, u.email, m.role, m.created_at
+            FROM household_members m JOIN users u ON u.id = m.user_id
+            WHERE m.household_id = $1 ORDER BY m.created_at",
+            household_id
+        )
+        .fetch_all(&data.db).await;
+
+    match (household, members) {
+        (Ok(household), Ok(members)) => {
+            let household_response =
+                serde_json::json!({"status": "success","data": serde_json::json!({
+                "household": household,
+                "role": role.as_str(),
+                "members": members
+            })});
+
+            HttpResponse::Ok().json(household_response)
+        }
+        (Err(err), _) | (_, Err(err)) => {
+            let message = format!("Error: {:?}", err);
+            HttpResponse::InternalServerError().json(json!({"status": "error","message": message}))
+        }
+    }
+}
+
+#[patch("/{id}")]
+async fn edit_household_handler(
+    path: web::Path<uuid::Uuid>,
+    body: web::Json<HouseholdSchema>,
+    data: web::Data<AppState>,
+    jwt: jwt_auth::JwtMiddleware
+) -> impl Responder {
+    let household_id = path.into_inner();
+    if let Err(response) = access::require_member(&data.db, household_id, jwt.user_id, MemberRole::Owner).await {
+        return response;
+    }
+
+    let name = body.name.trim();
+    if name.is_empty() {
+        return HttpResponse::BadRequest().json(json!({"status": "fail","message": "name is required"}));
+    }
+
+    let query_result = sqlx
+        ::query_as!(
+            HouseholdModel,
+            "UPDATE households SET name = $1, updated_at = NOW() WHERE id = $2 RETURNING *",
+            name,
+            household_id
+        )
+        .fetch_one(&data.db).await;
+
+    match query_result {
+        Ok(household) => {
+            let household_response =
+                serde_json::json!({"status": "success","data": serde_json::json!({
+                "household": household
+            })});
+
+            HttpResponse::Ok().json(household_response)
+        }
+        Err(err) => {
+            let message = format!("Error: {:?}", err);
+            HttpResponse::InternalServerError().json(json!({"status": "error","message": message}))
+        }
+    }
+}
+
+/// Only empty households can be deleted; trashed rows count as content
+/// until they are purged.
+#[delete("/{id}")]
+async fn delete_household_handler(
+    path: web::Path<uuid::Uuid>,
+    data: web::Data<AppState>,
+    jwt: jwt_auth::JwtMiddleware
+) -> impl Responder {
+    let household_id = path.into_inner();
+    if let Err(response) = access::require_member(&data.db, household_id, jwt.user_id, MemberRole::Owner).await {
+        return response;
+    }
+
+    let in_use = sqlx
+        ::query_scalar!(
+            r#"SELECT EXISTS(SELECT 1 FROM categories WHERE household_id = $1)
+            OR EXISTS(SELECT 1 FROM payments WHERE household_id = $1) AS "in_use!""#,
+            household_id
+        )
+        .fetch_one(&data.db).await
+        .unwrap_or(true);
+
+    if in_use {
+        return HttpResponse::Conflict().json(
+            json!({"status": "fail","message": "Household still has categories or payments"})
+        );
+    }
+
+    let query_result = sqlx
+        ::query!("DELETE FROM households WHERE id = $1", household_id)
+        .execute(&data.db).await;
+
+    match query_result {
+        Ok(_) => HttpResponse::NoContent().finish(),
+        Err(err) => {
+            let message = format!("Error: {:?}", err);
+            HttpResponse::InternalServerError().json(json!({"status": "error","message": message}))
+        }
+    }
+}
+
+#[get("/{id}/invitations")]
+async fn invitation_list_handler(
+    path: web::Path<uuid::Uuid>,
+    data: web::Data<AppState>,
+    jwt: jwt_auth::JwtMiddleware
+) -> impl Responder {
+    let household_id = path.into_inner();
+    if let Err(response) = access::require_member(&data.db, household_id, jwt.user_id, MemberRole::Owner).await {
+        return response;
+    }
+
+    let query_result = sqlx
+        ::query_as!(
+            InvitationModel,
+            "SELECT * FROM household_invitations
+            WHERE household_id = $1 AND accepted_at IS NULL AND revoked_at IS NULL AND expires_at > NOW()
+            ORDER BY created_at DESC",
+            household_id
+        )
+        .fetch_all(&data.db).await;
+
+    match query_result {
+        Ok(invitations) => {
+            HttpResponse::Ok().json(json!({
+                "status": "success",
+                "results": invitations.len(),
+                "invitations": invitations
+            }))
+        }
+        Err(err) => {
+            let message = format!("Error: {:?}", err);
+            HttpResponse::InternalServerError().json(json!({"status": "error","message": message}))
+        }
+    }
+}
+
+/// Emails a single-use invitation link. Inviting the same address again
+/// replaces any invitation still pending for it.
+#[post("/{id}/invitations")]
+async fn create_invitation_handler(
+    path: web::Path<uuid::Uuid>,
+    body: web::Json<CreateInvitationSchema>,
+    data: web::Data<AppState>,
+    jwt: jwt_auth::JwtMiddleware
+) -> impl Responder {
+    let household_id = path.into_inner();
+    if let Err(response) = access::require_member(&data.db, household_id, jwt.user_id, MemberRole::Owner).await {
+        return response;
+    }
+
+    if !matches!(MemberRole::parse(&body.role), Some(MemberRole::Editor | MemberRole::Viewer)) {
+        return HttpResponse::BadRequest().json(
+            json!({"status": "fail","message": "role must be editor or viewer"})
+        );
+    }
+
+    let email = body.email.trim().to_lowercase();
+    if !email.contains('@') {
+        return HttpResponse::BadRequest().json(json!({"status": "fail","message": "email is invalid"}));
+    }
+
+    let invitation_token = token::generate();
+    let invitation = match insert_invitation(
+        &data.db,
+        household_id,
+        &email,
+        &body.role,
+        &invitation_token,
+        jwt.user_id
+    ).await {
+        Ok(invitation) => invitation,
+        Err(err) => {
+            let message = format!("Error: {:?}", err);
+            return HttpResponse::InternalServerError().json(json!({"status": "error","message": message}));
+        }
+    };
+
+    let household_name = sqlx
+        ::query_scalar!("SELECT name FROM households WHERE id = $1", household_id)
+        .fetch_one(&data.db).await
+        .unwrap_or_default();
+
+    let message = Message {
+        to: email,
+        subject: format!("You've been invited to {}", household_name),
+        body: format!(
+            "Hi,\n\nYou've been invited to join the household \"{}\" as {}. Open the link below to accept. It expires in {} hours.\n\n{}",
+            household_name,
+            body.role,
+            get_config().invitation_ttl_hours,
+            mailer::link("/households/accept", &invitation_token)
+        ),
+    };
+    if let Err(err) = data.mailer.send(&message).await {
+        log::error!("Failed to send household invitation: {}", err);
+    }
+
+    let invitation_response =
+        serde_json::json!({"status": "success","data": serde_json::json!({
+        "invitation": invitation
+    })});
+
+    HttpResponse::Ok().json(invitation_response)
+}
+
+#[delete("/{id}/invitations/{invitation_id}")]
+async fn revoke_invitation_handler(
+    path: web::Path<InvitationPath>,
+    data: web::Data<AppState>,
+    jwt: jwt_auth::JwtMiddleware
+) -> impl Responder {
+    if let Err(response) = access::require_member(&data.db, path.id, jwt.user_id, MemberRole::Owner).await {
+        return response;
+    }
+
+    let query_result = sqlx
+        ::query!(
+            "UPDATE household_invitations SET revoked_at = NOW()
+            WHERE id = $1 AND household_id = $2 AND accepted_at IS NULL AND revoked_at IS NULL",
+            path.invitation_id,
+            path.id
+        )
+        .execute(&data.db).await;
+
+    match query_result {
+        Ok(result) if result.rows_affected() == 0 => {
+            let message = format!("Pending invitation with ID: {} not found", path.invitation_id);
+            HttpResponse::NotFound().json(json!({"status": "fail","message": message}))
+        }
+        Ok(_) => HttpResponse::NoContent().finish(),
+        Err(err) => {
+            let message = format!("Error: {:?}", err);
+            HttpResponse::InternalServerError().json(json!({"status": "error","message": message}))
+        }
+    }
+}
+
+#[patch("/{id}/members/{user_id}")]
+async fn edit_member_handler(
+    path: web::Path<MemberPath>,
+    body: web::Json<UpdateMemberSchema>,
+    data: web::Data<AppState>,
+    jwt: jwt_auth::JwtMiddleware
+) -> impl Responder {
+    if let Err(response) = access::require_member(&data.db, path.id, jwt.user_id, MemberRole::Owner).await {
+        return response;
+    }
+
+    let role = match MemberRole::parse(&body.role) {
+        Some(role) => role,
+        None => {
+            return HttpResponse::BadRequest().json(
+                json!({"status": "fail","message": "role must be owner, editor or viewer"})
+            );
+        }
+    };
+
+    match change_member(&data.db, path.id, path.user_id, Some(role)).await {
+        Ok(MemberChange::Changed) => {
+            HttpResponse::Ok().json(json!({"status": "success","data": {"userId": path.user_id, "role": role.as_str()}}))
+        }
+        Ok(MemberChange::NotFound) => {
+            let message = format!("Member with ID: {} not found", path.user_id);
+            HttpResponse::NotFound().json(json!({"status": "fail","message": message}))
+        }
+        Ok(MemberChange::LastOwner) => {
+            HttpResponse::Conflict().json(json!({"status": "fail","message": "A household needs at least one owner"}))
+        }
+        Err(err) => {
+            let message = format!("Error: {:?}", err);
+            HttpResponse::InternalServerError().json(json!({"status": "error","message": message}))
+        }
+    }
+}
+
+/// Owners can remove anyone; every member can remove themselves.
+#[delete("/{id}/members/{user_id}")]
+async fn remove_member_handler(
+    path: web::Path<MemberPath>,
+    data: web::Data<AppState>,
+    jwt: jwt_auth::JwtMiddleware
+) -> impl Responder {
+    let minimum = if path.user_id == jwt.user_id { MemberRole::Viewer } else { MemberRole::Owner };
+    if let Err(response) = access::require_member(&data.db, path.id, jwt.user_id, minimum).await {
+        return response;
+    }
+
+    match change_member(&data.db, path.id, path.user_id, None).await {
+        Ok(MemberChange::Changed) => HttpResponse::NoContent().finish(),
+        Ok(MemberChange::NotFound) => {
+            let message = format!("Member with ID: {} not found", path.user_id);
+            HttpResponse::NotFound().json(json!({"status": "fail","message": message}))
+        }
+        Ok(MemberChange::LastOwner) => {
+            HttpResponse::Conflict().json(json!({"status": "fail","message": "A household needs at least one owner"}))
+        }
+        Err(err) => {
+            let message = format!("Error: {:?}", err);
+            HttpResponse::InternalServerError().json(json!({"status": "error","message": message}))
+        }
+    }
+}
+
+/// Moves the caller's personal categories and payments, trashed ones
+/// included, into the household.
+#[post("/{id}/import")]
+async fn import_ledger_handler(
+    path: web::Path<uuid::Uuid>,
+    data: web::Data<AppState>,
+    jwt: jwt_auth::JwtMiddleware
+) -> impl Responder {
+    let household_id = path.into_inner();
+    if let Err(response) = access::require_member(&data.db, household_id, jwt.user_id, MemberRole::Editor).await {
+        return response;
+    }
+
+    match move_personal_ledger(&data.db, household_id, jwt.user_id).await {
+        Ok((categories, payments)) => {
+            HttpResponse::Ok().json(json!({
+                "status": "success",
+                "data": {"categories": categories, "payments": payments}
+            }))
+        }
+        Err(err) => {
+            let message = format!("Error: {:?}", err);
+            HttpResponse::InternalServerError().json(json!({"status": "error","message": message}))
+        }
+    }
+}
+
+async fn insert_household(
+    db: &Pool<Postgres>,
+    name: &str,
+    owner_id: uuid::Uuid
+) -> Result<HouseholdModel, sqlx::Error> {
+    let mut tx = db.begin().await?;
+
+    let household = sqlx
+        ::query_as!(HouseholdModel, "INSERT INTO households (name) VALUES ($1) RETURNING *", name)
+        .fetch_one(&mut *tx).await?;
+
+    sqlx
+        ::query!(
+            "INSERT INTO household_members (household_id, user_id, role) VALUES ($1, $2, $3)",
+            household.id,
+            owner_id,
+            MemberRole::Owner.as_str()
+        )
+        .execute(&mut *tx).await?;
+
+    tx.commit().await?;
+    Ok(household)
+}
+
+async fn insert_invitation(
+    db: &Pool<Postgres>,
+    household_id: uuid::Uuid,
+    email: &str,
+    role: &str,
+    invitation_token: &str,
+    invited_by: uuid::Uuid
+) -> Result<InvitationModel, sqlx::Error> {
+    let expires_at = chrono::Utc::now() + chrono::Duration::hours(get_config().invitation_ttl_hours);
+    let mut tx = db.begin().await?;
+
+    sqlx
+        ::query!(
+            "UPDATE household_invitations SET revoked_at = NOW()
+            WHERE household_id = $1 AND email = $2 AND accepted_at IS NULL AND revoked_at IS NULL",
+            household_id,
+            email
+        )
+        .execute(&mut *tx).await?;
+
+    let invitation = sqlx
+        ::query_as!(
+            InvitationModel,
+            "INSERT INTO household_invitations (household_id, email, role, token_hash, invited_by, expires_at)
+            VALUES ($1, $2, $3, $4, $5, $6) RETURNING *",
+            household_id,
+            email,
+            role,
+            token::hash(invitation_token),
+            invited_by,
+            expires_at
+        )
+        .fetch_one(&mut *tx).await?;
+
+    tx.commit().await?;
+    Ok(invitation)
+}
+
+/// Returns `None` when the token is unknown, used, expired or addressed to
+/// someone other than the caller. Existing members keep their current role.
+async fn accept_invitation(
+    db: &Pool<Postgres>,
+    invitation_token: &str,
+    user_id: uuid::Uuid
+) -> Result<Option<HouseholdModel>, sqlx::Error> {
+    let mut tx = db.begin().await?;
+
+    let invitation = sqlx
+        ::query_as!(
+            InvitationModel,
+            "SELECT i.* FROM household_invitations i JOIN users u ON lower(u.email) = i.email
+            WHERE i.token_hash = $1 AND u.id = $2
+            AND i.accepted_at IS NULL AND i.revoked_at IS NULL AND i.expires_at > NOW()
+            FOR UPDATE OF i",
+            token::hash(invitation_token),
+            user_id
+        )
+        .fetch_optional(&mut *tx).await?;
+
+    let invitation = match invitation {
+        Some(invitation) => invitation,
+        None => return Ok(None),
+    };
+
+    sqlx
+        ::query!(
+            "INSERT INTO household_members (household_id, user_id, role) VALUES ($1, $2, $3)
+            ON CONFLICT (household_id, user_id) DO NOTHING",
+            invitation.household_id,
+            user_id,
+            invitation.role
+        )
+        .execute(&mut *tx).await?;
+
+    sqlx
+        ::query!("UPDATE household_invitations SET accepted_at = NOW() WHERE id = $1", invitation.id)
+        .execute(&mut *tx).await?;
+
+    let household = sqlx
+        ::query_as!(HouseholdModel, "SELECT * FROM households WHERE id = $1", invitation.household_id)
+        .fetch_one(&mut *tx).await?;
+
+    tx.commit().await?;
+    Ok(Some(household))
+}
+
+enum MemberChange {
+    Changed,
+    NotFound,
+    LastOwner,
+}
+
+/// Sets a member's role, or removes them when `role` is `None`, refusing to
+/// leave the household without an owner.
+async fn change_member(
+    db: &Pool<Postgres>,
+    household_id: uuid::Uuid,
+    user_id: uuid::Uuid,
+    role: Option<MemberRole>
+) -> Result<MemberChange, sqlx::Error> {
+    let mut tx = db.begin().await?;
+
+    // Locking every owner row serialises concurrent demotions.
+    let owners = sqlx
+        ::query_scalar!(
+            "SELECT user_id FROM household_members WHERE household_id = $1 AND role = 'owner' FOR UPDATE",
+            household_id
+        )
+        .fetch_all(&mut *tx).await?;
+
+    let current = sqlx
+        ::query_scalar!(
+            "SELECT role FROM household_members WHERE household_id = $1 AND user_id = $2",
+            household_id,
+            user_id
+        )
+        .fetch_optional(&mut *tx).await?;
+
+    if current.is_none() {
+        return Ok(MemberChange::NotFound);
+    }
+
+    let demotes_owner = owners.contains(&user_id) && role != Some(MemberRole::Owner);
+    if demotes_owner && owners.len() == 1 {
+        return Ok(MemberChange::LastOwner);
+    }
+
+    match role {
+        Some(role) => {
+            sqlx
+                ::query!(
+                    "UPDATE household_members SET role = $1 WHERE household_id = $2 AND user_id = $3",
+                    role.as_str(),
+                    household_id,
+                    user_id
+                )
+                .execute(&mut *tx).await?;
+        }
+        None => {
+            sqlx
+                ::query!(
+                    "DELETE FROM household_members WHERE household_id = $1 AND user_id = $2",
+                    household_id,
+                    user_id
+                )
+                .execute(&mut *tx).await?;
+        }
+    }
+
+    tx.commit().await?;
+    Ok(MemberChange::Changed)
+}
+
+async fn move_personal_ledger(
+    db: &Pool<Postgres>,
+    household_id: uuid::Uuid,
+    actor_id: uuid::Uuid
+) -> Result<(usize, usize), sqlx::Error> {
+    let mut tx = db.begin().await?;
+
+    let categories_before: HashMap<uuid::Uuid, CategoryModel> = sqlx
+        ::query_as!(
+            CategoryModel,
+            "SELECT * FROM categories WHERE user_id = $1 AND household_id IS NULL FOR UPDATE",
+            actor_id
+        )
+        .fetch_all(&mut *tx).await?
+        .into_iter()
+        .map(|category| (category.id, category))
+        .collect();
+
+    let categories = sqlx
+        ::query_as!(
+            CategoryModel,
+            "UPDATE categories SET household_id = $2, updated_at = NOW()
+            WHERE user_id = $1 AND household_id IS NULL RETURNING *",
+            actor_id,
+            household_id
+        )
+        .fetch_all(&mut *tx).await?;
+
+    for category in &categories {
+        recorder::record(
+            &mut tx,
+            Entity::Category,
+            category.id,
+            Action::Update,
+            Some(actor_id),
+            categories_before.get(&category.id),
+            Some(category)
+        ).await?;
+    }
+
+    let payments_before: HashMap<uuid::Uuid, PaymentModel> = sqlx
+        ::query_as!(
+            PaymentModel,
+            "SELECT * FROM payments WHERE user_id = $1 AND household_id IS NULL FOR UPDATE",
+            actor_id
+        )
+        .fetch_all(&mut *tx).await?
+        .into_iter()
+        .map(|payment| (payment.id, payment))
+        .collect();
+
+    let payments = sqlx
+        ::query_as!(
+            PaymentModel,
+            "UPDATE payments SET household_id = $2, updated_at = NOW()
+            WHERE user_id = $1 AND household_id IS NULL RETURNING *",
+            actor_id,
+            household_id
+        )
+        .fetch_all(&mut *tx).await?;
+
+    for payment in &payments {
+        recorder::record(
+            &mut tx,
+            Entity::Payment,
+            payment.id,
+            Action::Update,
+            Some(actor_id),
+            payments_before.get(&payment.id),
+            Some(payment)
+        ).await?;
+    }
+
+    tx.commit().await?;
+    Ok((categories.len(), payments.len()))
+}
+
+pub fn config(conf: &mut web::ServiceConfig) {
+    let scope = web
+        ::scope("/households")
+        .service(household_list_handler)
+        .service(create_household_handler)
+        .service(accept_invitation_handler)
+        .service(get_household_handler)
+        .service(edit_household_handler)
+        .service(delete_household_handler)
+        .service(invitation_list_handler)
+        .service(create_invitation_handler)
+        .service(revoke_invitation_handler)
+        .service(edit_member_handler)
+        .service(remove_member_handler)
+        .service(import_ledger_handler);
+
+    conf.service(scope);
+}
@@ -0,0 +1,5 @@
+pub mod access;
+pub mod config;
+pub mod handler;
+pub mod model;
+pub mod schema;
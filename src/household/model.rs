@@ -0,0 +1,47 @@
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use uuid::Uuid;
+
+#[derive(Debug, FromRow, Deserialize, Serialize)]
+pub struct HouseholdModel {
+    pub id: Uuid,
+    pub name: String,
+    #[serde(rename = "createdAt")]
+    pub created_at: Option<chrono::DateTime<chrono::Utc>>,
+    #[serde(rename = "updatedAt")]
+    pub updated_at: Option<chrono::DateTime<chrono::Utc>>
+}
+
+#[derive(Debug, FromRow, Deserialize, Serialize)]
+pub struct MemberModel {
+    #[serde(rename = "householdId")]
+    pub household_id: Uuid,
+    #[serde(rename = "userId")]
+    pub user_id: Uuid,
+    pub name: String,
+    pub email: String,
+    pub role: String,
+    #[serde(rename = "createdAt")]
+    pub created_at: chrono::DateTime<chrono::Utc>
+}
+
+#[derive(Debug, FromRow, Deserialize, Serialize)]
+pub struct InvitationModel {
+    pub id: Uuid,
+    #[serde(rename = "householdId")]
+    pub household_id: Uuid,
+    pub email: String,
+    pub role: String,
+    #[serde(skip_serializing, default)]
+    pub token_hash: String,
+    #[serde(rename = "invitedBy")]
+    pub invited_by: Option<Uuid>,
+    #[serde(rename = "expiresAt")]
+    pub expires_at: chrono::DateTime<chrono::Utc>,
+    #[serde(rename = "acceptedAt")]
+    pub accepted_at: Option<chrono::DateTime<chrono::Utc>>,
+    #[serde(rename = "revokedAt")]
+    pub revoked_at: Option<chrono::DateTime<chrono::Utc>>,
+    #[serde(rename = "createdAt")]
+    pub created_at: chrono::DateTime<chrono::Utc>
+}
@@ -0,0 +1,36 @@
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct HouseholdSchema {
+    pub name: String,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct CreateInvitationSchema {
+    pub email: String,
+    /// `editor` or `viewer`.
+    pub role: String,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct AcceptInvitationSchema {
+    pub token: String,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct UpdateMemberSchema {
+    pub role: String,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct MemberPath {
+    pub id: Uuid,
+    pub user_id: Uuid,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct InvitationPath {
+    pub id: Uuid,
+    pub invitation_id: Uuid,
+}
@@ -0,0 +1,52 @@
+use lazy_static::lazy_static;
+use std::env;
+
+lazy_static! {
+    static ref JWT_CONFIG: Config = Config::from_env();
+}
+
+pub fn get_config() -> &'static Config {
+    &JWT_CONFIG
+}
+
+#[derive(Debug, Clone)]
+pub struct Config {
+    /// `HS256`, `RS256` or `EdDSA`.
+    pub algorithm: String,
+    /// `kid` stamped on every token signed with the active key.
+    pub key_id: String,
+    /// HMAC secret, only read for `HS256`.
+    pub secret: Option<String>,
+    /// PEM private key, only read for `RS256`/`EdDSA`.
+    pub private_key_path: Option<String>,
+    /// Retired keys still accepted for verification, as `kid=value` pairs:
+    /// paths to PEM public keys for asymmetric algorithms, secrets for `HS256`.
+    pub previous_keys: Vec<(String, String)>,
+    pub max_age_minutes: i64,
+}
+
+impl Config {
+    fn from_env() -> Self {
+        Self {
+            algorithm: env::var("JWT_ALGORITHM").unwrap_or_else(|_| "HS256".to_string()),
+            key_id: env::var("JWT_KEY_ID").unwrap_or_else(|_| "default".to_string()),
+            secret: env::var("JWT_SECRET_KEY").ok(),
+            private_key_path: env::var("JWT_PRIVATE_KEY_PATH").ok(),
+            previous_keys: env::var("JWT_PREVIOUS_KEYS")
+                .map(|v| parse_key_list(&v))
+                .unwrap_or_default(),
+            max_age_minutes: env::var("JWT_MAXAGE")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(60),
+        }
+    }
+}
+
+fn parse_key_list(value: &str) -> Vec<(String, String)> {
+    value
+        .split(',')
+        .filter_map(|entry| entry.trim().split_once('='))
+        .map(|(kid, key)| (kid.trim().to_string(), key.trim().to_string()))
+        .collect()
+}
@@ -0,0 +1,13 @@
+use crate::jwt::keys;
+use actix_web::{ get, http::header, web, HttpResponse, Responder };
+
+#[get("/.well-known/jwks.json")]
+async fn jwks_handler() -> impl Responder {
+    HttpResponse::Ok()
+        .insert_header((header::CACHE_CONTROL, "public, max-age=300"))
+        .json(keys::jwks())
+}
+
+pub fn config(conf: &mut web::ServiceConfig) {
+    conf.service(jwks_handler);
+}
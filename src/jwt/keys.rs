@@ -0,0 +1,313 @@
+use std::{collections::HashMap, fs};
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use jsonwebtoken::{
+    decode, decode_header, encode, errors::Error as JwtError, errors::ErrorKind, Algorithm,
+    DecodingKey, EncodingKey, Header, TokenData, Validation,
+};
+use lazy_static::lazy_static;
+use openssl::pkey::{Id, PKey, Public};
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::{json, Value};
+
+use crate::jwt::config::get_config;
+
+lazy_static! {
+    static ref KEY_STORE: Result<KeyStore, String> = KeyStore::from_config();
+}
+
+/// Loads the configured keys, reporting what is wrong with the configuration
+/// instead of failing on the first token signed or verified. Called at startup.
+pub fn init() -> Result<(), String> {
+    KEY_STORE.as_ref().map(|_| ()).map_err(Clone::clone)
+}
+
+fn store() -> Result<&'static KeyStore, JwtError> {
+    KEY_STORE
+        .as_ref()
+        .map_err(|_| JwtError::from(ErrorKind::InvalidKeyFormat))
+}
+
+struct VerificationKey {
+    algorithm: Algorithm,
+    key: DecodingKey,
+    jwk: Option<Value>,
+}
+
+/// The active signing key plus every key tokens may still be verified with,
+/// indexed by `kid`.
+struct KeyStore {
+    key_id: String,
+    algorithm: Algorithm,
+    signing: EncodingKey,
+    verification: HashMap<String, VerificationKey>,
+}
+
+impl KeyStore {
+    fn from_config() -> Result<Self, String> {
+        let config = get_config();
+        let mut verification = HashMap::new();
+
+        let (algorithm, signing) = match config.algorithm.as_str() {
+            "HS256" => {
+                let secret = config
+                    .secret
+                    .as_ref()
+                    .ok_or("JWT_SECRET_KEY must be set for HS256")?;
+                verification.insert(config.key_id.clone(), hmac_key(secret));
+                for (kid, secret) in &config.previous_keys {
+                    verification.insert(kid.clone(), hmac_key(secret));
+                }
+                (Algorithm::HS256, EncodingKey::from_secret(secret.as_bytes()))
+            }
+            "RS256" | "EdDSA" => {
+                let path = config
+                    .private_key_path
+                    .as_ref()
+                    .ok_or("JWT_PRIVATE_KEY_PATH must be set for asymmetric algorithms")?;
+                let pem = fs::read(path)
+                    .map_err(|err| format!("Unable to read JWT private key {}: {}", path, err))?;
+                let invalid = |err: &dyn std::fmt::Display| format!("Invalid JWT private key: {}", err);
+                let private = PKey::private_key_from_pem(&pem).map_err(|err| invalid(&err))?;
+                let public_pem = private.public_key_to_pem().map_err(|err| invalid(&err))?;
+
+                let signing = match private.id() {
+                    Id::RSA => EncodingKey::from_rsa_pem(&pem),
+                    Id::ED25519 => EncodingKey::from_ed_pem(&pem),
+                    _ => return Err("Unsupported JWT private key type".to_string()),
+                }
+                .map_err(|err| invalid(&err))?;
+
+                let active = public_key(&config.key_id, &public_pem)?;
+                let algorithm = active.algorithm;
+                verification.insert(config.key_id.clone(), active);
+                for (kid, path) in &config.previous_keys {
+                    let pem = fs::read(path)
+                        .map_err(|err| format!("Unable to read JWT verification key {}: {}", path, err))?;
+                    verification.insert(kid.clone(), public_key(kid, &pem)?);
+                }
+                (algorithm, signing)
+            }
+            other => return Err(format!("Unsupported JWT_ALGORITHM: {}", other)),
+        };
+
+        Ok(Self {
+            key_id: config.key_id.clone(),
+            algorithm,
+            signing,
+            verification,
+        })
+    }
+}
+
+fn hmac_key(secret: &str) -> VerificationKey {
+    VerificationKey {
+        algorithm: Algorithm::HS256,
+        key: DecodingKey::from_secret(secret.as_bytes()),
+        jwk: None,
+    }
+}
+
+fn public_key(kid: &str, pem: &[u8]) -> Result<VerificationKey, String> {
+    let invalid = |err: &dyn std::fmt::Display| format!("Invalid JWT public key for kid {}: {}", kid, err);
+    let public: PKey<Public> = PKey::public_key_from_pem(pem).map_err(|err| invalid(&err))?;
+
+    match public.id() {
+        Id::RSA => {
+            let rsa = public.rsa().map_err(|err| invalid(&err))?;
+            Ok(VerificationKey {
+                algorithm: Algorithm::RS256,
+                key: DecodingKey::from_rsa_pem(pem).map_err(|err| invalid(&err))?,
+                jwk: Some(json!({
+                    "kty": "RSA",
+                    "use": "sig",
+                    "alg": "RS256",
+                    "kid": kid,
+                    "n": URL_SAFE_NO_PAD.encode(rsa.n().to_vec()),
+                    "e": URL_SAFE_NO_PAD.encode(rsa.e().to_vec()),
+                })),
+            })
+        }
+        Id::ED25519 => {
+            let raw = public.raw_public_key().map_err(|err| invalid(&err))?;
+            Ok(VerificationKey {
+                algorithm: Algorithm::EdDSA,
+                key: DecodingKey::from_ed_pem(pem).map_err(|err| invalid(&err))?,
+                jwk: Some(json!({
+                    "kty": "OKP",
+                    "use": "sig",
+                    "alg": "EdDSA",
+                    "crv": "Ed25519",
+                    "kid": kid,
+                    "x": URL_SAFE_NO_PAD.encode(raw),
+                })),
+            })
+        }
+        _ => Err(format!("Unsupported JWT public key type for kid {}", kid)),
+    }
+}
+
+/// Signs `claims` with the active key, stamping its `kid` on the header.
+pub fn sign<T: Serialize>(claims: &T) -> Result<String, JwtError> {
+    store()?.sign(claims)
+}
+
+/// Verifies `token` with the key named by its `kid`. Tokens without a `kid`
+/// were issued before key rotation and are checked against the active key.
+pub fn verify<T: DeserializeOwned>(token: &str) -> Result<TokenData<T>, JwtError> {
+    store()?.verify(token)
+}
+
+/// Public verification keys as a JWKS document. HMAC secrets are never published.
+pub fn jwks() -> Value {
+    store().map(KeyStore::jwks).unwrap_or_else(|_| json!({ "keys": [] }))
+}
+
+impl KeyStore {
+    fn sign<T: Serialize>(&self, claims: &T) -> Result<String, JwtError> {
+        let mut header = Header::new(self.algorithm);
+        header.kid = Some(self.key_id.clone());
+
+        encode(&header, claims, &self.signing)
+    }
+
+    fn verify<T: DeserializeOwned>(&self, token: &str) -> Result<TokenData<T>, JwtError> {
+        let header = decode_header(token)?;
+        let kid = header.kid.unwrap_or_else(|| self.key_id.clone());
+
+        let key = self
+            .verification
+            .get(&kid)
+            .ok_or_else(|| JwtError::from(ErrorKind::InvalidKeyFormat))?;
+
+        decode::<T>(token, &key.key, &Validation::new(key.algorithm))
+    }
+
+    fn jwks(&self) -> Value {
+        let keys: Vec<&Value> = self.verification.values().filter_map(|key| key.jwk.as_ref()).collect();
+
+        json!({ "keys": keys })
+    }
+}
+
+pub fn max_age_minutes() -> i64 {
+    get_config().max_age_minutes
+}
+
+#[cfg(test)]
+mod tests {
+    use openssl::{pkey::Private, rsa::Rsa};
+
+    use super::*;
+
+    fn claims() -> Value {
+        json!({ "sub": "user", "exp": chrono::Utc::now().timestamp() + 60 })
+    }
+
+    fn hmac_store(key_id: &str, secret: &str, previous: &[(&str, &str)]) -> KeyStore {
+        let mut verification = HashMap::new();
+        verification.insert(key_id.to_string(), hmac_key(secret));
+        for (kid, secret) in previous {
+            verification.insert(kid.to_string(), hmac_key(secret));
+        }
+
+        KeyStore {
+            key_id: key_id.to_string(),
+            algorithm: Algorithm::HS256,
+            signing: EncodingKey::from_secret(secret.as_bytes()),
+            verification,
+        }
+    }
+
+    fn asymmetric_store(key_id: &str, private: &PKey<Private>) -> KeyStore {
+        let pem = private.private_key_to_pem_pkcs8().unwrap();
+        let signing = match private.id() {
+            Id::RSA => EncodingKey::from_rsa_pem(&pem),
+            _ => EncodingKey::from_ed_pem(&pem),
+        }
+        .unwrap();
+        let active = public_key(key_id, &private.public_key_to_pem().unwrap()).unwrap();
+
+        KeyStore {
+            key_id: key_id.to_string(),
+            algorithm: active.algorithm,
+            signing,
+            verification: HashMap::from([(key_id.to_string(), active)]),
+        }
+    }
+
+    fn rsa_key() -> PKey<Private> {
+        PKey::from_rsa(Rsa::generate(2048).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn verifies_tokens_signed_with_a_rotated_out_key() {
+        let old = hmac_store("old", "old-secret", &[]);
+        let current = hmac_store("new", "new-secret", &[("old", "old-secret")]);
+
+        let old_token = old.sign(&claims()).unwrap();
+        let new_token = current.sign(&claims()).unwrap();
+
+        assert_eq!(decode_header(&new_token).unwrap().kid.as_deref(), Some("new"));
+        assert_eq!(current.verify::<Value>(&old_token).unwrap().claims["sub"], "user");
+        assert_eq!(current.verify::<Value>(&new_token).unwrap().claims["sub"], "user");
+        assert!(old.verify::<Value>(&new_token).is_err());
+    }
+
+    #[test]
+    fn checks_tokens_without_kid_against_the_active_key() {
+        let store = hmac_store("new", "new-secret", &[("old", "old-secret")]);
+        let token = encode(&Header::new(Algorithm::HS256), &claims(), &EncodingKey::from_secret(b"new-secret")).unwrap();
+        let stale = encode(&Header::new(Algorithm::HS256), &claims(), &EncodingKey::from_secret(b"old-secret")).unwrap();
+
+        assert!(store.verify::<Value>(&token).is_ok());
+        assert!(store.verify::<Value>(&stale).is_err());
+    }
+
+    #[test]
+    fn rejects_unknown_kids() {
+        let store = hmac_store("new", "shared-secret", &[]);
+        let token = hmac_store("retired", "shared-secret", &[]).sign(&claims()).unwrap();
+
+        let err = store.verify::<Value>(&token).unwrap_err();
+        assert!(matches!(err.kind(), ErrorKind::InvalidKeyFormat), "{:?}", err);
+    }
+
+    #[test]
+    fn pins_the_algorithm_to_the_key() {
+        let private = rsa_key();
+        let store = asymmetric_store("rsa", &private);
+
+        // An HS256 token keyed with the published RSA key must not verify
+        // just because it names the RSA kid.
+        let mut header = Header::new(Algorithm::HS256);
+        header.kid = Some("rsa".to_string());
+        let public_pem = private.public_key_to_pem().unwrap();
+        let forged = encode(&header, &claims(), &EncodingKey::from_secret(&public_pem)).unwrap();
+
+        let err = store.verify::<Value>(&forged).unwrap_err();
+        assert!(matches!(err.kind(), ErrorKind::InvalidAlgorithm), "{:?}", err);
+        assert!(store.verify::<Value>(&store.sign(&claims()).unwrap()).is_ok());
+    }
+
+    #[test]
+    fn publishes_only_asymmetric_keys() {
+        let rsa = asymmetric_store("rsa", &rsa_key());
+        let ed = asymmetric_store("ed", &PKey::generate_ed25519().unwrap());
+        let mut store = hmac_store("hmac", "secret", &[]);
+        store.verification.extend(rsa.verification);
+        store.verification.extend(ed.verification);
+
+        let jwks = store.jwks();
+        let mut keys: Vec<(&str, &str, &str)> = jwks["keys"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|key| (key["kid"].as_str().unwrap(), key["kty"].as_str().unwrap(), key["alg"].as_str().unwrap()))
+            .collect();
+        keys.sort();
+
+        assert_eq!(keys, [("ed", "OKP", "EdDSA"), ("rsa", "RSA", "RS256")]);
+        assert_eq!(hmac_store("hmac", "secret", &[]).jwks(), json!({ "keys": [] }));
+    }
+}
@@ -1,13 +1,20 @@
 use core::fmt;
-use std::future::{ready, Ready};
+use std::future::ready;
 
-use actix_web::error::ErrorUnauthorized;
-use actix_web::{dev::Payload, Error as ActixWebError};
+use actix_web::error::{ErrorForbidden, ErrorInternalServerError, ErrorUnauthorized};
+use actix_web::{dev::Payload, web, Error as ActixWebError};
 use actix_web::{http, FromRequest, HttpMessage, HttpRequest};
-use jsonwebtoken::{decode, DecodingKey, Validation};
+use futures::future::LocalBoxFuture;
 use serde::Serialize;
+use serde_json::json;
 
+use crate::api_key::auth as api_key_auth;
+use crate::audit::log::{self as audit, AuditEntry, AuditEvent, RequestMeta};
+use crate::jwt::keys;
 use crate::user::schema::TokenClaims;
+use crate::AppState;
+
+pub const API_KEY_HEADER: &str = "X-Api-Key";
 
 #[derive(Debug, Serialize)]
 struct ErrorResponse {
@@ -21,53 +28,129 @@ impl fmt::Display for ErrorResponse {
     }
 }
 
+fn fail(message: &str) -> ErrorResponse {
+    ErrorResponse {
+        status: "fail".to_string(),
+        message: message.to_string(),
+    }
+}
+
 pub struct JwtMiddleware {
     pub user_id: uuid::Uuid,
+    /// Set when the request authenticated with an API key instead of a session token.
+    pub api_key_id: Option<uuid::Uuid>,
+    /// Set when an admin is impersonating `user_id`.
+    pub impersonator_id: Option<uuid::Uuid>,
+}
+
+impl JwtMiddleware {
+    /// Whether the request comes from the user's own login rather than an
+    /// API key or an impersonating admin. Credential changes require it.
+    pub fn is_session(&self) -> bool {
+        self.api_key_id.is_none() && self.impersonator_id.is_none()
+    }
 }
 
 impl FromRequest for JwtMiddleware {
     type Error = ActixWebError;
-    type Future = Ready<Result<Self, Self::Error>>;
+    type Future = LocalBoxFuture<'static, Result<Self, Self::Error>>;
     fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
-        let token = req
-            .cookie("token")
-            .map(|c| c.value().to_string())
-            .or_else(|| {
-                req.headers()
-                    .get(http::header::AUTHORIZATION)
-                    .map(|h| h.to_str().unwrap().split_at(7).1.to_string())
-            });
-
-        if token.is_none() {
-            let json_error = ErrorResponse {
-                status: "fail".to_string(),
-                message: "You are not logged in, please provide token".to_string(),
-            };
-            return ready(Err(ErrorUnauthorized(json_error)));
+        let api_key = req
+            .headers()
+            .get(API_KEY_HEADER)
+            .and_then(|h| h.to_str().ok())
+            .map(str::to_string);
+
+        match api_key {
+            Some(api_key) => Box::pin(from_api_key(req.clone(), api_key)),
+            None => match from_token(req) {
+                Ok(jwt) if jwt.impersonator_id.is_some() && !is_read_request(req.method()) => {
+                    Box::pin(audit_impersonated_write(req.clone(), jwt))
+                }
+                result => Box::pin(ready(result)),
+            },
         }
+    }
+}
+
+fn from_token(req: &HttpRequest) -> Result<JwtMiddleware, ActixWebError> {
+    let token = req
+        .cookie("token")
+        .map(|c| c.value().to_string())
+        .or_else(|| {
+            req.headers()
+                .get(http::header::AUTHORIZATION)
+                .map(|h| h.to_str().unwrap().split_at(7).1.to_string())
+        });
+
+    if token.is_none() {
+        return Err(ErrorUnauthorized(fail("You are not logged in, please provide token")));
+    }
+
+    let claims = match keys::verify::<TokenClaims>(&token.unwrap()) {
+        Ok(c) if !c.claims.mfa_pending => c.claims,
+        _ => return Err(ErrorUnauthorized(fail("Invalid token"))),
+    };
+
+    let user_id = uuid::Uuid::parse_str(claims.sub.as_str()).unwrap();
+    let impersonator_id = match claims.act.as_deref().map(uuid::Uuid::parse_str) {
+        Some(Ok(admin_id)) => Some(admin_id),
+        Some(Err(_)) => return Err(ErrorUnauthorized(fail("Invalid token"))),
+        None => None,
+    };
 
-        let secret_key = "my_ultra_secure_secret";
-        let secret_key_bytes = secret_key.as_bytes();
-
-        let claims = match decode::<TokenClaims>(
-            &token.unwrap(),
-            &DecodingKey::from_secret(secret_key_bytes),
-            &Validation::default(),
-        ) {
-            Ok(c) => c.claims,
-            Err(_) => {
-                let json_error = ErrorResponse {
-                    status: "fail".to_string(),
-                    message: "Invalid token".to_string(),
-                };
-                return ready(Err(ErrorUnauthorized(json_error)));
-            }
-        };
-
-        let user_id = uuid::Uuid::parse_str(claims.sub.as_str()).unwrap();
-        req.extensions_mut()
-            .insert::<uuid::Uuid>(user_id.to_owned());
-
-        ready(Ok(JwtMiddleware { user_id }))
+    if claims.read_only && !is_read_request(req.method()) {
+        return Err(ErrorForbidden(fail("This impersonation session is read-only")));
     }
+
+    req.extensions_mut()
+        .insert::<uuid::Uuid>(user_id.to_owned());
+
+    Ok(JwtMiddleware { user_id, api_key_id: None, impersonator_id })
+}
+
+async fn from_api_key(req: HttpRequest, api_key: String) -> Result<JwtMiddleware, ActixWebError> {
+    let data = req
+        .app_data::<web::Data<AppState>>()
+        .ok_or_else(|| ErrorInternalServerError(fail("Application state is not configured")))?;
+
+    let key = match api_key_auth::authenticate(&data.db, &api_key).await {
+        Ok(Some(key)) => key,
+        Ok(None) => return Err(ErrorUnauthorized(fail("Invalid API key"))),
+        Err(_) => return Err(ErrorInternalServerError(fail("Unable to verify API key"))),
+    };
+
+    if !api_key_auth::allows(&key.scopes, req.method()) {
+        return Err(ErrorForbidden(fail("API key scope does not allow this request")));
+    }
+
+    req.extensions_mut()
+        .insert::<uuid::Uuid>(key.user_id.to_owned());
+
+    Ok(JwtMiddleware { user_id: key.user_id, api_key_id: Some(key.id), impersonator_id: None })
+}
+
+/// Changes made while impersonating are attributed to the target user
+/// everywhere else, so each one is tied back to the admin here.
+async fn audit_impersonated_write(req: HttpRequest, jwt: JwtMiddleware) -> Result<JwtMiddleware, ActixWebError> {
+    if let Some(data) = req.app_data::<web::Data<AppState>>() {
+        audit::record(&data.db, AuditEntry {
+            event: AuditEvent::Impersonation,
+            success: true,
+            user_id: Some(jwt.user_id),
+            email: None,
+            meta: &RequestMeta::from_request(&req),
+            metadata: json!({
+                "adminId": jwt.impersonator_id,
+                "method": req.method().as_str(),
+                "path": req.path()
+            }),
+        }).await;
+    }
+
+    Ok(jwt)
+}
+
+fn is_read_request(method: &http::Method) -> bool {
+    matches!(*method, http::Method::GET | http::Method::HEAD | http::Method::OPTIONS)
 }
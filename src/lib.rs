@@ -0,0 +1,34 @@
+//! Shared code for the HTTP server, the AMQP consumer and the gRPC binaries.
+
+pub mod amqp;
+pub mod api_key;
+pub mod audit;
+pub mod avatar;
+pub mod category;
+pub mod gdpr;
+pub mod grpc;
+pub mod history;
+pub mod household;
+pub mod jwt;
+pub mod jwt_auth;
+pub mod lockout;
+pub mod mailer;
+pub mod outbox;
+pub mod payment;
+pub mod preferences;
+pub mod token;
+pub mod trash;
+pub mod user;
+
+#[cfg(test)]
+mod test_support;
+
+use std::sync::Arc;
+
+use sqlx::{Pool, Postgres};
+
+pub struct AppState {
+    pub db: Pool<Postgres>,
+    pub mailer: Arc<dyn mailer::Mailer>,
+    pub avatar_storage: Arc<dyn avatar::storage::AvatarStorage>,
+}
@@ -0,0 +1,41 @@
+use lazy_static::lazy_static;
+use std::env;
+
+lazy_static! {
+    static ref LOCKOUT_CONFIG: Config = Config::from_env();
+}
+
+pub fn get_config() -> &'static Config {
+    &LOCKOUT_CONFIG
+}
+
+#[derive(Debug, Clone)]
+pub struct Config {
+    pub max_account_failures: i32,
+    pub max_ip_failures: i32,
+    pub failure_window_secs: i64,
+    pub lockout_base_secs: i64,
+    pub lockout_max_secs: i64,
+    pub max_reset_account_requests: i32,
+    pub max_reset_ip_requests: i32,
+    pub reset_window_secs: i64,
+}
+
+impl Config {
+    fn from_env() -> Self {
+        Self {
+            max_account_failures: env_or("LOGIN_MAX_ACCOUNT_FAILURES", 5),
+            max_ip_failures: env_or("LOGIN_MAX_IP_FAILURES", 20),
+            failure_window_secs: env_or("LOGIN_FAILURE_WINDOW_SECS", 900),
+            lockout_base_secs: env_or("LOGIN_LOCKOUT_BASE_SECS", 60),
+            lockout_max_secs: env_or("LOGIN_LOCKOUT_MAX_SECS", 3600),
+            max_reset_account_requests: env_or("PASSWORD_RESET_MAX_ACCOUNT_REQUESTS", 3),
+            max_reset_ip_requests: env_or("PASSWORD_RESET_MAX_IP_REQUESTS", 10),
+            reset_window_secs: env_or("PASSWORD_RESET_WINDOW_SECS", 3600),
+        }
+    }
+}
+
+fn env_or<T: std::str::FromStr>(key: &str, default: T) -> T {
+    env::var(key).ok().and_then(|v| v.parse().ok()).unwrap_or(default)
+}
@@ -0,0 +1,252 @@
+use argon2::{
+    password_hash::{rand_core::OsRng, PasswordHash, PasswordHasher, PasswordVerifier, SaltString},
+    Argon2,
+};
+use chrono::{DateTime, Utc};
+use lazy_static::lazy_static;
+use sqlx::{Pool, Postgres};
+
+use crate::lockout::config::get_config;
+
+lazy_static! {
+    /// Hash verified against when the account does not exist, so that a
+    /// login for an unknown email costs the same as one for a known email.
+    static ref DUMMY_HASH: String = {
+        let salt = SaltString::generate(&mut OsRng);
+        Argon2::default()
+            .hash_password(b"dummy-password-for-timing", &salt)
+            .expect("Error while hashing password")
+            .to_string()
+    };
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum Scope {
+    Account,
+    Ip,
+    /// Password reset requests per normalized email.
+    ResetAccount,
+    /// Password reset requests per client IP.
+    ResetIp,
+}
+
+impl Scope {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Scope::Account => "account",
+            Scope::Ip => "ip",
+            Scope::ResetAccount => "reset_account",
+            Scope::ResetIp => "reset_ip",
+        }
+    }
+
+    fn limit(&self) -> i32 {
+        let config = get_config();
+        match self {
+            Scope::Account => config.max_account_failures,
+            Scope::Ip => config.max_ip_failures,
+            Scope::ResetAccount => config.max_reset_account_requests,
+            Scope::ResetIp => config.max_reset_ip_requests,
+        }
+    }
+
+    fn window_secs(&self) -> i64 {
+        let config = get_config();
+        match self {
+            Scope::Account | Scope::Ip => config.failure_window_secs,
+            Scope::ResetAccount | Scope::ResetIp => config.reset_window_secs,
+        }
+    }
+}
+
+/// Accounts are keyed by normalized email rather than user ID so unknown
+/// emails lock out exactly like real ones.
+pub fn account_key(email: &str) -> String {
+    email.trim().to_lowercase()
+}
+
+/// Verifies `password` against `hash`, or against a dummy hash when there is
+/// no account, so both paths spend the same time in Argon2.
+pub fn verify_password(password: &str, hash: Option<&str>) -> bool {
+    let (hash, exists) = match hash {
+        Some(hash) => (hash, true),
+        None => (DUMMY_HASH.as_str(), false),
+    };
+
+    let verified = PasswordHash::new(hash)
+        .map(|parsed| Argon2::default().verify_password(password.as_bytes(), &parsed).is_ok())
+        .unwrap_or(false);
+
+    exists && verified
+}
+
+/// Returns the latest `locked_until` among the given keys if any is still locked.
+pub async fn locked_until(
+    db: &Pool<Postgres>,
+    keys: &[(Scope, &str)],
+) -> Result<Option<DateTime<Utc>>, sqlx::Error> {
+    let mut latest: Option<DateTime<Utc>> = None;
+
+    for (scope, key) in keys {
+        let until = sqlx::query_scalar!(
+            "SELECT locked_until FROM login_throttles
+            WHERE scope = $1 AND key = $2 AND locked_until > NOW()",
+            scope.as_str(),
+            key
+        )
+        .fetch_optional(db)
+        .await?
+        .flatten();
+
+        if let Some(until) = until {
+            latest = Some(latest.map_or(until, |l| l.max(until)));
+        }
+    }
+
+    Ok(latest)
+}
+
+/// Counts a failed attempt. Failures older than the configured window are
+/// forgotten; once the threshold is reached the key is locked for
+/// `base * 2^(failures - threshold)` seconds, capped at the configured maximum.
+pub async fn register_failure(db: &Pool<Postgres>, scope: Scope, key: &str) -> Result<(), sqlx::Error> {
+    let failures = increment(db, scope, key).await?;
+
+    if let Some(backoff) = lockout_secs(scope, failures) {
+        sqlx::query!(
+            "UPDATE login_throttles SET locked_until = NOW() + make_interval(secs => $3)
+            WHERE scope = $1 AND key = $2",
+            scope.as_str(),
+            key,
+            backoff as f64
+        )
+        .execute(db)
+        .await?;
+    }
+
+    Ok(())
+}
+
+/// How long `failures` within the window lock the key for, if at all.
+fn lockout_secs(scope: Scope, failures: i32) -> Option<i64> {
+    let config = get_config();
+    let over = failures - scope.limit();
+    if over < 0 {
+        return None;
+    }
+
+    Some(
+        config
+            .lockout_base_secs
+            .saturating_mul(1i64 << over.min(30))
+            .min(config.lockout_max_secs),
+    )
+}
+
+/// Counts a request against `key` and reports whether it is still within the
+/// scope's allowance. The count resets once a full window has passed since
+/// the previous request.
+pub async fn allow_request(db: &Pool<Postgres>, scope: Scope, key: &str) -> Result<bool, sqlx::Error> {
+    let requests = increment(db, scope, key).await?;
+
+    Ok(requests <= scope.limit())
+}
+
+/// Bumps the counter for `key`, restarting it when the previous hit is older
+/// than the scope's window, and returns the new count.
+async fn increment(db: &Pool<Postgres>, scope: Scope, key: &str) -> Result<i32, sqlx::Error> {
+    sqlx::query_scalar!(
+        "INSERT INTO login_throttles (scope, key, failures, last_failure_at)
+        VALUES ($1, $2, 1, NOW())
+        ON CONFLICT (scope, key) DO UPDATE SET
+            failures = CASE
+                WHEN login_throttles.last_failure_at < NOW() - make_interval(secs => $3) THEN 1
+                ELSE login_throttles.failures + 1
+            END,
+            last_failure_at = NOW()
+        RETURNING failures",
+        scope.as_str(),
+        key,
+        scope.window_secs() as f64
+    )
+    .fetch_one(db)
+    .await
+}
+
+pub async fn clear(db: &Pool<Postgres>, scope: Scope, key: &str) -> Result<u64, sqlx::Error> {
+    let result = sqlx::query!(
+        "DELETE FROM login_throttles WHERE scope = $1 AND key = $2",
+        scope.as_str(),
+        key
+    )
+    .execute(db)
+    .await?;
+
+    Ok(result.rows_affected())
+}
+
+#[cfg(test)]
+mod tests {
+    use uuid::Uuid;
+
+    use super::*;
+    use crate::test_support;
+
+    #[test]
+    fn locks_once_the_threshold_is_reached() {
+        for scope in [Scope::Account, Scope::Ip] {
+            let limit = scope.limit();
+
+            assert_eq!(lockout_secs(scope, 1), None, "{:?}", scope);
+            assert_eq!(lockout_secs(scope, limit - 1), None, "{:?}", scope);
+            assert!(lockout_secs(scope, limit).is_some(), "{:?}", scope);
+        }
+    }
+
+    #[test]
+    fn doubles_the_lockout_per_failure_up_to_the_cap() {
+        let config = get_config();
+        let limit = Scope::Account.limit();
+        let mut expected = config.lockout_base_secs;
+
+        for failures in limit..limit + 40 {
+            assert_eq!(
+                lockout_secs(Scope::Account, failures),
+                Some(expected.min(config.lockout_max_secs)),
+                "{} failures",
+                failures
+            );
+            expected = expected.saturating_mul(2);
+        }
+        assert_eq!(lockout_secs(Scope::Account, i32::MAX), Some(config.lockout_max_secs));
+    }
+
+    #[test]
+    fn normalizes_account_keys() {
+        assert_eq!(account_key("  User@Example.COM "), "user@example.com");
+    }
+
+    #[test]
+    fn rejects_unknown_accounts_even_with_the_dummy_password() {
+        assert!(!verify_password("dummy-password-for-timing", None));
+    }
+
+    #[tokio::test]
+    #[ignore = "needs the docker-compose Postgres"]
+    async fn locks_and_clears_a_key() {
+        let db = test_support::pool().await;
+        let key = format!("{}@test.invalid", Uuid::new_v4());
+        let keys = [(Scope::Account, key.as_str())];
+
+        for _ in 1..Scope::Account.limit() {
+            register_failure(&db, Scope::Account, &key).await.unwrap();
+        }
+        assert_eq!(locked_until(&db, &keys).await.unwrap(), None);
+
+        register_failure(&db, Scope::Account, &key).await.unwrap();
+        assert!(locked_until(&db, &keys).await.unwrap().is_some());
+
+        assert_eq!(clear(&db, Scope::Account, &key).await.unwrap(), 1);
+        assert_eq!(locked_until(&db, &keys).await.unwrap(), None);
+    }
+}
@@ -0,0 +1,40 @@
+use lazy_static::lazy_static;
+use std::env;
+
+lazy_static! {
+    static ref MAILER_CONFIG: Config = Config::from_env();
+}
+
+pub fn get_config() -> &'static Config {
+    &MAILER_CONFIG
+}
+
+#[derive(Debug, Clone)]
+pub struct Config {
+    /// `log`, `file` or `smtp`.
+    pub backend: String,
+    pub from: String,
+    pub file_dir: String,
+    pub app_base_url: String,
+    pub smtp_host: Option<String>,
+    pub smtp_port: u16,
+    pub smtp_username: Option<String>,
+    pub smtp_password: Option<String>,
+    pub smtp_tls: bool,
+}
+
+impl Config {
+    fn from_env() -> Self {
+        Self {
+            backend: env::var("MAILER").unwrap_or_else(|_| "log".to_string()),
+            from: env::var("MAIL_FROM").unwrap_or_else(|_| "no-reply@localhost".to_string()),
+            file_dir: env::var("MAILER_FILE_DIR").unwrap_or_else(|_| "./mail".to_string()),
+            app_base_url: env::var("APP_BASE_URL").unwrap_or_else(|_| "http://localhost:3000".to_string()),
+            smtp_host: env::var("SMTP_HOST").ok(),
+            smtp_port: env::var("SMTP_PORT").ok().and_then(|v| v.parse().ok()).unwrap_or(587),
+            smtp_username: env::var("SMTP_USERNAME").ok().filter(|v| !v.is_empty()),
+            smtp_password: env::var("SMTP_PASSWORD").ok().filter(|v| !v.is_empty()),
+            smtp_tls: env::var("SMTP_TLS").map(|v| v != "false" && v != "0").unwrap_or(true),
+        }
+    }
+}
@@ -0,0 +1,40 @@
+use std::{error::Error, path::PathBuf};
+
+use async_trait::async_trait;
+
+use crate::mailer::{config::get_config, Mailer, Message};
+
+/// Writes each message as an `.eml` file, for local development and tests
+/// that need to read the mail back.
+pub struct FileMailer {
+    dir: PathBuf,
+}
+
+impl FileMailer {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+}
+
+#[async_trait]
+impl Mailer for FileMailer {
+    async fn send(&self, message: &Message) -> Result<(), Box<dyn Error + Send + Sync>> {
+        tokio::fs::create_dir_all(&self.dir).await?;
+
+        let file_name = format!(
+            "{}-{}.eml",
+            chrono::Utc::now().format("%Y%m%dT%H%M%S%.3f"),
+            uuid::Uuid::new_v4()
+        );
+        let contents = format!(
+            "From: {}\r\nTo: {}\r\nSubject: {}\r\n\r\n{}\r\n",
+            get_config().from,
+            message.to,
+            message.subject,
+            message.body
+        );
+
+        tokio::fs::write(self.dir.join(file_name), contents).await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,21 @@
+use std::error::Error;
+
+use async_trait::async_trait;
+
+use crate::mailer::{Mailer, Message};
+
+/// Writes mail to the application log instead of delivering it.
+pub struct LogMailer;
+
+#[async_trait]
+impl Mailer for LogMailer {
+    async fn send(&self, message: &Message) -> Result<(), Box<dyn Error + Send + Sync>> {
+        log::info!(
+            "Mail to {} | {}\n{}",
+            message.to,
+            message.subject,
+            message.body
+        );
+        Ok(())
+    }
+}
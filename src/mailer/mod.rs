@@ -0,0 +1,39 @@
+pub mod config;
+pub mod file;
+pub mod log;
+pub mod smtp;
+
+use std::{error::Error, sync::Arc};
+
+use async_trait::async_trait;
+
+use crate::mailer::config::get_config;
+
+#[derive(Debug, Clone)]
+pub struct Message {
+    pub to: String,
+    pub subject: String,
+    pub body: String,
+}
+
+#[async_trait]
+pub trait Mailer: Send + Sync {
+    async fn send(&self, message: &Message) -> Result<(), Box<dyn Error + Send + Sync>>;
+}
+
+/// Builds the mailer selected by `MAILER`.
+pub fn from_config() -> Arc<dyn Mailer> {
+    let config = get_config();
+
+    match config.backend.as_str() {
+        "file" => Arc::new(file::FileMailer::new(config.file_dir.clone())),
+        "log" => Arc::new(log::LogMailer),
+        "smtp" => Arc::new(smtp::SmtpMailer::new(config)),
+        other => panic!("Unsupported MAILER: {}", other),
+    }
+}
+
+/// Absolute link into the frontend, e.g. `link("/reset-password", &token)`.
+pub fn link(path: &str, token: &str) -> String {
+    format!("{}{}?token={}", get_config().app_base_url.trim_end_matches('/'), path, token)
+}
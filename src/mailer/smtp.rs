@@ -0,0 +1,50 @@
+use std::error::Error;
+
+use async_trait::async_trait;
+use lettre::{
+    message::Mailbox, transport::smtp::authentication::Credentials, AsyncSmtpTransport,
+    AsyncTransport, Message as Email, Tokio1Executor,
+};
+
+use crate::mailer::{config::Config, Mailer, Message};
+
+pub struct SmtpMailer {
+    from: Mailbox,
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+}
+
+impl SmtpMailer {
+    pub fn new(config: &Config) -> Self {
+        let host = config.smtp_host.as_deref().expect("SMTP_HOST must be set");
+
+        let mut builder = if config.smtp_tls {
+            AsyncSmtpTransport::<Tokio1Executor>::relay(host).expect("Invalid SMTP_HOST")
+        } else {
+            AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(host)
+        };
+
+        builder = builder.port(config.smtp_port);
+        if let (Some(username), Some(password)) = (&config.smtp_username, &config.smtp_password) {
+            builder = builder.credentials(Credentials::new(username.clone(), password.clone()));
+        }
+
+        Self {
+            from: config.from.parse().expect("Invalid MAIL_FROM"),
+            transport: builder.build(),
+        }
+    }
+}
+
+#[async_trait]
+impl Mailer for SmtpMailer {
+    async fn send(&self, message: &Message) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let email = Email::builder()
+            .from(self.from.clone())
+            .to(message.to.parse()?)
+            .subject(message.subject.clone())
+            .body(message.body.clone())?;
+
+        self.transport.send(email).await?;
+        Ok(())
+    }
+}
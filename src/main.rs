@@ -1,16 +1,10 @@
-mod user;
-mod category;
-mod payment;
-
 use actix_cors::Cors;
 use actix_web::middleware::Logger;
 use actix_web::{http::header, web, App, HttpServer};
+use backend::{amqp, api_key, audit, avatar, category, household, jwt, mailer, outbox, payment, trash, user, AppState};
 use dotenv::dotenv;
-use sqlx::{postgres::PgPoolOptions, Pool, Postgres};
-
-pub struct AppState {
-    db: Pool<Postgres>,
-}
+use sqlx::postgres::PgPoolOptions;
+use tokio::sync::watch;
 
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
@@ -20,6 +14,11 @@ async fn main() -> std::io::Result<()> {
     dotenv().ok();
     env_logger::init();
 
+    if let Err(err) = jwt::keys::init() {
+        println!("Failed to load the JWT keys: {}", err);
+        std::process::exit(1);
+    }
+
     let database_url = std::env::var("DATABASE_URL").expect("DATABASE_URL must be set");
     let pool = match PgPoolOptions::new()
         .max_connections(10)
@@ -41,18 +40,23 @@ async fn main() -> std::io::Result<()> {
         Err(e) => eprintln!("Error executing migrations: {}", e),
     };
 
-    let pg_pool_move = pool.clone();
-    tokio::spawn(async move {
-        let result = amqp::lib::run(pg_pool_move).await;
-        if result.is_err() {
-            println!("{}", result.unwrap_err().to_string());
-            std::process::exit(1)
-        }
-    });
+    let (shutdown_tx, shutdown_rx) = watch::channel(false);
+
+    let consumer = if amqp::config::get_config().consumer_enabled {
+        Some(tokio::spawn(amqp::payment::run(pool.clone(), shutdown_rx)))
+    } else {
+        None
+    };
+
+    tokio::spawn(trash::purge::run(pool.clone()));
+    tokio::spawn(outbox::relay::run(pool.clone()));
+
+    let mailer = mailer::from_config();
+    let avatar_storage = avatar::storage::from_config();
 
     println!("Server started successfully");
 
-    HttpServer::new(move || {
+    let server = HttpServer::new(move || {
         let cors = Cors::default()
             .allow_any_origin()
             .allowed_methods(vec!["GET", "POST", "PATCH", "DELETE"])
@@ -61,6 +65,7 @@ async fn main() -> std::io::Result<()> {
                 header::AUTHORIZATION,
                 header::ACCEPT,
                 header::REFERER,
+                header::HeaderName::from_static("x-api-key"),
             ])
             .supports_credentials();
         App::new()
@@ -68,11 +73,61 @@ async fn main() -> std::io::Result<()> {
                 actix_web::middleware::DefaultHeaders::new().add((header::REFERER, "*")),
             )
             .wrap(cors)
-            .app_data(web::Data::new(AppState { db: pool.clone() }))
+            .app_data(web::Data::new(AppState {
+                db: pool.clone(),
+                mailer: mailer.clone(),
+                avatar_storage: avatar_storage.clone(),
+            }))
             .configure(user::handler::config)
+            .configure(category::handler::config)
+            .configure(payment::handler::config)
+            .configure(household::handler::config)
+            .configure(audit::handler::config)
+            .configure(jwt::handler::config)
+            .configure(api_key::handler::config)
+            .configure(amqp::handler::config)
             .wrap(Logger::default())
     })
     .bind(("0.0.0.0", 8081))?
-    .run()
-    .await
+    .disable_signals()
+    .run();
+
+    // The HTTP server and the AMQP consumer drain in-flight work together.
+    let server_handle = server.handle();
+    tokio::spawn(async move {
+        shutdown_signal().await;
+        println!("Shutting down");
+        let _ = shutdown_tx.send(true);
+        server_handle.stop(true).await;
+    });
+
+    server.await?;
+
+    if let Some(consumer) = consumer {
+        let _ = consumer.await;
+    }
+
+    Ok(())
+}
+
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        tokio::signal::ctrl_c().await.expect("Failed to listen for Ctrl+C");
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+            .expect("Failed to listen for SIGTERM")
+            .recv()
+            .await;
+    };
+
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {}
+        _ = terminate => {}
+    }
 }
@@ -0,0 +1,40 @@
+use lazy_static::lazy_static;
+use std::env;
+
+lazy_static! {
+    static ref OUTBOX_CONFIG: Config = Config::from_env();
+}
+
+pub fn get_config() -> &'static Config {
+    &OUTBOX_CONFIG
+}
+
+#[derive(Debug, Clone)]
+pub struct Config {
+    pub relay_enabled: bool,
+    /// Topic exchange events are published to, routed by event type.
+    pub exchange: String,
+    pub poll_interval_ms: u64,
+    pub batch_size: i64,
+}
+
+impl Config {
+    fn from_env() -> Self {
+        Self {
+            relay_enabled: env::var("OUTBOX_RELAY_ENABLED")
+                .map(|v| v != "false" && v != "0")
+                .unwrap_or(true),
+            exchange: env::var("OUTBOX_EXCHANGE").unwrap_or_else(|_| "events.exchange".to_string()),
+            poll_interval_ms: env::var("OUTBOX_POLL_INTERVAL_MS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .filter(|&n: &u64| n > 0)
+                .unwrap_or(1000),
+            batch_size: env::var("OUTBOX_BATCH_SIZE")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .filter(|&n: &i64| n > 0)
+                .unwrap_or(100),
+        }
+    }
+}
@@ -0,0 +1,98 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use sqlx::{FromRow, PgConnection};
+use uuid::Uuid;
+
+use crate::history::recorder::{Action, Entity};
+
+#[derive(Debug, FromRow, Deserialize, Serialize)]
+pub struct OutboxEventModel {
+    pub id: Uuid,
+    #[serde(rename = "eventType")]
+    pub event_type: String,
+    #[serde(rename = "aggregateType")]
+    pub aggregate_type: String,
+    #[serde(rename = "aggregateId")]
+    pub aggregate_id: Uuid,
+    pub payload: Value,
+    pub attempts: i32,
+    #[serde(rename = "lastError")]
+    pub last_error: Option<String>,
+    #[serde(rename = "publishedAt")]
+    pub published_at: Option<chrono::DateTime<chrono::Utc>>,
+    #[serde(rename = "createdAt")]
+    pub created_at: chrono::DateTime<chrono::Utc>
+}
+
+/// `payment.created`, `category.deleted`, ... Also used as the routing key.
+pub fn event_type(entity: Entity, action: Action) -> String {
+    let verb = match action {
+        Action::Create => "created",
+        Action::Update => "updated",
+        Action::Delete => "deleted",
+        Action::Restore => "restored",
+    };
+    format!("{}.{}", entity.as_str(), verb)
+}
+
+/// Adds an event to the outbox inside the caller's transaction, so it is
+/// published if and only if the change commits.
+pub async fn enqueue(
+    conn: &mut PgConnection,
+    entity: Entity,
+    entity_id: Uuid,
+    action: Action,
+    actor_id: Option<Uuid>,
+    state: Option<&Value>,
+    changes: &Value,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "INSERT INTO outbox_events (event_type, aggregate_type, aggregate_id, payload)
+        VALUES ($1, $2, $3, $4)",
+        event_type(entity, action),
+        entity.as_str(),
+        entity_id,
+        payload(actor_id, state, changes)
+    )
+    .execute(conn)
+    .await?;
+
+    Ok(())
+}
+
+/// Like [`enqueue`] for many events of the same type with one `INSERT`.
+/// `events` holds each aggregate's ID, actor, state and changes.
+pub async fn enqueue_many(
+    conn: &mut PgConnection,
+    entity: Entity,
+    action: Action,
+    events: &[(Uuid, Option<Uuid>, &Value, &Value)],
+) -> Result<(), sqlx::Error> {
+    let aggregate_ids: Vec<Uuid> = events.iter().map(|event| event.0).collect();
+    let payloads: Vec<Value> = events
+        .iter()
+        .map(|&(_, actor_id, state, changes)| payload(actor_id, Some(state), changes))
+        .collect();
+
+    sqlx::query!(
+        "INSERT INTO outbox_events (event_type, aggregate_type, aggregate_id, payload)
+        SELECT $1, $2, t.aggregate_id, t.payload
+        FROM UNNEST($3::uuid[], $4::jsonb[]) AS t(aggregate_id, payload)",
+        event_type(entity, action),
+        entity.as_str(),
+        &aggregate_ids,
+        &payloads
+    )
+    .execute(conn)
+    .await?;
+
+    Ok(())
+}
+
+fn payload(actor_id: Option<Uuid>, state: Option<&Value>, changes: &Value) -> Value {
+    json!({
+        "actorId": actor_id,
+        "data": state,
+        "changes": changes
+    })
+}
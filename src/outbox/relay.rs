@@ -0,0 +1,140 @@
+use std::error::Error;
+use std::time::Duration;
+
+use lapin::{
+    options::{BasicPublishOptions, ConfirmSelectOptions, ExchangeDeclareOptions},
+    types::FieldTable,
+    BasicProperties, Channel, Connection, ConnectionProperties, ExchangeKind,
+};
+use serde_json::json;
+use sqlx::{Pool, Postgres};
+
+use crate::amqp;
+use crate::outbox::{config::get_config, event::OutboxEventModel};
+
+/// Publishes outbox events until the process exits, reconnecting to the
+/// broker whenever the connection is lost. Events stay in the table until the
+/// broker confirms them, so delivery is at least once and consumers should
+/// deduplicate on the message id.
+pub async fn run(pool: Pool<Postgres>) {
+    let config = get_config();
+
+    if !config.relay_enabled {
+        log::info!("Outbox relay disabled");
+        return;
+    }
+
+    loop {
+        if let Err(err) = relay(&pool).await {
+            log::error!("Outbox relay stopped: {}", err);
+        }
+        tokio::time::sleep(Duration::from_millis(config.poll_interval_ms)).await;
+    }
+}
+
+async fn relay(pool: &Pool<Postgres>) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let config = get_config();
+
+    let connection = Connection::connect(&amqp::config::get_config().amqp_addr, ConnectionProperties::default()).await?;
+    let channel = connection.create_channel().await?;
+    channel.confirm_select(ConfirmSelectOptions::default()).await?;
+    channel
+        .exchange_declare(
+            &config.exchange,
+            ExchangeKind::Topic,
+            ExchangeDeclareOptions {
+                durable: true,
+                ..Default::default()
+            },
+            FieldTable::default(),
+        )
+        .await?;
+
+    let mut interval = tokio::time::interval(Duration::from_millis(config.poll_interval_ms));
+
+    loop {
+        interval.tick().await;
+
+        // Keep draining while full batches come back instead of waiting a tick.
+        while publish_batch(pool, &channel).await? == config.batch_size as usize {}
+    }
+}
+
+/// Publishes the oldest unpublished events in order and marks each one once
+/// the broker has confirmed it. Rows are locked so several relays can run
+/// side by side. Returns how many events were published.
+async fn publish_batch(pool: &Pool<Postgres>, channel: &Channel) -> Result<usize, Box<dyn Error + Send + Sync>> {
+    let config = get_config();
+    let mut tx = pool.begin().await?;
+
+    let events = sqlx::query_as!(
+        OutboxEventModel,
+        "SELECT * FROM outbox_events WHERE published_at IS NULL
+        ORDER BY created_at LIMIT $1 FOR UPDATE SKIP LOCKED",
+        config.batch_size
+    )
+    .fetch_all(&mut *tx)
+    .await?;
+
+    let mut published = 0;
+    for event in events {
+        match publish(channel, &config.exchange, &event).await {
+            Ok(()) => {
+                sqlx::query!("UPDATE outbox_events SET published_at = NOW() WHERE id = $1", event.id)
+                    .execute(&mut *tx)
+                    .await?;
+                published += 1;
+            }
+            Err(err) => {
+                // Later events wait so that consumers see changes in order.
+                sqlx::query!(
+                    "UPDATE outbox_events SET attempts = attempts + 1, last_error = $2 WHERE id = $1",
+                    event.id,
+                    err.to_string()
+                )
+                .execute(&mut *tx)
+                .await?;
+                tx.commit().await?;
+                return Err(err);
+            }
+        }
+    }
+
+    tx.commit().await?;
+    Ok(published)
+}
+
+async fn publish(channel: &Channel, exchange: &str, event: &OutboxEventModel) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let body = json!({
+        "id": event.id,
+        "type": event.event_type,
+        "aggregateType": event.aggregate_type,
+        "aggregateId": event.aggregate_id,
+        "occurredAt": event.created_at,
+        "payload": event.payload
+    });
+
+    let properties = BasicProperties::default()
+        .with_content_type("application/json".into())
+        .with_delivery_mode(2)
+        .with_message_id(event.id.to_string().into())
+        .with_type(event.event_type.as_str().into())
+        .with_timestamp(event.created_at.timestamp() as u64);
+
+    let confirmation = channel
+        .basic_publish(
+            exchange,
+            &event.event_type,
+            BasicPublishOptions::default(),
+            &serde_json::to_vec(&body)?,
+            properties,
+        )
+        .await?
+        .await?;
+
+    if confirmation.is_nack() {
+        return Err(format!("Broker rejected event {}", event.id).into());
+    }
+
+    Ok(())
+}
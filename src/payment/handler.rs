@@ -0,0 +1,432 @@
+use crate::{
+    history::recorder::{ self, Entity },
+    household::access::{ self, Access, MemberRole },
+    jwt_auth,
+    preferences::format,
+    payment::model::PaymentModel,
+    payment::service::{ self, PaymentError, PaymentQuery },
+    payment::schema::{
+        CreatePaymentSchema,
+        FilterOptions,
+        SummaryOptions,
+        UpdatePaymentSchema,
+        VisibilityOptions,
+    },
+    AppState,
+};
+use actix_web::{
+    delete,
+    get,
+    patch,
+    post,
+    web,
+    HttpResponse,
+    Responder,
+};
+use serde_json::json;
+
+#[get("/")]
+pub async fn payment_list_handler(
+    opts: web::Query<FilterOptions>,
+    data: web::Data<AppState>,
+    jwt: jwt_auth::JwtMiddleware
+) -> impl Responder {
+    let limit = opts.limit.unwrap_or(10);
+    let offset = (opts.page.unwrap_or(1) - 1) * limit;
+    let with_deleted = opts.with_deleted.unwrap_or(false);
+
+    let query = PaymentQuery {
+        limit: limit as i64,
+        offset: offset as i64,
+        with_deleted,
+        household_id: opts.household_id,
+        from: opts.from,
+        to: opts.to,
+    };
+    let query_result = service::list_payments(&data.db, jwt.user_id, &query).await;
+
+    if query_result.is_err() {
+        let message = "Something bad happened while fetching all payment items";
+        return HttpResponse::InternalServerError().json(
+            json!({"status": "error","message": message})
+        );
+    }
+
+    let payments = query_result.unwrap();
+
+    let json_response =
+        serde_json::json!({
+        "status": "success",
+        "results": payments.len(),
+        "payments": payments
+    });
+    HttpResponse::Ok().json(json_response)
+}
+
+#[post("/")]
+async fn create_payment_handler(
+    body: web::Json<CreatePaymentSchema>,
+    data: web::Data<AppState>,
+    jwt: jwt_auth::JwtMiddleware
+) -> impl Responder {
+    if body.userId != jwt.user_id {
+        return HttpResponse::Forbidden().json(
+            serde_json::json!({"status": "fail","message": "Payments can only be created for yourself"})
+        );
+    }
+
+    match service::create_payment(&data.db, &body).await {
+        Ok(payment) => {
+            let payment_response =
+                serde_json::json!({"status": "success","data": serde_json::json!({
+                "payment": payment
+            })});
+
+            HttpResponse::Ok().json(payment_response)
+        }
+        Err(PaymentError::Invalid(message)) => {
+            HttpResponse::BadRequest().json(json!({"status": "fail","message": message}))
+        }
+        Err(PaymentError::Forbidden(message)) => {
+            HttpResponse::Forbidden().json(json!({"status": "fail","message": message}))
+        }
+        Err(PaymentError::NotFound(message)) => {
+            HttpResponse::NotFound().json(json!({"status": "fail","message": message}))
+        }
+        Err(PaymentError::Database(err)) => {
+            let message = format!("Error: {:?}", err);
+            HttpResponse::InternalServerError().json(json!({"status": "error","message": message}))
+        }
+    }
+}
+
+#[get("/trash")]
+async fn payment_trash_handler(
+    opts: web::Query<FilterOptions>,
+    data: web::Data<AppState>,
+    jwt: jwt_auth::JwtMiddleware
+) -> impl Responder {
+    let limit = opts.limit.unwrap_or(10);
+    let offset = (opts.page.unwrap_or(1) - 1) * limit;
+
+    let query_result = sqlx::query_as!(
+            PaymentModel,
+            "SELECT * FROM payments WHERE deleted_at IS NOT NULL
+            AND ((household_id IS NULL AND user_id = $1)
+                OR household_id IN (SELECT household_id FROM household_members WHERE user_id = $1))
+            AND ($4::uuid IS NULL OR household_id = $4)
+            ORDER by deleted_at DESC LIMIT $2 OFFSET $3",
+            jwt.user_id,
+            limit as i32,
+            offset as i32,
+            opts.household_id
+        )
+        .fetch_all(&data.db).await;
+
+    if query_result.is_err() {
+        let message = "Something bad happened while fetching trashed payment items";
+        return HttpResponse::InternalServerError().json(
+            json!({"status": "error","message": message})
+        );
+    }
+
+    let payments = query_result.unwrap();
+
+    let json_response =
+        serde_json::json!({
+        "status": "success",
+        "results": payments.len(),
+        "payments": payments
+    });
+    HttpResponse::Ok().json(json_response)
+}
+
+/// Totals the caller's personal payments, or a household's with `householdId`,
+/// per day, week or month, bucketed in the caller's preferred timezone and
+/// starting weeks on their preferred day.
+#[get("/summary")]
+async fn payment_summary_handler(
+    opts: web::Query<SummaryOptions>,
+    data: web::Data<AppState>,
+    jwt: jwt_auth::JwtMiddleware
+) -> impl Responder {
+    let period = opts.period.clone().unwrap_or_else(|| "month".to_string());
+    if !["day", "week", "month"].contains(&period.as_str()) {
+        return HttpResponse::BadRequest().json(
+            json!({"status": "fail","message": "period must be one of day, week or month"})
+        );
+    }
+
+    if let Some(household_id) = opts.household_id {
+        if let Err(response) = access::require_member(&data.db, household_id, jwt.user_id, MemberRole::Viewer).await {
+            return response;
+        }
+    }
+
+    let preferences = match format::load(&data.db, jwt.user_id).await {
+        Ok(preferences) => preferences,
+        Err(err) => {
+            let message = format!("Error: {:?}", err);
+            return HttpResponse::InternalServerError().json(json!({"status": "error","message": message}));
+        }
+    };
+    let week_offset = if period == "week" { format::week_offset_days(&preferences) } else { 0 };
+
+    let query_result = sqlx
+        ::query!(
+            r#"SELECT
+                date_trunc($2, (created_at AT TIME ZONE $3) + make_interval(days => $4))
+                    - make_interval(days => $4) AS "bucket!",
+                COALESCE(SUM(price), 0) AS "total!",
+                COUNT(*) AS "count!"
+            FROM payments
+            WHERE deleted_at IS NULL
+            AND (($7::uuid IS NULL AND household_id IS NULL AND user_id = $1) OR household_id = $7)
+            AND ($5::timestamptz IS NULL OR created_at >= $5)
+            AND ($6::timestamptz IS NULL OR created_at < $6)
+            GROUP BY 1 ORDER BY 1"#,
+            jwt.user_id,
+            period,
+            preferences.timezone,
+            week_offset,
+            opts.from,
+            opts.to,
+            opts.household_id
+        )
+        .fetch_all(&data.db).await;
+
+    match query_result {
+        Ok(rows) => {
+            let buckets: Vec<serde_json::Value> = rows
+                .iter()
+                .map(|row| json!({
+                    "start": row.bucket.date(),
+                    "label": format::format_date(row.bucket.date(), &preferences),
+                    "total": row.total,
+                    "formattedTotal": format::format_amount(row.total, &preferences),
+                    "count": row.count
+                }))
+                .collect();
+
+            HttpResponse::Ok().json(json!({
+                "status": "success",
+                "period": period,
+                "timezone": preferences.timezone,
+                "currency": preferences.currency,
+                "results": buckets.len(),
+                "buckets": buckets
+            }))
+        }
+        Err(err) => {
+            let message = format!("Error: {:?}", err);
+            HttpResponse::InternalServerError().json(json!({"status": "error","message": message}))
+        }
+    }
+}
+
+#[get("/{id}")]
+async fn get_payment_handler(
+    path: web::Path<uuid::Uuid>,
+    opts: web::Query<VisibilityOptions>,
+    data: web::Data<AppState>,
+    jwt: jwt_auth::JwtMiddleware
+) -> impl Responder {
+    let payment_id = path.into_inner();
+    if let Err(response) = access::require(&data.db, Entity::Payment, payment_id, jwt.user_id, Access::Read).await {
+        return response;
+    }
+
+    let with_deleted = opts.with_deleted.unwrap_or(false);
+    let query_result = sqlx
+        ::query_as!(
+            PaymentModel,
+            "SELECT * FROM payments WHERE id = $1 AND ($2 OR deleted_at IS NULL)",
+            payment_id,
+            with_deleted
+        )
+        .fetch_one(&data.db).await;
+
+    match query_result {
+        Ok(payment) => {
+            let payment_response =
+                serde_json::json!({"status": "success","data": serde_json::json!({
+                "payment": payment
+            })});
+
+            HttpResponse::Ok().json(payment_response)
+        }
+        Err(_) => {
+            let message = format!("Payment with ID: {} not found", payment_id);
+            HttpResponse::NotFound().json(
+                serde_json::json!({"status": "fail","message": message})
+            )
+        }
+    }
+}
+
+#[patch("/{id}")]
+async fn edit_payment_handler(
+    path: web::Path<uuid::Uuid>,
+    body: web::Json<UpdatePaymentSchema>,
+    data: web::Data<AppState>,
+    jwt: jwt_auth::JwtMiddleware
+) -> impl Responder {
+    let payment_id = path.into_inner();
+    if let Err(response) = access::require(&data.db, Entity::Payment, payment_id, jwt.user_id, Access::Write).await {
+        return response;
+    }
+
+    let query_result = service::update_payment(&data.db, payment_id, &body, jwt.user_id).await;
+
+    match query_result {
+        Ok(Some(payment)) => {
+            let payment_response =
+                serde_json::json!({"status": "success","data": serde_json::json!({
+                "payment": payment
+            })});
+
+            HttpResponse::Ok().json(payment_response)
+        }
+        Ok(None) => {
+            let message = format!("Payment with ID: {} not found", payment_id);
+            HttpResponse::NotFound().json(
+                serde_json::json!({"status": "fail","message": message})
+            )
+        }
+        Err(err) => {
+            let message = format!("Error: {:?}", err);
+            HttpResponse::InternalServerError().json(
+                serde_json::json!({"status": "error","message": message})
+            )
+        }
+    }
+}
+
+#[delete("/{id}")]
+async fn delete_payment_handler(
+    path: web::Path<uuid::Uuid>,
+    data: web::Data<AppState>,
+    jwt: jwt_auth::JwtMiddleware
+) -> impl Responder {
+    let payment_id = path.into_inner();
+    if let Err(response) = access::require(&data.db, Entity::Payment, payment_id, jwt.user_id, Access::Write).await {
+        return response;
+    }
+
+    let query_result = service::set_payment_deleted(&data.db, payment_id, true, jwt.user_id).await;
+
+    match query_result {
+        Ok(None) => {
+            let message = format!("Payment with ID: {} not found", payment_id);
+            HttpResponse::NotFound().json(json!({"status": "fail","message": message}))
+        }
+        Ok(Some(_)) => HttpResponse::NoContent().finish(),
+        Err(err) => {
+            let message = format!("Error: {:?}", err);
+            HttpResponse::InternalServerError().json(json!({"status": "error","message": message}))
+        }
+    }
+}
+
+#[post("/{id}/restore")]
+async fn restore_payment_handler(
+    path: web::Path<uuid::Uuid>,
+    data: web::Data<AppState>,
+    jwt: jwt_auth::JwtMiddleware
+) -> impl Responder {
+    let payment_id = path.into_inner();
+    if let Err(response) = access::require(&data.db, Entity::Payment, payment_id, jwt.user_id, Access::Write).await {
+        return response;
+    }
+
+    let category_trashed = sqlx
+        ::query_scalar!(
+            r#"SELECT c.deleted_at IS NOT NULL AS "trashed!"
+            FROM payments p JOIN categories c ON c.id = p.category_id
+            WHERE p.id = $1"#,
+            payment_id
+        )
+        .fetch_optional(&data.db).await;
+
+    if let Ok(Some(true)) = category_trashed {
+        let message = "Restore the payment's category before restoring the payment";
+        return HttpResponse::Conflict().json(json!({"status": "fail","message": message}));
+    }
+
+    let query_result = service::set_payment_deleted(&data.db, payment_id, false, jwt.user_id).await;
+
+    match query_result {
+        Ok(Some(payment)) => {
+            let payment_response =
+                serde_json::json!({"status": "success","data": serde_json::json!({
+                "payment": payment
+            })});
+
+            HttpResponse::Ok().json(payment_response)
+        }
+        Ok(None) => {
+            let message = format!("Trashed payment with ID: {} not found", payment_id);
+            HttpResponse::NotFound().json(json!({"status": "fail","message": message}))
+        }
+        Err(err) => {
+            let message = format!("Error: {:?}", err);
+            HttpResponse::InternalServerError().json(json!({"status": "error","message": message}))
+        }
+    }
+}
+
+#[get("/{id}/history")]
+async fn payment_history_handler(
+    path: web::Path<uuid::Uuid>,
+    opts: web::Query<FilterOptions>,
+    data: web::Data<AppState>,
+    jwt: jwt_auth::JwtMiddleware
+) -> impl Responder {
+    let payment_id = path.into_inner();
+    if let Err(response) = access::require(&data.db, Entity::Payment, payment_id, jwt.user_id, Access::Read).await {
+        return response;
+    }
+
+    let limit = opts.limit.unwrap_or(10);
+    let offset = (opts.page.unwrap_or(1) - 1) * limit;
+
+    let query_result = recorder::list(
+        &data.db,
+        Entity::Payment,
+        payment_id,
+        limit as i64,
+        offset as i64
+    ).await;
+
+    match query_result {
+        Ok(history) => {
+            let json_response =
+                serde_json::json!({
+                "status": "success",
+                "results": history.len(),
+                "history": history
+            });
+            HttpResponse::Ok().json(json_response)
+        }
+        Err(err) => {
+            let message = format!("Error: {:?}", err);
+            HttpResponse::InternalServerError().json(json!({"status": "error","message": message}))
+        }
+    }
+}
+
+pub fn config(conf: &mut web::ServiceConfig) {
+    let scope = web
+        ::scope("/payments")
+        .service(payment_list_handler)
+        .service(create_payment_handler)
+        .service(payment_trash_handler)
+        .service(payment_summary_handler)
+        .service(get_payment_handler)
+        .service(payment_history_handler)
+        .service(edit_payment_handler)
+        .service(delete_payment_handler)
+        .service(restore_payment_handler);
+
+    conf.service(scope);
+}
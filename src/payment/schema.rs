@@ -0,0 +1,57 @@
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+#[derive(Deserialize, Debug)]
+pub struct FilterOptions {
+    pub page: Option<usize>,
+    pub limit: Option<usize>,
+    #[serde(rename = "withDeleted")]
+    pub with_deleted: Option<bool>,
+    /// Narrows the list to one household's ledger.
+    #[serde(rename = "householdId")]
+    pub household_id: Option<Uuid>,
+    /// Only payments created at or after this instant.
+    pub from: Option<chrono::DateTime<chrono::Utc>>,
+    /// Only payments created before this instant.
+    pub to: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct VisibilityOptions {
+    #[serde(rename = "withDeleted")]
+    pub with_deleted: Option<bool>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct SummaryOptions {
+    /// `day`, `week` or `month`.
+    pub period: Option<String>,
+    pub from: Option<chrono::DateTime<chrono::Utc>>,
+    pub to: Option<chrono::DateTime<chrono::Utc>>,
+    /// Summarises a household's ledger instead of the personal one.
+    #[serde(rename = "householdId")]
+    pub household_id: Option<Uuid>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ParamOptions {
+    pub id: String,
+}
+
+#[allow(non_snake_case)]
+#[derive(Serialize, Deserialize, Debug)]
+pub struct CreatePaymentSchema {
+    pub name: String,
+    pub description: String,
+    pub price: f64,
+    pub userId: Uuid,
+    pub categoryId: Uuid,
+}
+
+#[allow(non_snake_case)]
+#[derive(Serialize, Deserialize, Debug)]
+pub struct UpdatePaymentSchema {
+    pub name: String,
+    pub price: f64,
+    pub description: String,
+}
\ No newline at end of file
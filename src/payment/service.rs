@@ -0,0 +1,586 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+};
+
+use chrono::{DateTime, Utc};
+use sqlx::{PgConnection, Pool, Postgres};
+use uuid::Uuid;
+
+use crate::{
+    history::recorder::{self, Action, Entity},
+    household::access,
+    payment::{
+        model::PaymentModel,
+        schema::{CreatePaymentSchema, UpdatePaymentSchema},
+    },
+    user::{config::get_config as get_user_config, verification_handler},
+};
+
+/// Why a payment couldn't be created. Every transport maps these to its own
+/// responses: HTTP statuses, gRPC codes, or ack/reject for AMQP deliveries.
+#[derive(Debug)]
+pub enum PaymentError {
+    Invalid(String),
+    Forbidden(String),
+    NotFound(String),
+    Database(sqlx::Error),
+}
+
+impl fmt::Display for PaymentError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PaymentError::Invalid(message)
+            | PaymentError::Forbidden(message)
+            | PaymentError::NotFound(message) => write!(f, "{}", message),
+            PaymentError::Database(err) => write!(f, "Error: {:?}", err),
+        }
+    }
+}
+
+impl std::error::Error for PaymentError {}
+
+impl From<sqlx::Error> for PaymentError {
+    fn from(err: sqlx::Error) -> Self {
+        PaymentError::Database(err)
+    }
+}
+
+/// Filters for [`list_payments`], shared by the REST list and the AMQP RPC.
+#[derive(Debug)]
+pub struct PaymentQuery {
+    pub limit: i64,
+    pub offset: i64,
+    pub with_deleted: bool,
+    pub household_id: Option<Uuid>,
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+}
+
+/// Payments in `user_id`'s personal ledger and the households they belong to.
+pub async fn list_payments(
+    db: &Pool<Postgres>,
+    user_id: Uuid,
+    query: &PaymentQuery,
+) -> Result<Vec<PaymentModel>, sqlx::Error> {
+    sqlx::query_as!(
+        PaymentModel,
+        "SELECT * FROM payments WHERE ($3 OR deleted_at IS NULL)
+        AND ((household_id IS NULL AND user_id = $4)
+            OR household_id IN (SELECT household_id FROM household_members WHERE user_id = $4))
+        AND ($5::uuid IS NULL OR household_id = $5)
+        AND ($6::timestamptz IS NULL OR created_at >= $6)
+        AND ($7::timestamptz IS NULL OR created_at < $7)
+        ORDER by id LIMIT $1 OFFSET $2",
+        query.limit,
+        query.offset,
+        query.with_deleted,
+        user_id,
+        query.household_id,
+        query.from,
+        query.to
+    )
+    .fetch_all(db)
+    .await
+}
+
+/// Creates a payment on behalf of `input.userId`, who must be able to write
+/// to the category's ledger. The payment joins whichever ledger (personal or
+/// household) its category belongs to.
+pub async fn create_payment(
+    db: &Pool<Postgres>,
+    input: &CreatePaymentSchema,
+) -> Result<PaymentModel, PaymentError> {
+    authorize(db, input).await?;
+
+    let mut tx = db.begin().await?;
+    let payment = insert(&mut tx, input).await?;
+    tx.commit().await?;
+
+    Ok(payment)
+}
+
+/// Like [`create_payment`], but at most once per `message_key`. The key is
+/// recorded in the same transaction as the payment, so a redelivered message
+/// returns `None` instead of inserting a duplicate.
+pub async fn create_payment_once(
+    db: &Pool<Postgres>,
+    input: &CreatePaymentSchema,
+    message_key: &str,
+) -> Result<Option<PaymentModel>, PaymentError> {
+    // Checked up front so a duplicate is acknowledged even if it would no
+    // longer pass validation, e.g. because its category was deleted since.
+    if is_processed(db, message_key).await? {
+        return Ok(None);
+    }
+
+    authorize(db, input).await?;
+
+    let mut tx = db.begin().await?;
+    let payment = insert(&mut tx, input).await?;
+
+    // A concurrent delivery of the same message blocks here until the first
+    // one commits, then finds the key taken and rolls its payment back.
+    if !claim_message(&mut tx, message_key, Some(payment.id)).await? {
+        tx.rollback().await?;
+        return Ok(None);
+    }
+
+    tx.commit().await?;
+    Ok(Some(payment))
+}
+
+/// Records `message_key` as processed inside the caller's transaction.
+/// Returns `false` when another transaction already claimed it, in which case
+/// the caller should roll back.
+pub async fn claim_message(
+    conn: &mut PgConnection,
+    message_key: &str,
+    payment_id: Option<Uuid>,
+) -> Result<bool, sqlx::Error> {
+    let claimed = sqlx::query!(
+        "INSERT INTO processed_messages (message_key, payment_id) VALUES ($1, $2)
+        ON CONFLICT (message_key) DO NOTHING",
+        message_key,
+        payment_id
+    )
+    .execute(conn)
+    .await?
+    .rows_affected()
+        == 1;
+
+    Ok(claimed)
+}
+
+pub async fn is_processed(db: &Pool<Postgres>, message_key: &str) -> Result<bool, sqlx::Error> {
+    sqlx::query_scalar!(
+        r#"SELECT EXISTS(SELECT 1 FROM processed_messages WHERE message_key = $1) AS "exists!""#,
+        message_key
+    )
+    .fetch_one(db)
+    .await
+}
+
+/// The subset of `message_keys` already processed.
+pub async fn processed_keys(db: &Pool<Postgres>, message_keys: &[String]) -> Result<HashSet<String>, sqlx::Error> {
+    let keys = sqlx::query_scalar!(
+        "SELECT message_key FROM processed_messages WHERE message_key = ANY($1)",
+        message_keys
+    )
+    .fetch_all(db)
+    .await?;
+
+    Ok(keys.into_iter().collect())
+}
+
+/// Bulk version of [`create_payment_once`] for high-volume feeds: one
+/// multi-row `INSERT` for all payments, in one transaction. Inputs must
+/// already be authorized and have distinct keys. Returns each input's payment,
+/// or `None` when its key was already claimed.
+pub async fn create_payments_once(
+    db: &Pool<Postgres>,
+    inputs: &[(CreatePaymentSchema, String)],
+) -> Result<Vec<Option<PaymentModel>>, sqlx::Error> {
+    let mut tx = db.begin().await?;
+
+    let keys: Vec<String> = inputs.iter().map(|(_, key)| key.clone()).collect();
+    let claimed: HashSet<String> = sqlx::query_scalar!(
+        "INSERT INTO processed_messages (message_key) SELECT * FROM UNNEST($1::text[])
+        ON CONFLICT (message_key) DO NOTHING RETURNING message_key",
+        &keys
+    )
+    .fetch_all(&mut *tx)
+    .await?
+    .into_iter()
+    .collect();
+
+    // IDs are chosen up front so each payment can be tied back to its key.
+    let new: Vec<(Uuid, &CreatePaymentSchema, &String)> = inputs
+        .iter()
+        .filter(|(_, key)| claimed.contains(key))
+        .map(|(input, key)| (Uuid::new_v4(), input, key))
+        .collect();
+
+    let ids: Vec<Uuid> = new.iter().map(|row| row.0).collect();
+    let names: Vec<String> = new.iter().map(|row| row.1.name.clone()).collect();
+    let descriptions: Vec<String> = new.iter().map(|row| row.1.description.clone()).collect();
+    let prices: Vec<f64> = new.iter().map(|row| row.1.price).collect();
+    let user_ids: Vec<Uuid> = new.iter().map(|row| row.1.userId).collect();
+    let category_ids: Vec<Uuid> = new.iter().map(|row| row.1.categoryId).collect();
+    let new_keys: Vec<String> = new.iter().map(|row| row.2.clone()).collect();
+
+    let payments = sqlx::query_as!(
+        PaymentModel,
+        "INSERT INTO payments (id,name,description,price,user_id,category_id,household_id)
+        SELECT t.id, t.name, t.description, t.price, t.user_id, c.id, c.household_id
+        FROM UNNEST($1::uuid[], $2::text[], $3::text[], $4::float8[], $5::uuid[], $6::uuid[])
+            AS t(id, name, description, price, user_id, category_id)
+        JOIN categories c ON c.id = t.category_id
+        RETURNING *",
+        &ids,
+        &names,
+        &descriptions,
+        &prices,
+        &user_ids,
+        &category_ids
+    )
+    .fetch_all(&mut *tx)
+    .await?;
+
+    // A category removed since authorization would leave a claimed key
+    // without its payment.
+    if payments.len() != new.len() {
+        return Err(sqlx::Error::RowNotFound);
+    }
+
+    sqlx::query!(
+        "UPDATE processed_messages m SET payment_id = t.payment_id
+        FROM UNNEST($1::text[], $2::uuid[]) AS t(message_key, payment_id)
+        WHERE m.message_key = t.message_key",
+        &new_keys,
+        &ids
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    let history: Vec<(Uuid, Uuid, &PaymentModel)> = payments
+        .iter()
+        .map(|payment| (payment.id, payment.user_id, payment))
+        .collect();
+    recorder::record_created(&mut tx, Entity::Payment, &history).await?;
+
+    tx.commit().await?;
+
+    let ids_by_key: HashMap<&str, Uuid> = new.iter().map(|(id, _, key)| (key.as_str(), *id)).collect();
+    let mut payments_by_id: HashMap<Uuid, PaymentModel> =
+        payments.into_iter().map(|payment| (payment.id, payment)).collect();
+
+    Ok(keys
+        .iter()
+        .map(|key| ids_by_key.get(key.as_str()).and_then(|id| payments_by_id.remove(id)))
+        .collect())
+}
+
+async fn authorize(db: &Pool<Postgres>, input: &CreatePaymentSchema) -> Result<(), PaymentError> {
+    if input.name.trim().is_empty() {
+        return Err(PaymentError::Invalid("name is required".to_string()));
+    }
+    if !input.price.is_finite() {
+        return Err(PaymentError::Invalid("price must be a number".to_string()));
+    }
+
+    authorize_category(db, input.userId, input.categoryId).await
+}
+
+/// Checks `user_id` may add payments to `category_id`. Split from the field
+/// checks so bulk ingestion can check each user and category pair once.
+pub async fn authorize_category(db: &Pool<Postgres>, user_id: Uuid, category_id: Uuid) -> Result<(), PaymentError> {
+    match access::entity_role(db, Entity::Category, category_id, user_id).await? {
+        Some(role) if role.can_write() => {}
+        Some(_) => {
+            return Err(PaymentError::Forbidden(
+                "Viewers can't change this household's ledger".to_string(),
+            ))
+        }
+        None => {
+            return Err(PaymentError::NotFound(format!(
+                "Category with ID: {} not found",
+                category_id
+            )))
+        }
+    }
+
+    if get_user_config().require_verified_for_payments
+        && !verification_handler::is_verified(db, user_id).await?
+    {
+        return Err(PaymentError::Forbidden(
+            "Verify your email address before creating payments".to_string(),
+        ));
+    }
+
+    Ok(())
+}
+
+async fn insert(conn: &mut PgConnection, input: &CreatePaymentSchema) -> Result<PaymentModel, sqlx::Error> {
+    let payment = sqlx::query_as!(
+        PaymentModel,
+        "INSERT INTO payments (name,description,price,user_id,category_id,household_id)
+        SELECT $1, $2, $3, $4, c.id, c.household_id FROM categories c WHERE c.id = $5
+        RETURNING *",
+        input.name,
+        input.description,
+        input.price,
+        input.userId,
+        input.categoryId,
+    )
+    .fetch_one(&mut *conn)
+    .await?;
+
+    recorder::record(
+        conn,
+        Entity::Payment,
+        payment.id,
+        Action::Create,
+        Some(input.userId),
+        None,
+        Some(&payment),
+    )
+    .await?;
+
+    Ok(payment)
+}
+
+pub async fn update_payment(
+    db: &Pool<Postgres>,
+    payment_id: Uuid,
+    input: &UpdatePaymentSchema,
+    actor_id: Uuid,
+) -> Result<Option<PaymentModel>, sqlx::Error> {
+    let mut tx = db.begin().await?;
+    let payment = update(&mut tx, payment_id, input, actor_id).await?;
+    tx.commit().await?;
+
+    Ok(payment)
+}
+
+/// Returns `None` when there is no live payment with that ID.
+pub async fn update(
+    conn: &mut PgConnection,
+    payment_id: Uuid,
+    input: &UpdatePaymentSchema,
+    actor_id: Uuid,
+) -> Result<Option<PaymentModel>, sqlx::Error> {
+    let before = sqlx::query_as!(
+        PaymentModel,
+        "SELECT * FROM payments WHERE id = $1 AND deleted_at IS NULL FOR UPDATE",
+        payment_id
+    )
+    .fetch_optional(&mut *conn)
+    .await?;
+
+    let before = match before {
+        Some(payment) => payment,
+        None => return Ok(None),
+    };
+
+    let payment = sqlx::query_as!(
+        PaymentModel,
+        "UPDATE payments SET name = $1, description = $2, price = $3, updated_at = $4 WHERE id = $5 RETURNING *",
+        input.name,
+        input.description,
+        input.price,
+        Utc::now(),
+        payment_id
+    )
+    .fetch_one(&mut *conn)
+    .await?;
+
+    recorder::record(
+        conn,
+        Entity::Payment,
+        payment_id,
+        Action::Update,
+        Some(actor_id),
+        Some(&before),
+        Some(&payment),
+    )
+    .await?;
+
+    Ok(Some(payment))
+}
+
+pub async fn set_payment_deleted(
+    db: &Pool<Postgres>,
+    payment_id: Uuid,
+    deleted: bool,
+    actor_id: Uuid,
+) -> Result<Option<PaymentModel>, sqlx::Error> {
+    let mut tx = db.begin().await?;
+    let payment = set_deleted(&mut tx, payment_id, deleted, actor_id).await?;
+    tx.commit().await?;
+
+    Ok(payment)
+}
+
+/// Moves a payment to (`deleted == true`) or out of the trash, returning
+/// `None` when there was no payment in the opposite state to change.
+pub async fn set_deleted(
+    conn: &mut PgConnection,
+    payment_id: Uuid,
+    deleted: bool,
+    actor_id: Uuid,
+) -> Result<Option<PaymentModel>, sqlx::Error> {
+    let before = sqlx::query_as!(
+        PaymentModel,
+        "SELECT * FROM payments WHERE id = $1 AND (deleted_at IS NULL) = $2 FOR UPDATE",
+        payment_id,
+        deleted
+    )
+    .fetch_optional(&mut *conn)
+    .await?;
+
+    let before = match before {
+        Some(payment) => payment,
+        None => return Ok(None),
+    };
+
+    let payment = sqlx::query_as!(
+        PaymentModel,
+        "UPDATE payments SET deleted_at = CASE WHEN $2 THEN NOW() ELSE NULL END WHERE id = $1 RETURNING *",
+        payment_id,
+        deleted
+    )
+    .fetch_one(&mut *conn)
+    .await?;
+
+    let action = if deleted { Action::Delete } else { Action::Restore };
+    recorder::record(
+        conn,
+        Entity::Payment,
+        payment_id,
+        action,
+        Some(actor_id),
+        Some(&before),
+        Some(&payment),
+    )
+    .await?;
+
+    Ok(Some(payment))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{household::access::MemberRole, test_support};
+
+    fn input(user_id: Uuid, category_id: Uuid) -> CreatePaymentSchema {
+        CreatePaymentSchema {
+            name: "Groceries".to_string(),
+            // Descriptions are unique across all payments.
+            description: format!("Groceries {}", Uuid::new_v4()),
+            price: 42.5,
+            userId: user_id,
+            categoryId: category_id,
+        }
+    }
+
+    #[tokio::test]
+    async fn rejects_blank_name() {
+        let db = test_support::unreachable_pool();
+        let input = CreatePaymentSchema {
+            name: "  ".to_string(),
+            ..input(Uuid::new_v4(), Uuid::new_v4())
+        };
+
+        let result = create_payment(&db, &input).await;
+        assert!(matches!(result, Err(PaymentError::Invalid(_))), "{:?}", result);
+    }
+
+    #[tokio::test]
+    async fn rejects_non_finite_price() {
+        let db = test_support::unreachable_pool();
+
+        for price in [f64::NAN, f64::INFINITY, f64::NEG_INFINITY] {
+            let input = CreatePaymentSchema {
+                price,
+                ..input(Uuid::new_v4(), Uuid::new_v4())
+            };
+
+            let result = create_payment(&db, &input).await;
+            assert!(matches!(result, Err(PaymentError::Invalid(_))), "{}: {:?}", price, result);
+        }
+    }
+
+    #[tokio::test]
+    #[ignore = "needs the docker-compose Postgres"]
+    async fn creates_payment_in_personal_ledger() {
+        let db = test_support::pool().await;
+        let user_id = test_support::user(&db, true).await;
+        let category_id = test_support::category(&db, user_id, None).await;
+
+        let result = create_payment(&db, &input(user_id, category_id)).await;
+        test_support::cleanup(&db, &[user_id]).await;
+
+        let payment = result.expect("payment should be created");
+        assert_eq!(payment.user_id, user_id);
+        assert_eq!(payment.category_id, category_id);
+        assert_eq!(payment.household_id, None);
+    }
+
+    #[tokio::test]
+    #[ignore = "needs the docker-compose Postgres"]
+    async fn reports_unknown_category_as_not_found() {
+        let db = test_support::pool().await;
+        let user_id = test_support::user(&db, true).await;
+
+        let result = create_payment(&db, &input(user_id, Uuid::new_v4())).await;
+        test_support::cleanup(&db, &[user_id]).await;
+
+        assert!(matches!(result, Err(PaymentError::NotFound(_))), "{:?}", result);
+    }
+
+    #[tokio::test]
+    #[ignore = "needs the docker-compose Postgres"]
+    async fn hides_other_users_personal_categories() {
+        let db = test_support::pool().await;
+        let owner_id = test_support::user(&db, true).await;
+        let other_id = test_support::user(&db, true).await;
+        let category_id = test_support::category(&db, owner_id, None).await;
+
+        let result = create_payment(&db, &input(other_id, category_id)).await;
+        test_support::cleanup(&db, &[owner_id, other_id]).await;
+
+        assert!(matches!(result, Err(PaymentError::NotFound(_))), "{:?}", result);
+    }
+
+    #[tokio::test]
+    #[ignore = "needs the docker-compose Postgres"]
+    async fn refuses_household_viewers() {
+        let db = test_support::pool().await;
+        let owner_id = test_support::user(&db, true).await;
+        let viewer_id = test_support::user(&db, true).await;
+        let household_id =
+            test_support::household(&db, &[(owner_id, MemberRole::Owner), (viewer_id, MemberRole::Viewer)]).await;
+        let category_id = test_support::category(&db, owner_id, Some(household_id)).await;
+
+        let result = create_payment(&db, &input(viewer_id, category_id)).await;
+        test_support::cleanup(&db, &[owner_id, viewer_id]).await;
+
+        assert!(matches!(result, Err(PaymentError::Forbidden(_))), "{:?}", result);
+    }
+
+    #[tokio::test]
+    #[ignore = "needs the docker-compose Postgres"]
+    async fn files_household_editors_payments_in_the_household_ledger() {
+        let db = test_support::pool().await;
+        let owner_id = test_support::user(&db, true).await;
+        let editor_id = test_support::user(&db, true).await;
+        let household_id =
+            test_support::household(&db, &[(owner_id, MemberRole::Owner), (editor_id, MemberRole::Editor)]).await;
+        let category_id = test_support::category(&db, owner_id, Some(household_id)).await;
+
+        let result = create_payment(&db, &input(editor_id, category_id)).await;
+        test_support::cleanup(&db, &[owner_id, editor_id]).await;
+
+        let payment = result.expect("payment should be created");
+        assert_eq!(payment.user_id, editor_id);
+        assert_eq!(payment.household_id, Some(household_id));
+    }
+
+    #[tokio::test]
+    #[ignore = "needs the docker-compose Postgres"]
+    async fn requires_a_verified_email_when_configured() {
+        let db = test_support::pool().await;
+        let user_id = test_support::user(&db, false).await;
+        let category_id = test_support::category(&db, user_id, None).await;
+
+        let result = create_payment(&db, &input(user_id, category_id)).await;
+        test_support::cleanup(&db, &[user_id]).await;
+
+        if get_user_config().require_verified_for_payments {
+            assert!(matches!(result, Err(PaymentError::Forbidden(_))), "{:?}", result);
+        } else {
+            assert!(result.is_ok(), "{:?}", result);
+        }
+    }
+}
@@ -0,0 +1,142 @@
+use chrono::{DateTime, NaiveDate, Utc};
+use chrono_tz::Tz;
+use sqlx::{Pool, Postgres};
+use uuid::Uuid;
+
+use crate::preferences::model::PreferencesModel;
+
+pub const WEEK_STARTS: [&str; 3] = ["monday", "sunday", "saturday"];
+pub const DATE_FORMATS: [(&str, &str); 4] = [
+    ("YYYY-MM-DD", "%Y-%m-%d"),
+    ("DD/MM/YYYY", "%d/%m/%Y"),
+    ("MM/DD/YYYY", "%m/%d/%Y"),
+    ("DD.MM.YYYY", "%d.%m.%Y"),
+];
+
+/// Languages that write `1.234,56` rather than `1,234.56`.
+const COMMA_DECIMAL_LANGUAGES: [&str; 9] = ["de", "es", "fr", "it", "nl", "pt", "pl", "ru", "tr"];
+
+pub async fn load(db: &Pool<Postgres>, user_id: Uuid) -> Result<PreferencesModel, sqlx::Error> {
+    let preferences = sqlx::query_as!(
+        PreferencesModel,
+        "SELECT * FROM user_preferences WHERE user_id = $1",
+        user_id
+    )
+    .fetch_optional(db)
+    .await?;
+
+    Ok(preferences.unwrap_or_else(|| PreferencesModel::defaults(user_id)))
+}
+
+pub fn is_valid_timezone(timezone: &str) -> bool {
+    timezone.parse::<Tz>().is_ok()
+}
+
+pub fn is_valid_locale(locale: &str) -> bool {
+    let mut parts = locale.split('-');
+    let language = parts.next().unwrap_or_default();
+    let region = parts.next();
+
+    (2..=3).contains(&language.len())
+        && language.chars().all(|c| c.is_ascii_lowercase())
+        && region.is_none_or(|r| r.len() == 2 && r.chars().all(|c| c.is_ascii_uppercase()))
+        && parts.next().is_none()
+}
+
+pub fn is_valid_currency(currency: &str) -> bool {
+    currency.len() == 3 && currency.chars().all(|c| c.is_ascii_uppercase())
+}
+
+/// Days to shift a timestamp forward so that Postgres' Monday-based
+/// `date_trunc('week', ..)` lands on the preferred first day of the week.
+pub fn week_offset_days(preferences: &PreferencesModel) -> i32 {
+    match preferences.week_start.as_str() {
+        "sunday" => 1,
+        "saturday" => 2,
+        _ => 0,
+    }
+}
+
+pub fn timezone(preferences: &PreferencesModel) -> Tz {
+    preferences.timezone.parse().unwrap_or(Tz::UTC)
+}
+
+pub fn format_date(date: NaiveDate, preferences: &PreferencesModel) -> String {
+    let pattern = DATE_FORMATS
+        .iter()
+        .find(|(name, _)| *name == preferences.date_format)
+        .map(|(_, pattern)| *pattern)
+        .unwrap_or("%Y-%m-%d");
+
+    date.format(pattern).to_string()
+}
+
+pub fn format_datetime(value: DateTime<Utc>, preferences: &PreferencesModel) -> String {
+    let local = value.with_timezone(&timezone(preferences));
+    format!("{} {}", format_date(local.date_naive(), preferences), local.format("%H:%M"))
+}
+
+pub fn format_amount(amount: f64, preferences: &PreferencesModel) -> String {
+    let language = preferences.locale.split('-').next().unwrap_or_default();
+    let (group, decimal) = if COMMA_DECIMAL_LANGUAGES.contains(&language) {
+        ('.', ',')
+    } else {
+        (',', '.')
+    };
+
+    let fixed = format!("{:.2}", amount.abs());
+    let (integer, fraction) = fixed.split_once('.').unwrap_or((&fixed, "00"));
+
+    let mut grouped = String::new();
+    for (i, digit) in integer.chars().enumerate() {
+        if i > 0 && (integer.len() - i) % 3 == 0 {
+            grouped.push(group);
+        }
+        grouped.push(digit);
+    }
+
+    let sign = if amount < 0.0 { "-" } else { "" };
+    format!("{}{}{}{} {}", sign, grouped, decimal, fraction, preferences.currency)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn preferences(week_start: &str) -> PreferencesModel {
+        PreferencesModel {
+            week_start: week_start.to_string(),
+            ..PreferencesModel::defaults(Uuid::nil())
+        }
+    }
+
+    #[test]
+    fn accepts_language_with_optional_region() {
+        for locale in ["en", "pt", "fil", "en-US", "pt-BR", "fil-PH"] {
+            assert!(is_valid_locale(locale), "{}", locale);
+        }
+    }
+
+    #[test]
+    fn rejects_malformed_locales() {
+        for locale in ["", "e", "engl", "EN", "en-us", "en_US", "en-USA", "en-", "-US", "en-US-x", "é"] {
+            assert!(!is_valid_locale(locale), "{}", locale);
+        }
+    }
+
+    #[test]
+    fn offsets_weeks_to_the_preferred_first_day() {
+        let cases = [("monday", 0), ("sunday", 1), ("saturday", 2)];
+        assert_eq!(cases.len(), WEEK_STARTS.len());
+
+        for (week_start, offset) in cases {
+            assert!(WEEK_STARTS.contains(&week_start));
+            assert_eq!(week_offset_days(&preferences(week_start)), offset, "{}", week_start);
+        }
+    }
+
+    #[test]
+    fn treats_unknown_week_starts_as_monday() {
+        assert_eq!(week_offset_days(&preferences("friday")), 0);
+    }
+}
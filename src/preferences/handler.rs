@@ -0,0 +1,102 @@
+use crate::{
+    jwt_auth,
+    preferences::{ format, model::PreferencesModel, schema::UpdatePreferencesSchema },
+    AppState,
+};
+use actix_web::{ get, patch, web, HttpResponse, Responder };
+use serde_json::json;
+
+fn validate(body: &UpdatePreferencesSchema) -> Result<(), String> {
+    if let Some(timezone) = &body.timezone {
+        if !format::is_valid_timezone(timezone) {
+            return Err(format!("Unknown timezone: {}", timezone));
+        }
+    }
+    if let Some(locale) = &body.locale {
+        if !format::is_valid_locale(locale) {
+            return Err(format!("Invalid locale: {}, expected e.g. en-US", locale));
+        }
+    }
+    if let Some(currency) = &body.currency {
+        if !format::is_valid_currency(currency) {
+            return Err(format!("Invalid currency: {}, expected an ISO 4217 code", currency));
+        }
+    }
+    if let Some(week_start) = &body.weekStart {
+        if !format::WEEK_STARTS.contains(&week_start.as_str()) {
+            return Err(format!("weekStart must be one of {:?}", format::WEEK_STARTS));
+        }
+    }
+    if let Some(date_format) = &body.dateFormat {
+        if !format::DATE_FORMATS.iter().any(|(name, _)| name == date_format) {
+            let names: Vec<&str> = format::DATE_FORMATS.iter().map(|(name, _)| *name).collect();
+            return Err(format!("dateFormat must be one of {:?}", names));
+        }
+    }
+    Ok(())
+}
+
+#[get("/me/preferences")]
+pub async fn get_preferences_handler(
+    data: web::Data<AppState>,
+    jwt: jwt_auth::JwtMiddleware
+) -> impl Responder {
+    match format::load(&data.db, jwt.user_id).await {
+        Ok(preferences) => {
+            HttpResponse::Ok().json(json!({"status": "success","data": {"preferences": preferences}}))
+        }
+        Err(err) => {
+            let message = format!("Error: {:?}", err);
+            HttpResponse::InternalServerError().json(json!({"status": "error","message": message}))
+        }
+    }
+}
+
+#[patch("/me/preferences")]
+pub async fn edit_preferences_handler(
+    body: web::Json<UpdatePreferencesSchema>,
+    data: web::Data<AppState>,
+    jwt: jwt_auth::JwtMiddleware
+) -> impl Responder {
+    if let Err(message) = validate(&body) {
+        return HttpResponse::BadRequest().json(json!({"status": "fail","message": message}));
+    }
+
+    let defaults = PreferencesModel::defaults(jwt.user_id);
+    let query_result = sqlx
+        ::query_as!(
+            PreferencesModel,
+            "INSERT INTO user_preferences (user_id, timezone, locale, currency, week_start, date_format)
+            VALUES ($1, COALESCE($2, $7), COALESCE($3, $8), COALESCE($4, $9), COALESCE($5, $10), COALESCE($6, $11))
+            ON CONFLICT (user_id) DO UPDATE SET
+                timezone = COALESCE($2, user_preferences.timezone),
+                locale = COALESCE($3, user_preferences.locale),
+                currency = COALESCE($4, user_preferences.currency),
+                week_start = COALESCE($5, user_preferences.week_start),
+                date_format = COALESCE($6, user_preferences.date_format),
+                updated_at = NOW()
+            RETURNING *",
+            jwt.user_id,
+            body.timezone,
+            body.locale,
+            body.currency,
+            body.weekStart,
+            body.dateFormat,
+            defaults.timezone,
+            defaults.locale,
+            defaults.currency,
+            defaults.week_start,
+            defaults.date_format
+        )
+        .fetch_one(&data.db).await;
+
+    match query_result {
+        Ok(preferences) => {
+            HttpResponse::Ok().json(json!({"status": "success","data": {"preferences": preferences}}))
+        }
+        Err(err) => {
+            let message = format!("Error: {:?}", err);
+            HttpResponse::InternalServerError().json(json!({"status": "error","message": message}))
+        }
+    }
+}
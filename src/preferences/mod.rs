@@ -0,0 +1,4 @@
+pub mod format;
+pub mod handler;
+pub mod model;
+pub mod schema;
@@ -0,0 +1,37 @@
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use uuid::Uuid;
+
+#[derive(Debug, Clone, FromRow, Deserialize, Serialize)]
+#[allow(non_snake_case)]
+pub struct PreferencesModel {
+    #[serde(rename = "userId")]
+    pub user_id: Uuid,
+    pub timezone: String,
+    pub locale: String,
+    pub currency: String,
+    #[serde(rename = "weekStart")]
+    pub week_start: String,
+    #[serde(rename = "dateFormat")]
+    pub date_format: String,
+    #[serde(rename = "createdAt")]
+    pub created_at: Option<chrono::DateTime<chrono::Utc>>,
+    #[serde(rename = "updatedAt")]
+    pub updated_at: Option<chrono::DateTime<chrono::Utc>>
+}
+
+impl PreferencesModel {
+    /// Preferences for users who never saved any.
+    pub fn defaults(user_id: Uuid) -> Self {
+        Self {
+            user_id,
+            timezone: "UTC".to_string(),
+            locale: "en-US".to_string(),
+            currency: "USD".to_string(),
+            week_start: "monday".to_string(),
+            date_format: "YYYY-MM-DD".to_string(),
+            created_at: None,
+            updated_at: None,
+        }
+    }
+}
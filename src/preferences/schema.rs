@@ -0,0 +1,11 @@
+use serde::{Deserialize, Serialize};
+
+#[allow(non_snake_case)]
+#[derive(Serialize, Deserialize, Debug)]
+pub struct UpdatePreferencesSchema {
+    pub timezone: Option<String>,
+    pub locale: Option<String>,
+    pub currency: Option<String>,
+    pub weekStart: Option<String>,
+    pub dateFormat: Option<String>,
+}
@@ -0,0 +1,96 @@
+//! Fixtures for tests that run against the docker-compose services. Those
+//! tests are `#[ignore]`d so `cargo test` passes without them; start the
+//! services with `make dev` and run `make test-integration`.
+
+use sqlx::{postgres::PgPoolOptions, Pool, Postgres};
+use uuid::Uuid;
+
+use crate::household::access::MemberRole;
+
+/// Connects to the migrated database at `DATABASE_URL`.
+pub async fn pool() -> Pool<Postgres> {
+    dotenv::dotenv().ok();
+    let database_url = std::env::var("DATABASE_URL").expect("DATABASE_URL must be set");
+    PgPoolOptions::new()
+        .max_connections(2)
+        .connect(&database_url)
+        .await
+        .expect("Failed to connect to the test database")
+}
+
+/// A pool that never connects, for code paths that must fail before
+/// touching the database.
+pub fn unreachable_pool() -> Pool<Postgres> {
+    PgPoolOptions::new()
+        .connect_lazy("postgres://unused@127.0.0.1:1/unused")
+        .expect("Invalid database URL")
+}
+
+pub async fn user(db: &Pool<Postgres>, verified: bool) -> Uuid {
+    let id = Uuid::new_v4();
+    sqlx::query!(
+        "INSERT INTO users (id, name, email, password, verified_at)
+        VALUES ($1, 'Test user', $2, 'not a hash', CASE WHEN $3 THEN NOW() END)",
+        id,
+        format!("{}@test.invalid", id),
+        verified
+    )
+    .execute(db)
+    .await
+    .expect("Failed to insert test user");
+    id
+}
+
+pub async fn category(db: &Pool<Postgres>, user_id: Uuid, household_id: Option<Uuid>) -> Uuid {
+    sqlx::query_scalar!(
+        "INSERT INTO categories (name, description, user_id, household_id)
+        VALUES ('Test category', $1, $2, $3) RETURNING id",
+        format!("Test category {}", Uuid::new_v4()),
+        user_id,
+        household_id
+    )
+    .fetch_one(db)
+    .await
+    .expect("Failed to insert test category")
+}
+
+pub async fn household(db: &Pool<Postgres>, members: &[(Uuid, MemberRole)]) -> Uuid {
+    let household_id = sqlx::query_scalar!("INSERT INTO households (name) VALUES ('Test household') RETURNING id")
+        .fetch_one(db)
+        .await
+        .expect("Failed to insert test household");
+
+    for (user_id, role) in members {
+        sqlx::query!(
+            "INSERT INTO household_members (household_id, user_id, role) VALUES ($1, $2, $3)",
+            household_id,
+            user_id,
+            role.as_str()
+        )
+        .execute(db)
+        .await
+        .expect("Failed to insert test household member");
+    }
+
+    household_id
+}
+
+/// Removes the given users and everything the fixtures and the code under
+/// test created for them.
+pub async fn cleanup(db: &Pool<Postgres>, user_ids: &[Uuid]) {
+    let statements = [
+        "DELETE FROM processed_messages WHERE payment_id IN (SELECT id FROM payments WHERE user_id = ANY($1))",
+        "DELETE FROM payments WHERE user_id = ANY($1)",
+        "DELETE FROM categories WHERE user_id = ANY($1)",
+        "DELETE FROM households WHERE id IN (SELECT household_id FROM household_members WHERE user_id = ANY($1))",
+        "DELETE FROM users WHERE id = ANY($1)",
+    ];
+
+    for statement in statements {
+        sqlx::query(statement)
+            .bind(user_ids)
+            .execute(db)
+            .await
+            .expect("Failed to clean up test data");
+    }
+}
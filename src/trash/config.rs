@@ -0,0 +1,35 @@
+use lazy_static::lazy_static;
+use std::env;
+
+lazy_static! {
+    static ref TRASH_CONFIG: Config = Config::from_env();
+}
+
+pub fn get_config() -> &'static Config {
+    &TRASH_CONFIG
+}
+
+#[derive(Debug, Clone)]
+pub struct Config {
+    pub purge_enabled: bool,
+    pub retention_days: i64,
+    pub purge_interval_secs: u64,
+}
+
+impl Config {
+    fn from_env() -> Self {
+        Self {
+            purge_enabled: env::var("TRASH_PURGE_ENABLED")
+                .map(|v| v != "false" && v != "0")
+                .unwrap_or(true),
+            retention_days: env::var("TRASH_RETENTION_DAYS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(30),
+            purge_interval_secs: env::var("TRASH_PURGE_INTERVAL_SECS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(3600),
+        }
+    }
+}
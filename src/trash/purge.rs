@@ -0,0 +1,70 @@
+use std::time::Duration;
+
+use sqlx::{Pool, Postgres};
+
+use crate::trash::config::get_config;
+
+pub async fn run(pool: Pool<Postgres>) {
+    let config = get_config();
+
+    if !config.purge_enabled {
+        log::info!("Trash purge job disabled");
+        return;
+    }
+
+    let mut interval = tokio::time::interval(Duration::from_secs(config.purge_interval_secs));
+
+    loop {
+        interval.tick().await;
+
+        match purge_expired(&pool, config.retention_days).await {
+            Ok(purged) => {
+                if purged > 0 {
+                    log::info!("Purged {} trashed rows older than {} days", purged, config.retention_days);
+                }
+            }
+            Err(err) => log::error!("Failed to purge trashed rows: {}", err),
+        }
+    }
+}
+
+/// Hard-deletes rows that have been in the trash longer than `retention_days`.
+///
+/// Payments go first so that categories and users they reference can follow.
+/// Rows still referenced by a live (or not yet expired) row are kept until
+/// their dependents are purged as well.
+pub async fn purge_expired(pool: &Pool<Postgres>, retention_days: i64) -> Result<u64, sqlx::Error> {
+    let cutoff = chrono::Utc::now() - chrono::Duration::days(retention_days);
+    let mut tx = pool.begin().await?;
+
+    let payments = sqlx::query!(
+        "DELETE FROM payments WHERE deleted_at IS NOT NULL AND deleted_at < $1",
+        cutoff
+    )
+    .execute(&mut *tx)
+    .await?
+    .rows_affected();
+
+    let categories = sqlx::query!(
+        "DELETE FROM categories c WHERE c.deleted_at IS NOT NULL AND c.deleted_at < $1
+        AND NOT EXISTS (SELECT 1 FROM payments p WHERE p.category_id = c.id)",
+        cutoff
+    )
+    .execute(&mut *tx)
+    .await?
+    .rows_affected();
+
+    let users = sqlx::query!(
+        "DELETE FROM users u WHERE u.deleted_at IS NOT NULL AND u.deleted_at < $1
+        AND NOT EXISTS (SELECT 1 FROM categories c WHERE c.user_id = u.id)
+        AND NOT EXISTS (SELECT 1 FROM payments p WHERE p.user_id = u.id)",
+        cutoff
+    )
+    .execute(&mut *tx)
+    .await?
+    .rows_affected();
+
+    tx.commit().await?;
+
+    Ok(payments + categories + users)
+}
@@ -0,0 +1,65 @@
+use lazy_static::lazy_static;
+use std::env;
+
+lazy_static! {
+    static ref USER_CONFIG: Config = Config::from_env();
+}
+
+pub fn get_config() -> &'static Config {
+    &USER_CONFIG
+}
+
+#[derive(Debug, Clone)]
+pub struct Config {
+    pub password_reset_ttl_minutes: i64,
+    pub verification_ttl_hours: i64,
+    /// Verification emails a user may request within `verification_resend_window_minutes`.
+    pub verification_resend_limit: i64,
+    pub verification_resend_window_minutes: i64,
+    pub require_verified_for_payments: bool,
+    pub mfa_pending_ttl_minutes: i64,
+    pub totp_issuer: String,
+    pub recovery_code_count: usize,
+    /// Upper bound on how long an impersonation token stays valid.
+    pub impersonation_max_minutes: i64,
+}
+
+impl Config {
+    fn from_env() -> Self {
+        Self {
+            password_reset_ttl_minutes: env::var("PASSWORD_RESET_TTL_MINUTES")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(30),
+            verification_ttl_hours: env::var("EMAIL_VERIFICATION_TTL_HOURS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(24),
+            verification_resend_limit: env::var("EMAIL_VERIFICATION_RESEND_LIMIT")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(3),
+            verification_resend_window_minutes: env::var("EMAIL_VERIFICATION_RESEND_WINDOW_MINUTES")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(60),
+            require_verified_for_payments: env::var("REQUIRE_VERIFIED_EMAIL_FOR_PAYMENTS")
+                .map(|v| v != "false" && v != "0")
+                .unwrap_or(true),
+            mfa_pending_ttl_minutes: env::var("MFA_PENDING_TTL_MINUTES")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(5),
+            totp_issuer: env::var("TOTP_ISSUER").unwrap_or_else(|_| "Rust Finance".to_string()),
+            recovery_code_count: env::var("MFA_RECOVERY_CODE_COUNT")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(10),
+            impersonation_max_minutes: env::var("IMPERSONATION_MAX_MINUTES")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .filter(|&n: &i64| n > 0)
+                .unwrap_or(30),
+        }
+    }
+}
@@ -1,12 +1,25 @@
 use crate::{
+    audit::log::{ self as audit, AuditEntry, AuditEvent, RequestMeta },
+    avatar::handler as avatar_handler,
+    gdpr::handler as gdpr_handler,
     jwt_auth,
-    user::model::UserModel,
+    lockout::guard::{ self as lockout, Scope },
+    preferences::handler as preferences_handler,
+    user::model::{ RoleModel, UserModel },
+    user::password_handler,
+    user::impersonation_handler,
+    user::me_handler,
+    user::mfa_handler,
+    user::role,
+    user::session,
+    user::verification_handler,
     user::schema::{
         LoginUserSchema,
-        TokenClaims,
         CreateUserSchema,
         FilterOptions,
+        UpdateRoleSchema,
         UpdateUserSchema,
+        VisibilityOptions,
     },
     AppState,
 };
@@ -17,6 +30,7 @@ use actix_web::{
     patch,
     post,
     web,
+    HttpRequest,
     HttpResponse,
     Responder,
 };
@@ -24,7 +38,6 @@ use argon2::{
     password_hash::{ rand_core::OsRng, PasswordHash, PasswordHasher, PasswordVerifier, SaltString },
     Argon2,
 };
-use jsonwebtoken::{ encode, EncodingKey, Header };
 use chrono::prelude::*;
 use serde_json::json;
 use sqlx::Row;
@@ -37,12 +50,14 @@ pub async fn user_list_handler(
 ) -> impl Responder {
     let limit = opts.limit.unwrap_or(10);
     let offset = (opts.page.unwrap_or(1) - 1) * limit;
+    let with_deleted = opts.with_deleted.unwrap_or(false);
 
     let query_result = sqlx::query_as!(
             UserModel,
-            "SELECT * FROM users ORDER by id LIMIT $1 OFFSET $2",
+            "SELECT * FROM users WHERE ($3 OR deleted_at IS NULL) ORDER by id LIMIT $1 OFFSET $2",
             limit as i32,
-            offset as i32
+            offset as i32,
+            with_deleted
         )
         .fetch_all(&data.db).await;
 
@@ -99,12 +114,21 @@ async fn create_user_handler(
 
     match query_result {
         Ok(user) => {
+            if let Err(err) = verification_handler::send_verification_email(
+                &data.db,
+                &data.mailer,
+                &user,
+                &user.email
+            ).await {
+                log::error!("Failed to create verification token: {}", err);
+            }
+
             let user_response =
                 serde_json::json!({"status": "success","data": serde_json::json!({
                 "user": user
             })});
 
-            return HttpResponse::Ok().json(user_response);
+            HttpResponse::Ok().json(user_response)
         }
         Err(e) => {
             if e.to_string().contains("duplicate key value violates unique constraint") {
@@ -113,22 +137,70 @@ async fn create_user_handler(
                 );
             }
 
-            return HttpResponse::InternalServerError().json(
+            HttpResponse::InternalServerError().json(
                 serde_json::json!({"status": "error","message": format!("{:?}", e)})
-            );
+            )
         }
     }
 }
 
+#[get("/trash")]
+async fn user_trash_handler(
+    opts: web::Query<FilterOptions>,
+    data: web::Data<AppState>,
+    jwt: jwt_auth::JwtMiddleware
+) -> impl Responder {
+    if !role::is_admin(&data.db, jwt.user_id).await {
+        return HttpResponse::Forbidden().json(
+            json!({"status": "fail","message": "Only administrators can browse trashed users"})
+        );
+    }
+
+    let limit = opts.limit.unwrap_or(10);
+    let offset = (opts.page.unwrap_or(1) - 1) * limit;
+
+    let query_result = sqlx::query_as!(
+            UserModel,
+            "SELECT * FROM users WHERE deleted_at IS NOT NULL ORDER by deleted_at DESC LIMIT $1 OFFSET $2",
+            limit as i32,
+            offset as i32
+        )
+        .fetch_all(&data.db).await;
+
+    if query_result.is_err() {
+        let message = "Something bad happened while fetching trashed user items";
+        return HttpResponse::InternalServerError().json(
+            json!({"status": "error","message": message})
+        );
+    }
+
+    let users = query_result.unwrap();
+
+    let json_response =
+        serde_json::json!({
+        "status": "success",
+        "results": users.len(),
+        "users": users
+    });
+    HttpResponse::Ok().json(json_response)
+}
+
 #[get("/{id}")]
 async fn get_user_handler(
     path: web::Path<uuid::Uuid>,
+    opts: web::Query<VisibilityOptions>,
     data: web::Data<AppState>,
     _: jwt_auth::JwtMiddleware
 ) -> impl Responder {
     let user_id = path.into_inner();
+    let with_deleted = opts.with_deleted.unwrap_or(false);
     let query_result = sqlx
-        ::query_as!(UserModel, "SELECT * FROM users WHERE id = $1", user_id)
+        ::query_as!(
+            UserModel,
+            "SELECT * FROM users WHERE id = $1 AND ($2 OR deleted_at IS NULL)",
+            user_id,
+            with_deleted
+        )
         .fetch_one(&data.db).await;
 
     match query_result {
@@ -138,47 +210,86 @@ async fn get_user_handler(
                 "user": user
             })});
 
-            return HttpResponse::Ok().json(user_response);
+            HttpResponse::Ok().json(user_response)
         }
         Err(_) => {
             let message = format!("User with ID: {} not found", user_id);
-            return HttpResponse::NotFound().json(
+            HttpResponse::NotFound().json(
                 serde_json::json!({"status": "fail","message": message})
-            );
+            )
         }
     }
 }
 
 #[patch("/{id}")]
 async fn edit_user_handler(
+    req: HttpRequest,
     path: web::Path<uuid::Uuid>,
     body: web::Json<UpdateUserSchema>,
     data: web::Data<AppState>,
-    _: jwt_auth::JwtMiddleware
+    jwt: jwt_auth::JwtMiddleware
 ) -> impl Responder {
     let user_id = path.into_inner();
-    let query_result = sqlx
-        ::query_as!(UserModel, "SELECT * FROM users WHERE id = $1", user_id)
-        .fetch_one(&data.db).await;
 
-    if query_result.is_err() {
-        let message = format!("User with ID: {} not found", user_id);
-        return HttpResponse::NotFound().json(
-            serde_json::json!({"status": "fail","message": message})
+    // Impersonation and API keys can't reach another account's credentials.
+    if !jwt.is_session() {
+        return HttpResponse::Forbidden().json(
+            json!({"status": "fail","message": "Users can only be edited from your own session"})
+        );
+    }
+
+    // Users change their own profile through /users/me, which confirms a new
+    // email and asks for the current password; this route is an admin tool.
+    if !role::is_admin(&data.db, jwt.user_id).await {
+        return HttpResponse::Forbidden().json(
+            json!({"status": "fail","message": "Only administrators can edit other users"})
         );
     }
 
+    if role::is_super_admin(&data.db, user_id).await && !role::is_super_admin(&data.db, jwt.user_id).await {
+        return HttpResponse::Forbidden().json(
+            json!({"status": "fail","message": "Only super administrators can edit a super administrator"})
+        );
+    }
+
+    let query_result = sqlx
+        ::query_as!(UserModel, "SELECT * FROM users WHERE id = $1 AND deleted_at IS NULL", user_id)
+        .fetch_one(&data.db).await;
+
+    let existing = match query_result {
+        Ok(user) => user,
+        Err(_) => {
+            let message = format!("User with ID: {} not found", user_id);
+            return HttpResponse::NotFound().json(
+                serde_json::json!({"status": "fail","message": message})
+            );
+        }
+    };
+
+    // Only re-hash when a password was sent and it differs from the current one.
+    let hashed_password = body.password
+        .as_ref()
+        .filter(|password| {
+            PasswordHash::new(&existing.password)
+                .map(|hash| Argon2::default().verify_password(password.as_bytes(), &hash).is_err())
+                .unwrap_or(true)
+        })
+        .map(|password| {
+            let salt = SaltString::generate(&mut OsRng);
+            Argon2::default()
+                .hash_password(password.as_bytes(), &salt)
+                .expect("Error while hashing password")
+                .to_string()
+        });
+    let password_changed = hashed_password.is_some();
+
     let now = Utc::now();
-    let salt = SaltString::generate(&mut OsRng);
-    let hashed_password = Argon2::default()
-        .hash_password(body.password.as_bytes(), &salt)
-        .expect("Error while hashing password")
-        .to_string();
     let query_result = sqlx
         ::query_as!(
             UserModel,
-            "UPDATE users SET name = $1, email = $2, password = $3 updated_at = 4$ WHERE id = $5 RETURNING *",
-            body.name.to_string(),
+            "UPDATE users SET name = COALESCE($1, name), email = COALESCE($2, email),
+            password = COALESCE($3, password), updated_at = $4 WHERE id = $5 RETURNING *",
+            body.name,
             body.email,
             hashed_password,
             now,
@@ -188,18 +299,173 @@ async fn edit_user_handler(
 
     match query_result {
         Ok(user) => {
+            if password_changed {
+                audit::record(&data.db, AuditEntry {
+                    event: AuditEvent::PasswordChange,
+                    success: true,
+                    user_id: Some(user.id),
+                    email: Some(&user.email),
+                    meta: &RequestMeta::from_request(&req),
+                    metadata: json!({"changedBy": jwt.user_id}),
+                }).await;
+            }
+
             let user_response =
                 serde_json::json!({"status": "success","data": serde_json::json!({
                 "user": user
             })});
 
-            return HttpResponse::Ok().json(user_response);
+            HttpResponse::Ok().json(user_response)
         }
         Err(err) => {
             let message = format!("Error: {:?}", err);
-            return HttpResponse::InternalServerError().json(
+            HttpResponse::InternalServerError().json(
                 serde_json::json!({"status": "error","message": message})
-            );
+            )
+        }
+    }
+}
+
+#[patch("/{id}/role")]
+async fn edit_user_role_handler(
+    req: HttpRequest,
+    path: web::Path<uuid::Uuid>,
+    body: web::Json<UpdateRoleSchema>,
+    data: web::Data<AppState>,
+    jwt: jwt_auth::JwtMiddleware
+) -> impl Responder {
+    let user_id = path.into_inner();
+    let meta = RequestMeta::from_request(&req);
+
+    if !role::is_admin(&data.db, jwt.user_id).await {
+        audit::record(&data.db, AuditEntry {
+            event: AuditEvent::RoleChange,
+            success: false,
+            user_id: Some(user_id),
+            email: None,
+            meta: &meta,
+            metadata: json!({"changedBy": jwt.user_id, "roleId": body.roleId, "reason": "forbidden"}),
+        }).await;
+
+        return HttpResponse::Forbidden().json(
+            json!({"status": "fail","message": "Only administrators can change roles"})
+        );
+    }
+
+    let previous = match role::fetch_role(&data.db, user_id).await {
+        Ok(Some(previous)) => previous,
+        _ => {
+            let message = format!("User with ID: {} not found", user_id);
+            return HttpResponse::NotFound().json(json!({"status": "fail","message": message}));
+        }
+    };
+
+    let requested = match role::find_role(&data.db, body.roleId).await {
+        Ok(Some(requested)) => requested,
+        Ok(None) => {
+            let message = format!("Role with ID: {} not found", body.roleId);
+            return HttpResponse::BadRequest().json(json!({"status": "fail","message": message}));
+        }
+        Err(err) => {
+            let message = format!("Error: {:?}", err);
+            return HttpResponse::InternalServerError().json(json!({"status": "error","message": message}));
+        }
+    };
+
+    // Administrator roles are handed out and taken away by super admins only,
+    // so a plain admin can't promote anyone (themselves included) past them.
+    let privileged = |role: &RoleModel| role.admin || role.super_admin;
+    if (privileged(&previous) || privileged(&requested)) && !role::is_super_admin(&data.db, jwt.user_id).await {
+        audit::record(&data.db, AuditEntry {
+            event: AuditEvent::RoleChange,
+            success: false,
+            user_id: Some(user_id),
+            email: None,
+            meta: &meta,
+            metadata: json!({"changedBy": jwt.user_id, "roleId": body.roleId, "reason": "forbidden"}),
+        }).await;
+
+        return HttpResponse::Forbidden().json(
+            json!({"status": "fail","message": "Only super administrators can grant or revoke administrator roles"})
+        );
+    }
+
+    let query_result = sqlx
+        ::query_as!(
+            UserModel,
+            "UPDATE users SET role_id = $1, updated_at = NOW() WHERE id = $2 AND deleted_at IS NULL RETURNING *",
+            body.roleId,
+            user_id
+        )
+        .fetch_one(&data.db).await;
+
+    match query_result {
+        Ok(user) => {
+            audit::record(&data.db, AuditEntry {
+                event: AuditEvent::RoleChange,
+                success: true,
+                user_id: Some(user.id),
+                email: Some(&user.email),
+                meta: &meta,
+                metadata: json!({"changedBy": jwt.user_id, "from": previous.id, "to": user.role_id}),
+            }).await;
+
+            let user_response =
+                serde_json::json!({"status": "success","data": serde_json::json!({
+                "user": user
+            })});
+
+            HttpResponse::Ok().json(user_response)
+        }
+        Err(err) => {
+            let message = format!("Error: {:?}", err);
+            HttpResponse::InternalServerError().json(json!({"status": "error","message": message}))
+        }
+    }
+}
+
+#[post("/{id}/unlock")]
+async fn unlock_user_handler(
+    req: HttpRequest,
+    path: web::Path<uuid::Uuid>,
+    data: web::Data<AppState>,
+    jwt: jwt_auth::JwtMiddleware
+) -> impl Responder {
+    if !role::is_admin(&data.db, jwt.user_id).await {
+        return HttpResponse::Forbidden().json(
+            json!({"status": "fail","message": "Only administrators can unlock accounts"})
+        );
+    }
+
+    let user_id = path.into_inner();
+    let query_result = sqlx
+        ::query_as!(UserModel, "SELECT * FROM users WHERE id = $1 AND deleted_at IS NULL", user_id)
+        .fetch_one(&data.db).await;
+
+    let user = match query_result {
+        Ok(user) => user,
+        Err(_) => {
+            let message = format!("User with ID: {} not found", user_id);
+            return HttpResponse::NotFound().json(json!({"status": "fail","message": message}));
+        }
+    };
+
+    match lockout::clear(&data.db, Scope::Account, &lockout::account_key(&user.email)).await {
+        Ok(cleared) => {
+            audit::record(&data.db, AuditEntry {
+                event: AuditEvent::AccountUnlock,
+                success: true,
+                user_id: Some(user.id),
+                email: Some(&user.email),
+                meta: &RequestMeta::from_request(&req),
+                metadata: json!({"unlockedBy": jwt.user_id, "wasLocked": cleared > 0}),
+            }).await;
+
+            HttpResponse::Ok().json(json!({"status": "success"}))
+        }
+        Err(err) => {
+            let message = format!("Error: {:?}", err);
+            HttpResponse::InternalServerError().json(json!({"status": "error","message": message}))
         }
     }
 }
@@ -208,43 +474,143 @@ async fn edit_user_handler(
 async fn delete_user_handler(
     path: web::Path<uuid::Uuid>,
     data: web::Data<AppState>,
-    _: jwt_auth::JwtMiddleware
+    jwt: jwt_auth::JwtMiddleware
 ) -> impl Responder {
+    if !role::is_admin(&data.db, jwt.user_id).await {
+        return HttpResponse::Forbidden().json(
+            json!({"status": "fail","message": "Only administrators can delete users"})
+        );
+    }
+
     let user_id = path.into_inner();
-    let rows_affected = sqlx
-        ::query!("DELETE FROM users WHERE id = $1", user_id)
-        .execute(&data.db).await
-        .unwrap()
-        .rows_affected();
+    if role::is_super_admin(&data.db, user_id).await && !role::is_super_admin(&data.db, jwt.user_id).await {
+        return HttpResponse::Forbidden().json(
+            json!({"status": "fail","message": "Only super administrators can delete a super administrator"})
+        );
+    }
+
+    let query_result = sqlx
+        ::query!("UPDATE users SET deleted_at = NOW() WHERE id = $1 AND deleted_at IS NULL", user_id)
+        .execute(&data.db).await;
+
+    match query_result {
+        Ok(result) if result.rows_affected() == 0 => {
+            let message = format!("User with ID: {} not found", user_id);
+            HttpResponse::NotFound().json(json!({"status": "fail","message": message}))
+        }
+        Ok(_) => HttpResponse::NoContent().finish(),
+        Err(err) => {
+            let message = format!("Error: {:?}", err);
+            HttpResponse::InternalServerError().json(json!({"status": "error","message": message}))
+        }
+    }
+}
 
-    if rows_affected == 0 {
-        let message = format!("User with ID: {} not found", user_id);
-        return HttpResponse::NotFound().json(json!({"status": "fail","message": message}));
+#[post("/{id}/restore")]
+async fn restore_user_handler(
+    path: web::Path<uuid::Uuid>,
+    data: web::Data<AppState>,
+    jwt: jwt_auth::JwtMiddleware
+) -> impl Responder {
+    if !role::is_admin(&data.db, jwt.user_id).await {
+        return HttpResponse::Forbidden().json(
+            json!({"status": "fail","message": "Only administrators can restore users"})
+        );
     }
 
-    HttpResponse::NoContent().finish()
+    let user_id = path.into_inner();
+    let query_result = sqlx
+        ::query_as!(
+            UserModel,
+            "UPDATE users SET deleted_at = NULL WHERE id = $1 AND deleted_at IS NOT NULL RETURNING *",
+            user_id
+        )
+        .fetch_optional(&data.db).await;
+
+    match query_result {
+        Ok(Some(user)) => {
+            let user_response =
+                serde_json::json!({"status": "success","data": serde_json::json!({
+                "user": user
+            })});
+
+            HttpResponse::Ok().json(user_response)
+        }
+        Ok(None) => {
+            let message = format!("Trashed user with ID: {} not found", user_id);
+            HttpResponse::NotFound().json(json!({"status": "fail","message": message}))
+        }
+        Err(err) => {
+            let message = format!("Error: {:?}", err);
+            HttpResponse::InternalServerError().json(json!({"status": "error","message": message}))
+        }
+    }
 }
 
 #[post("/login")]
 async fn login_user_handler(
+    req: HttpRequest,
     body: web::Json<LoginUserSchema>,
     data: web::Data<AppState>
 ) -> impl Responder {
+    let meta = RequestMeta::from_request(&req);
+    let account_key = lockout::account_key(&body.email);
+
+    // Without a peer address every such request would share one IP counter.
+    let mut keys = vec![(Scope::Account, account_key.as_str())];
+    if let Some(ip) = &meta.ip {
+        keys.push((Scope::Ip, ip.as_str()));
+    }
+
+    let locked = lockout::locked_until(&data.db, &keys).await;
+
+    if let Ok(Some(until)) = locked {
+        audit::record(&data.db, AuditEntry {
+            event: AuditEvent::Login,
+            success: false,
+            user_id: None,
+            email: Some(&body.email),
+            meta: &meta,
+            metadata: json!({"reason": "locked", "lockedUntil": until}),
+        }).await;
+
+        let retry_after = (until - Utc::now()).num_seconds().max(1);
+        return HttpResponse::TooManyRequests()
+            .insert_header(("Retry-After", retry_after.to_string()))
+            .json(
+                json!({"status": "fail", "message": "Too many failed login attempts, try again later"})
+            );
+    }
+
     let query_result = sqlx
-        ::query_as!(UserModel, "SELECT * FROM users WHERE email = $1", body.email)
+        ::query_as!(
+            UserModel,
+            "SELECT * FROM users WHERE email = $1 AND deleted_at IS NULL",
+            body.email
+        )
         .fetch_one(&data.db).await;
 
-    let user = match query_result {
-        Ok(ref user) => {
-            let parsed_hash = PasswordHash::new(&user.password).unwrap();
-            Argon2::default()
-                .verify_password(body.password.as_bytes(), &parsed_hash)
-                .map_or(false, |_| true)
-        }
-        Err(_) => false,
-    };
+    let user = lockout::verify_password(
+        &body.password,
+        query_result.as_ref().ok().map(|user| user.password.as_str())
+    );
 
     if !user {
+        for &(scope, key) in &keys {
+            if let Err(err) = lockout::register_failure(&data.db, scope, key).await {
+                log::error!("Failed to register login failure: {}", err);
+            }
+        }
+
+        audit::record(&data.db, AuditEntry {
+            event: AuditEvent::Login,
+            success: false,
+            user_id: query_result.as_ref().ok().map(|user| user.id),
+            email: Some(&body.email),
+            meta: &meta,
+            metadata: json!({"reason": "invalid_credentials"}),
+        }).await;
+
         return HttpResponse::BadRequest().json(
             json!({"status": "fail", "message": "Invalid email or password"})
         );
@@ -252,65 +618,103 @@ async fn login_user_handler(
 
     let user = query_result.unwrap();
 
-    let now = chrono::Utc::now();
-    let iat = now.timestamp() as usize;
-    let exp = (now + chrono::Duration::minutes(60)).timestamp() as usize;
-    let claims: TokenClaims = TokenClaims {
-        sub: user.id.to_string(),
-        exp,
-        iat,
-    };
+    if let Err(err) = lockout::clear(&data.db, Scope::Account, &account_key).await {
+        log::error!("Failed to clear login failures: {}", err);
+    }
 
-    let secret_key = "my_ultra_secure_secret";
-    let secret_key_bytes = secret_key.as_bytes();
+    if user.totp_enabled_at.is_some() {
+        audit::record(&data.db, AuditEntry {
+            event: AuditEvent::Login,
+            success: true,
+            user_id: Some(user.id),
+            email: Some(&user.email),
+            meta: &meta,
+            metadata: json!({"step": "password", "mfaRequired": true}),
+        }).await;
+
+        return match session::issue_mfa_pending_token(user.id) {
+            Ok(token) => HttpResponse::Ok().json(json!({"status": "mfa_required", "mfaToken": token})),
+            Err(err) => session::token_error(err),
+        };
+    }
 
-    let token = encode(
-        &Header::default(),
-        &claims,
-        &EncodingKey::from_secret(secret_key_bytes)
-    ).unwrap();
+    audit::record(&data.db, AuditEntry {
+        event: AuditEvent::Login,
+        success: true,
+        user_id: Some(user.id),
+        email: Some(&user.email),
+        meta: &meta,
+        metadata: json!({}),
+    }).await;
 
-    let cookie = Cookie::build("token", token.to_owned())
-        .path("/")
-        .max_age(ActixWebDuration::new(60 * 60, 0))
-        .http_only(true)
-        .finish();
+    session::login_response(&data.db, user).await
+}
 
-    let role = sqlx::query!(
-        "SELECT * FROM roles WHERE id = $1",
-        user.role_id
-    )
-    .fetch_one(&data.db)
-    .await
-    .map(|row| {
-        json!({
-            "id": row.id,
-            "name": row.name,
-            "description": row.description,
-            "admin": row.admin,
-            "super_admin": row.super_admin
-        })
-    })
-    .unwrap_or_else(|_| {
-        json!({
-            "id": user.role_id,
-            "name": "Unknown Role",
-            "description": "Role details not found",
-            "admin": false,
-            "super_admin": false
-        })
-    });
+#[post("/refresh")]
+async fn refresh_token_handler(
+    req: HttpRequest,
+    data: web::Data<AppState>,
+    jwt: jwt_auth::JwtMiddleware
+) -> impl Responder {
+    let meta = RequestMeta::from_request(&req);
 
-    let mut user_json = serde_json::to_value(user).unwrap();
-    user_json["role"] = role;
+    // Impersonation is time-limited and API keys aren't sessions; refreshing
+    // either would turn it into a regular session.
+    if !jwt.is_session() {
+        return HttpResponse::Forbidden().json(
+            json!({"status": "fail", "message": "Only session tokens can be refreshed"})
+        );
+    }
+
+    let exists = sqlx
+        ::query_scalar!(
+            r#"SELECT EXISTS(SELECT 1 FROM users WHERE id = $1 AND deleted_at IS NULL) AS "exists!""#,
+            jwt.user_id
+        )
+        .fetch_one(&data.db).await
+        .unwrap_or(false);
+
+    audit::record(&data.db, AuditEntry {
+        event: AuditEvent::TokenRefresh,
+        success: exists,
+        user_id: Some(jwt.user_id),
+        email: None,
+        meta: &meta,
+        metadata: json!({}),
+    }).await;
+
+    if !exists {
+        return HttpResponse::Unauthorized().json(
+            json!({"status": "fail", "message": "The user belonging to this token no longer exists"})
+        );
+    }
+
+    let token = match session::issue_token(jwt.user_id) {
+        Ok(token) => token,
+        Err(err) => return session::token_error(err),
+    };
+    let cookie = session::token_cookie(&token);
 
     HttpResponse::Ok()
         .cookie(cookie)
-        .json(json!({"status": "success", "token": token, "user": user_json}))
+        .json(json!({"status": "success", "token": token}))
 }
 
 #[get("/logout")]
-async fn logout_handler(_: jwt_auth::JwtMiddleware) -> impl Responder {
+async fn logout_handler(
+    req: HttpRequest,
+    data: web::Data<AppState>,
+    jwt: jwt_auth::JwtMiddleware
+) -> impl Responder {
+    audit::record(&data.db, AuditEntry {
+        event: AuditEvent::Logout,
+        success: true,
+        user_id: Some(jwt.user_id),
+        email: None,
+        meta: &RequestMeta::from_request(&req),
+        metadata: json!({}),
+    }).await;
+
     let cookie = Cookie::build("token", "")
         .path("/")
         .max_age(ActixWebDuration::new(-1, 0))
@@ -327,11 +731,37 @@ pub fn config(conf: &mut web::ServiceConfig) {
         ::scope("/users")
         .service(user_list_handler)
         .service(create_user_handler)
+        .service(user_trash_handler)
+        .service(logout_handler)
+        .service(me_handler::get_me_handler)
+        .service(me_handler::edit_me_handler)
+        .service(me_handler::change_password_handler)
+        .service(preferences_handler::get_preferences_handler)
+        .service(preferences_handler::edit_preferences_handler)
+        .service(avatar_handler::upload_avatar_handler)
+        .service(avatar_handler::delete_avatar_handler)
+        .service(avatar_handler::get_avatar_handler)
+        .service(gdpr_handler::export_me_handler)
+        .service(gdpr_handler::erase_me_handler)
+        .service(gdpr_handler::erase_user_handler)
         .service(get_user_handler)
         .service(edit_user_handler)
+        .service(edit_user_role_handler)
+        .service(impersonation_handler::impersonate_user_handler)
+        .service(unlock_user_handler)
         .service(delete_user_handler)
+        .service(restore_user_handler)
         .service(login_user_handler)
-        .service(logout_handler);
+        .service(refresh_token_handler)
+        .service(password_handler::forgot_password_handler)
+        .service(password_handler::reset_password_handler)
+        .service(verification_handler::verify_email_handler)
+        .service(verification_handler::resend_verification_handler)
+        .service(mfa_handler::login_mfa_handler)
+        .service(mfa_handler::totp_setup_handler)
+        .service(mfa_handler::totp_enable_handler)
+        .service(mfa_handler::totp_disable_handler)
+        .service(mfa_handler::recovery_codes_handler);
 
     conf.service(scope);
 }
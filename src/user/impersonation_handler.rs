@@ -0,0 +1,108 @@
+use crate::{
+    audit::log::{ self as audit, AuditEntry, AuditEvent, RequestMeta },
+    jwt_auth,
+    user::config::get_config,
+    user::model::UserModel,
+    user::role,
+    user::schema::ImpersonateSchema,
+    user::session,
+    AppState,
+};
+use actix_web::{ post, web, HttpRequest, HttpResponse, Responder };
+use serde_json::json;
+
+/// Issues a token that lets a super admin see the app as another user.
+/// Sessions are read-only unless `allowWrites` is set, can't manage the
+/// user's credentials, and are capped at `IMPERSONATION_MAX_MINUTES`.
+#[post("/{id}/impersonate")]
+pub async fn impersonate_user_handler(
+    req: HttpRequest,
+    path: web::Path<uuid::Uuid>,
+    body: web::Json<ImpersonateSchema>,
+    data: web::Data<AppState>,
+    jwt: jwt_auth::JwtMiddleware
+) -> impl Responder {
+    let target_id = path.into_inner();
+    let meta = RequestMeta::from_request(&req);
+    let read_only = !body.allowWrites.unwrap_or(false);
+
+    let refusal = if !jwt.is_session() {
+        Some("Impersonation must be started from your own session")
+    } else if !role::is_super_admin(&data.db, jwt.user_id).await {
+        Some("Only super administrators can impersonate users")
+    } else if target_id == jwt.user_id {
+        Some("You can't impersonate yourself")
+    } else if body.reason.trim().is_empty() {
+        Some("A reason is required")
+    } else {
+        None
+    };
+
+    if let Some(message) = refusal {
+        audit::record(&data.db, AuditEntry {
+            event: AuditEvent::Impersonation,
+            success: false,
+            user_id: Some(target_id),
+            email: None,
+            meta: &meta,
+            metadata: json!({"adminId": jwt.user_id, "reason": body.reason, "error": message}),
+        }).await;
+
+        return HttpResponse::Forbidden().json(json!({"status": "fail","message": message}));
+    }
+
+    let query_result = sqlx
+        ::query_as!(UserModel, "SELECT * FROM users WHERE id = $1 AND deleted_at IS NULL", target_id)
+        .fetch_optional(&data.db).await;
+
+    let user = match query_result {
+        Ok(Some(user)) => user,
+        Ok(None) => {
+            let message = format!("User with ID: {} not found", target_id);
+            return HttpResponse::NotFound().json(json!({"status": "fail","message": message}));
+        }
+        Err(err) => {
+            let message = format!("Error: {:?}", err);
+            return HttpResponse::InternalServerError().json(json!({"status": "error","message": message}));
+        }
+    };
+
+    if role::is_super_admin(&data.db, user.id).await {
+        return HttpResponse::Forbidden().json(
+            json!({"status": "fail","message": "Super administrators can't be impersonated"})
+        );
+    }
+
+    let max_minutes = get_config().impersonation_max_minutes;
+    let minutes = body.minutes.unwrap_or(max_minutes).clamp(1, max_minutes);
+    let expires_at = chrono::Utc::now() + chrono::Duration::minutes(minutes);
+    let token = match session::issue_impersonation_token(jwt.user_id, user.id, read_only, expires_at) {
+        Ok(token) => token,
+        Err(err) => return session::token_error(err),
+    };
+
+    audit::record(&data.db, AuditEntry {
+        event: AuditEvent::Impersonation,
+        success: true,
+        user_id: Some(user.id),
+        email: Some(&user.email),
+        meta: &meta,
+        metadata: json!({
+            "adminId": jwt.user_id,
+            "reason": body.reason,
+            "readOnly": read_only,
+            "expiresAt": expires_at
+        }),
+    }).await;
+
+    HttpResponse::Ok().json(json!({
+        "status": "success",
+        "token": token,
+        "data": {
+            "user": user,
+            "impersonatorId": jwt.user_id,
+            "readOnly": read_only,
+            "expiresAt": expires_at
+        }
+    }))
+}
@@ -0,0 +1,209 @@
+use crate::{
+    audit::log::{ self as audit, AuditEntry, AuditEvent, RequestMeta },
+    jwt_auth,
+    user::model::UserModel,
+    user::schema::{ ChangePasswordSchema, UpdateMeSchema },
+    user::verification_handler,
+    AppState,
+};
+use actix_web::{ get, patch, post, web, HttpRequest, HttpResponse, Responder };
+use argon2::{
+    password_hash::{ rand_core::OsRng, PasswordHash, PasswordHasher, PasswordVerifier, SaltString },
+    Argon2,
+};
+use serde_json::json;
+
+#[get("/me")]
+pub async fn get_me_handler(
+    data: web::Data<AppState>,
+    jwt: jwt_auth::JwtMiddleware
+) -> impl Responder {
+    let query_result = sqlx
+        ::query_as!(UserModel, "SELECT * FROM users WHERE id = $1 AND deleted_at IS NULL", jwt.user_id)
+        .fetch_one(&data.db).await;
+
+    match query_result {
+        Ok(user) => {
+            let user_response =
+                serde_json::json!({"status": "success","data": serde_json::json!({
+                "user": user
+            })});
+
+            HttpResponse::Ok().json(user_response)
+        }
+        Err(_) => {
+            let message = format!("User with ID: {} not found", jwt.user_id);
+            HttpResponse::NotFound().json(json!({"status": "fail","message": message}))
+        }
+    }
+}
+
+/// Updates only the fields that were sent. A new email is not applied
+/// directly: it is confirmed through a verification link first.
+#[patch("/me")]
+pub async fn edit_me_handler(
+    body: web::Json<UpdateMeSchema>,
+    data: web::Data<AppState>,
+    jwt: jwt_auth::JwtMiddleware
+) -> impl Responder {
+    let query_result = sqlx
+        ::query_as!(UserModel, "SELECT * FROM users WHERE id = $1 AND deleted_at IS NULL", jwt.user_id)
+        .fetch_optional(&data.db).await;
+
+    let existing = match query_result {
+        Ok(Some(user)) => user,
+        Ok(None) => {
+            let message = format!("User with ID: {} not found", jwt.user_id);
+            return HttpResponse::NotFound().json(json!({"status": "fail","message": message}));
+        }
+        Err(err) => {
+            let message = format!("Error: {:?}", err);
+            return HttpResponse::InternalServerError().json(json!({"status": "error","message": message}));
+        }
+    };
+
+    let pending_email = body.email
+        .as_ref()
+        .map(|email| email.trim().to_string())
+        .filter(|email| !email.eq_ignore_ascii_case(&existing.email));
+
+    // Refuse a taken email before anything is written, so a 409 never
+    // follows a half-applied update.
+    if let Some(email) = &pending_email {
+        let taken = sqlx
+            ::query_scalar!(
+                r#"SELECT EXISTS(SELECT 1 FROM users WHERE email = $1) AS "exists!""#,
+                email
+            )
+            .fetch_one(&data.db).await
+            .unwrap_or(true);
+
+        if taken {
+            return HttpResponse::Conflict().json(
+                json!({"status": "fail","message": "User with that email already exists"})
+            );
+        }
+    }
+
+    let query_result = sqlx
+        ::query_as!(
+            UserModel,
+            "UPDATE users SET name = COALESCE($1, name), updated_at = NOW()
+            WHERE id = $2 AND deleted_at IS NULL RETURNING *",
+            body.name,
+            jwt.user_id
+        )
+        .fetch_optional(&data.db).await;
+
+    let user = match query_result {
+        Ok(Some(user)) => user,
+        Ok(None) => {
+            let message = format!("User with ID: {} not found", jwt.user_id);
+            return HttpResponse::NotFound().json(json!({"status": "fail","message": message}));
+        }
+        Err(err) => {
+            let message = format!("Error: {:?}", err);
+            return HttpResponse::InternalServerError().json(json!({"status": "error","message": message}));
+        }
+    };
+
+    if let Some(email) = &pending_email {
+        if let Err(err) = verification_handler::send_verification_email(
+            &data.db,
+            &data.mailer,
+            &user,
+            email
+        ).await {
+            let message = format!("Error: {:?}", err);
+            return HttpResponse::InternalServerError().json(json!({"status": "error","message": message}));
+        }
+    }
+
+    let user_response =
+        serde_json::json!({"status": "success","data": serde_json::json!({
+        "user": user,
+        "pendingEmail": pending_email
+    })});
+
+    HttpResponse::Ok().json(user_response)
+}
+
+#[post("/me/password")]
+pub async fn change_password_handler(
+    req: HttpRequest,
+    body: web::Json<ChangePasswordSchema>,
+    data: web::Data<AppState>,
+    jwt: jwt_auth::JwtMiddleware
+) -> impl Responder {
+    let meta = RequestMeta::from_request(&req);
+
+    if !jwt.is_session() {
+        return HttpResponse::Forbidden().json(
+            json!({"status": "fail","message": "Passwords can only be changed with your own session token"})
+        );
+    }
+
+    let query_result = sqlx
+        ::query_as!(UserModel, "SELECT * FROM users WHERE id = $1 AND deleted_at IS NULL", jwt.user_id)
+        .fetch_one(&data.db).await;
+
+    let user = match query_result {
+        Ok(user) => user,
+        Err(_) => {
+            let message = format!("User with ID: {} not found", jwt.user_id);
+            return HttpResponse::NotFound().json(json!({"status": "fail","message": message}));
+        }
+    };
+
+    let current_matches = PasswordHash::new(&user.password)
+        .map(|hash| Argon2::default().verify_password(body.currentPassword.as_bytes(), &hash).is_ok())
+        .unwrap_or(false);
+
+    if !current_matches {
+        audit::record(&data.db, AuditEntry {
+            event: AuditEvent::PasswordChange,
+            success: false,
+            user_id: Some(user.id),
+            email: Some(&user.email),
+            meta: &meta,
+            metadata: json!({"reason": "invalid_current_password"}),
+        }).await;
+
+        return HttpResponse::BadRequest().json(
+            json!({"status": "fail","message": "Current password is incorrect"})
+        );
+    }
+
+    let salt = SaltString::generate(&mut OsRng);
+    let hashed_password = Argon2::default()
+        .hash_password(body.newPassword.as_bytes(), &salt)
+        .expect("Error while hashing password")
+        .to_string();
+
+    let query_result = sqlx
+        ::query!(
+            "UPDATE users SET password = $1, updated_at = NOW() WHERE id = $2",
+            hashed_password,
+            user.id
+        )
+        .execute(&data.db).await;
+
+    match query_result {
+        Ok(_) => {
+            audit::record(&data.db, AuditEntry {
+                event: AuditEvent::PasswordChange,
+                success: true,
+                user_id: Some(user.id),
+                email: Some(&user.email),
+                meta: &meta,
+                metadata: json!({"via": "self_service"}),
+            }).await;
+
+            HttpResponse::Ok().json(json!({"status": "success"}))
+        }
+        Err(err) => {
+            let message = format!("Error: {:?}", err);
+            HttpResponse::InternalServerError().json(json!({"status": "error","message": message}))
+        }
+    }
+}
@@ -0,0 +1,445 @@
+use crate::{
+    audit::log::{ self as audit, AuditEntry, AuditEvent, RequestMeta },
+    jwt::keys,
+    jwt_auth,
+    lockout::guard::{ self as lockout, Scope },
+    token,
+    user::config::get_config,
+    user::model::UserModel,
+    user::schema::{ LoginMfaSchema, TokenClaims, TotpCodeSchema },
+    user::session,
+    AppState,
+};
+use actix_web::{ post, web, HttpRequest, HttpResponse, Responder };
+use rand_core::{ OsRng, RngCore };
+use serde_json::json;
+use sqlx::{ Pool, Postgres };
+use totp_rs::{ Algorithm, Secret, TOTP };
+
+const RECOVERY_CODE_ALPHABET: &[u8] = b"abcdefghjkmnpqrstuvwxyz23456789";
+
+fn totp_for(secret: &str, account: &str) -> Option<TOTP> {
+    let bytes = Secret::Encoded(secret.to_string()).to_bytes().ok()?;
+    TOTP::new(
+        Algorithm::SHA1,
+        6,
+        1,
+        30,
+        bytes,
+        Some(get_config().totp_issuer.clone()),
+        account.to_string()
+    ).ok()
+}
+
+fn check_totp(user: &UserModel, code: &str) -> bool {
+    user.totp_secret
+        .as_deref()
+        .and_then(|secret| totp_for(secret, &user.email))
+        .map(|totp| totp.check_current(code.trim()).unwrap_or(false))
+        .unwrap_or(false)
+}
+
+fn normalize_recovery_code(code: &str) -> String {
+    code.trim().to_lowercase().replace('-', "")
+}
+
+fn generate_recovery_code() -> String {
+    let mut bytes = [0u8; 10];
+    OsRng.fill_bytes(&mut bytes);
+    let code: String = bytes
+        .iter()
+        .map(|b| RECOVERY_CODE_ALPHABET[(*b as usize) % RECOVERY_CODE_ALPHABET.len()] as char)
+        .collect();
+    format!("{}-{}", &code[..5], &code[5..])
+}
+
+/// Accepts either a current TOTP code or an unused recovery code, burning
+/// the recovery code on success.
+async fn check_second_factor(
+    db: &Pool<Postgres>,
+    user: &UserModel,
+    code: Option<&str>,
+    recovery_code: Option<&str>
+) -> Result<bool, sqlx::Error> {
+    if let Some(code) = code {
+        return Ok(check_totp(user, code));
+    }
+
+    if let Some(recovery_code) = recovery_code {
+        let used = sqlx
+            ::query_scalar!(
+                "UPDATE mfa_recovery_codes SET used_at = NOW()
+                WHERE user_id = $1 AND code_hash = $2 AND used_at IS NULL
+                RETURNING id",
+                user.id,
+                token::hash(&normalize_recovery_code(recovery_code))
+            )
+            .fetch_optional(db).await?;
+        return Ok(used.is_some());
+    }
+
+    Ok(false)
+}
+
+/// Replaces the user's recovery codes, returning the plaintext codes to show once.
+async fn replace_recovery_codes(
+    conn: &mut sqlx::PgConnection,
+    user_id: uuid::Uuid
+) -> Result<Vec<String>, sqlx::Error> {
+    sqlx::query!("DELETE FROM mfa_recovery_codes WHERE user_id = $1", user_id)
+        .execute(&mut *conn).await?;
+
+    let codes: Vec<String> = (0..get_config().recovery_code_count)
+        .map(|_| generate_recovery_code())
+        .collect();
+
+    for code in &codes {
+        sqlx::query!(
+            "INSERT INTO mfa_recovery_codes (user_id, code_hash) VALUES ($1, $2)",
+            user_id,
+            token::hash(&normalize_recovery_code(code))
+        )
+        .execute(&mut *conn).await?;
+    }
+
+    Ok(codes)
+}
+
+/// Second factors are only managed from the user's own session.
+fn reject_delegated(jwt: &jwt_auth::JwtMiddleware) -> Option<HttpResponse> {
+    (!jwt.is_session()).then(|| {
+        HttpResponse::Forbidden().json(
+            json!({"status": "fail","message": "Two-factor authentication can only be managed with your own session token"})
+        )
+    })
+}
+
+async fn fetch_user(db: &Pool<Postgres>, user_id: uuid::Uuid) -> Option<UserModel> {
+    sqlx
+        ::query_as!(UserModel, "SELECT * FROM users WHERE id = $1 AND deleted_at IS NULL", user_id)
+        .fetch_optional(db).await
+        .unwrap_or(None)
+}
+
+#[post("/login/mfa")]
+pub async fn login_mfa_handler(
+    req: HttpRequest,
+    body: web::Json<LoginMfaSchema>,
+    data: web::Data<AppState>
+) -> impl Responder {
+    let meta = RequestMeta::from_request(&req);
+    let invalid = || HttpResponse::Unauthorized().json(
+        json!({"status": "fail", "message": "Invalid or expired MFA token"})
+    );
+
+    let claims = match keys::verify::<TokenClaims>(&body.mfaToken) {
+        Ok(c) if c.claims.mfa_pending => c.claims,
+        _ => return invalid(),
+    };
+
+    let user = match uuid::Uuid::parse_str(&claims.sub).ok() {
+        Some(user_id) => fetch_user(&data.db, user_id).await,
+        None => None,
+    };
+    let user = match user {
+        Some(user) if user.totp_enabled_at.is_some() => user,
+        _ => return invalid(),
+    };
+
+    let account_key = lockout::account_key(&user.email);
+    if let Ok(Some(_)) = lockout::locked_until(&data.db, &[(Scope::Account, &account_key)]).await {
+        return HttpResponse::TooManyRequests().json(
+            json!({"status": "fail", "message": "Too many failed login attempts, try again later"})
+        );
+    }
+
+    let verified = check_second_factor(
+        &data.db,
+        &user,
+        body.code.as_deref(),
+        body.recoveryCode.as_deref()
+    ).await.unwrap_or(false);
+
+    audit::record(&data.db, AuditEntry {
+        event: AuditEvent::MfaChallenge,
+        success: verified,
+        user_id: Some(user.id),
+        email: Some(&user.email),
+        meta: &meta,
+        metadata: json!({"method": if body.code.is_some() { "totp" } else { "recovery_code" }}),
+    }).await;
+
+    if !verified {
+        if let Err(err) = lockout::register_failure(&data.db, Scope::Account, &account_key).await {
+            log::error!("Failed to register login failure: {}", err);
+        }
+
+        return HttpResponse::BadRequest().json(
+            json!({"status": "fail", "message": "Invalid authentication code"})
+        );
+    }
+
+    if let Err(err) = lockout::clear(&data.db, Scope::Account, &account_key).await {
+        log::error!("Failed to clear login failures: {}", err);
+    }
+
+    session::login_response(&data.db, user).await
+}
+
+#[post("/mfa/totp/setup")]
+pub async fn totp_setup_handler(
+    data: web::Data<AppState>,
+    jwt: jwt_auth::JwtMiddleware
+) -> impl Responder {
+    if let Some(response) = reject_delegated(&jwt) {
+        return response;
+    }
+
+    let user = match fetch_user(&data.db, jwt.user_id).await {
+        Some(user) => user,
+        None => {
+            let message = format!("User with ID: {} not found", jwt.user_id);
+            return HttpResponse::NotFound().json(json!({"status": "fail","message": message}));
+        }
+    };
+
+    if user.totp_enabled_at.is_some() {
+        return HttpResponse::Conflict().json(
+            json!({"status": "fail","message": "Two-factor authentication is already enabled"})
+        );
+    }
+
+    let secret = Secret::generate_secret().to_encoded().to_string();
+    let totp = match totp_for(&secret, &user.email) {
+        Some(totp) => totp,
+        None => {
+            return HttpResponse::InternalServerError().json(
+                json!({"status": "error","message": "Unable to generate TOTP secret"})
+            );
+        }
+    };
+
+    let query_result = sqlx
+        ::query!("UPDATE users SET totp_secret = $1 WHERE id = $2", secret, user.id)
+        .execute(&data.db).await;
+
+    match query_result {
+        Ok(_) => {
+            HttpResponse::Ok().json(json!({
+                "status": "success",
+                "data": {"secret": secret, "otpauthUrl": totp.get_url()}
+            }))
+        }
+        Err(err) => {
+            let message = format!("Error: {:?}", err);
+            HttpResponse::InternalServerError().json(json!({"status": "error","message": message}))
+        }
+    }
+}
+
+#[post("/mfa/totp/enable")]
+pub async fn totp_enable_handler(
+    req: HttpRequest,
+    body: web::Json<TotpCodeSchema>,
+    data: web::Data<AppState>,
+    jwt: jwt_auth::JwtMiddleware
+) -> impl Responder {
+    if let Some(response) = reject_delegated(&jwt) {
+        return response;
+    }
+
+    let user = match fetch_user(&data.db, jwt.user_id).await {
+        Some(user) if user.totp_secret.is_some() && user.totp_enabled_at.is_none() => user,
+        _ => {
+            return HttpResponse::BadRequest().json(
+                json!({"status": "fail","message": "Start two-factor setup before enabling it"})
+            );
+        }
+    };
+
+    if !body.code.as_deref().map(|code| check_totp(&user, code)).unwrap_or(false) {
+        return HttpResponse::BadRequest().json(
+            json!({"status": "fail", "message": "Invalid authentication code"})
+        );
+    }
+
+    let enable_result = async {
+        let mut tx = data.db.begin().await?;
+        sqlx::query!("UPDATE users SET totp_enabled_at = NOW() WHERE id = $1", user.id)
+            .execute(&mut *tx).await?;
+        let codes = replace_recovery_codes(&mut tx, user.id).await?;
+        tx.commit().await?;
+        Ok::<_, sqlx::Error>(codes)
+    }.await;
+
+    match enable_result {
+        Ok(codes) => {
+            audit::record(&data.db, AuditEntry {
+                event: AuditEvent::MfaEnabled,
+                success: true,
+                user_id: Some(user.id),
+                email: Some(&user.email),
+                meta: &RequestMeta::from_request(&req),
+                metadata: json!({"method": "totp"}),
+            }).await;
+
+            HttpResponse::Ok().json(json!({"status": "success", "data": {"recoveryCodes": codes}}))
+        }
+        Err(err) => {
+            let message = format!("Error: {:?}", err);
+            HttpResponse::InternalServerError().json(json!({"status": "error","message": message}))
+        }
+    }
+}
+
+#[post("/mfa/totp/disable")]
+pub async fn totp_disable_handler(
+    req: HttpRequest,
+    body: web::Json<TotpCodeSchema>,
+    data: web::Data<AppState>,
+    jwt: jwt_auth::JwtMiddleware
+) -> impl Responder {
+    if let Some(response) = reject_delegated(&jwt) {
+        return response;
+    }
+
+    let user = match fetch_user(&data.db, jwt.user_id).await {
+        Some(user) if user.totp_enabled_at.is_some() => user,
+        _ => {
+            return HttpResponse::BadRequest().json(
+                json!({"status": "fail","message": "Two-factor authentication is not enabled"})
+            );
+        }
+    };
+
+    let verified = check_second_factor(
+        &data.db,
+        &user,
+        body.code.as_deref(),
+        body.recoveryCode.as_deref()
+    ).await.unwrap_or(false);
+
+    if !verified {
+        return HttpResponse::BadRequest().json(
+            json!({"status": "fail", "message": "Invalid authentication code"})
+        );
+    }
+
+    let disable_result = async {
+        let mut tx = data.db.begin().await?;
+        sqlx::query!(
+            "UPDATE users SET totp_secret = NULL, totp_enabled_at = NULL WHERE id = $1",
+            user.id
+        )
+        .execute(&mut *tx).await?;
+        sqlx::query!("DELETE FROM mfa_recovery_codes WHERE user_id = $1", user.id)
+            .execute(&mut *tx).await?;
+        tx.commit().await
+    }.await;
+
+    match disable_result {
+        Ok(_) => {
+            audit::record(&data.db, AuditEntry {
+                event: AuditEvent::MfaDisabled,
+                success: true,
+                user_id: Some(user.id),
+                email: Some(&user.email),
+                meta: &RequestMeta::from_request(&req),
+                metadata: json!({"method": "totp"}),
+            }).await;
+
+            HttpResponse::Ok().json(json!({"status": "success"}))
+        }
+        Err(err) => {
+            let message = format!("Error: {:?}", err);
+            HttpResponse::InternalServerError().json(json!({"status": "error","message": message}))
+        }
+    }
+}
+
+#[post("/mfa/recovery-codes")]
+pub async fn recovery_codes_handler(
+    req: HttpRequest,
+    body: web::Json<TotpCodeSchema>,
+    data: web::Data<AppState>,
+    jwt: jwt_auth::JwtMiddleware
+) -> impl Responder {
+    if let Some(response) = reject_delegated(&jwt) {
+        return response;
+    }
+
+    let user = match fetch_user(&data.db, jwt.user_id).await {
+        Some(user) if user.totp_enabled_at.is_some() => user,
+        _ => {
+            return HttpResponse::BadRequest().json(
+                json!({"status": "fail","message": "Two-factor authentication is not enabled"})
+            );
+        }
+    };
+
+    if !body.code.as_deref().map(|code| check_totp(&user, code)).unwrap_or(false) {
+        return HttpResponse::BadRequest().json(
+            json!({"status": "fail", "message": "Invalid authentication code"})
+        );
+    }
+
+    let regenerate_result = async {
+        let mut tx = data.db.begin().await?;
+        let codes = replace_recovery_codes(&mut tx, user.id).await?;
+        tx.commit().await?;
+        Ok::<_, sqlx::Error>(codes)
+    }.await;
+
+    match regenerate_result {
+        Ok(codes) => {
+            audit::record(&data.db, AuditEntry {
+                event: AuditEvent::RecoveryCodesRegenerated,
+                success: true,
+                user_id: Some(user.id),
+                email: Some(&user.email),
+                meta: &RequestMeta::from_request(&req),
+                metadata: json!({}),
+            }).await;
+
+            HttpResponse::Ok().json(json!({"status": "success", "data": {"recoveryCodes": codes}}))
+        }
+        Err(err) => {
+            let message = format!("Error: {:?}", err);
+            HttpResponse::InternalServerError().json(json!({"status": "error","message": message}))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalizes_recovery_codes_as_typed() {
+        let cases = [
+            ("abcde-fghjk", "abcdefghjk"),
+            ("ABCDE-FGHJK", "abcdefghjk"),
+            ("  abcde-fghjk\n", "abcdefghjk"),
+            ("abcdefghjk", "abcdefghjk"),
+            ("ab-cde-fg-hjk", "abcdefghjk"),
+        ];
+
+        for (typed, expected) in cases {
+            assert_eq!(normalize_recovery_code(typed), expected, "{:?}", typed);
+        }
+    }
+
+    #[test]
+    fn generated_codes_survive_normalization() {
+        for _ in 0..100 {
+            let code = generate_recovery_code();
+
+            assert_eq!(code.len(), 11, "{}", code);
+            assert_eq!(&code[5..6], "-", "{}", code);
+            let normalized = normalize_recovery_code(&code);
+            assert_eq!(normalized.len(), 10, "{}", code);
+            assert!(normalized.bytes().all(|b| RECOVERY_CODE_ALPHABET.contains(&b)), "{}", code);
+            assert_eq!(normalize_recovery_code(&code.to_uppercase()), normalized);
+        }
+    }
+}
@@ -1,3 +1,11 @@
+pub mod config;
 pub mod handler;
+pub mod impersonation_handler;
+pub mod me_handler;
+pub mod mfa_handler;
 pub mod model;
-pub mod schema;
\ No newline at end of file
+pub mod password_handler;
+pub mod role;
+pub mod schema;
+pub mod session;
+pub mod verification_handler;
\ No newline at end of file
@@ -8,9 +8,35 @@ pub struct UserModel {
     pub id: Uuid,
     pub name: String,
     pub email: String,
+    #[serde(skip_serializing)]
     pub password: String,
+    #[serde(rename = "roleId")]
+    pub role_id: Uuid,
+    #[serde(rename = "verifiedAt")]
+    pub verified_at: Option<chrono::DateTime<chrono::Utc>>,
+    #[serde(skip_serializing, default)]
+    pub totp_secret: Option<String>,
+    #[serde(rename = "totpEnabledAt")]
+    pub totp_enabled_at: Option<chrono::DateTime<chrono::Utc>>,
+    #[serde(rename = "avatarVersion")]
+    pub avatar_version: Option<String>,
+    #[serde(rename = "createdAt")]
+    pub created_at: Option<chrono::DateTime<chrono::Utc>>,
+    #[serde(rename = "updatedAt")]
+    pub updated_at: Option<chrono::DateTime<chrono::Utc>>,
+    #[serde(rename = "deletedAt")]
+    pub deleted_at: Option<chrono::DateTime<chrono::Utc>>
+}
+
+#[derive(Debug, FromRow, Deserialize, Serialize)]
+pub struct RoleModel {
+    pub id: Uuid,
+    pub name: String,
+    pub description: String,
+    pub admin: bool,
+    pub super_admin: bool,
     #[serde(rename = "createdAt")]
     pub created_at: Option<chrono::DateTime<chrono::Utc>>,
     #[serde(rename = "updatedAt")]
     pub updated_at: Option<chrono::DateTime<chrono::Utc>>
-}
\ No newline at end of file
+}
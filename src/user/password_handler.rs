@@ -0,0 +1,187 @@
+use crate::{
+    audit::log::{ self as audit, AuditEntry, AuditEvent, RequestMeta },
+    lockout::guard::{ self as lockout, Scope },
+    mailer::{ self, Message },
+    token,
+    user::config::get_config,
+    user::model::UserModel,
+    user::schema::{ ForgotPasswordSchema, ResetPasswordSchema },
+    AppState,
+};
+use actix_web::{ post, web, HttpRequest, HttpResponse, Responder };
+use argon2::{ password_hash::{ rand_core::OsRng, PasswordHasher, SaltString }, Argon2 };
+use serde_json::json;
+
+#[post("/forgot-password")]
+pub async fn forgot_password_handler(
+    req: HttpRequest,
+    body: web::Json<ForgotPasswordSchema>,
+    data: web::Data<AppState>
+) -> impl Responder {
+    let meta = RequestMeta::from_request(&req);
+
+    // Keyed by the normalized email whether or not it is registered, so
+    // hitting the limit says nothing about the account.
+    let mut keys = vec![(Scope::ResetAccount, lockout::account_key(&body.email))];
+    if let Some(ip) = &meta.ip {
+        keys.push((Scope::ResetIp, ip.clone()));
+    }
+    for (scope, key) in keys {
+        match lockout::allow_request(&data.db, scope, &key).await {
+            Ok(true) => {}
+            Ok(false) => {
+                return HttpResponse::TooManyRequests().json(
+                    json!({"status": "fail","message": "Too many password reset requests, try again later"})
+                );
+            }
+            Err(err) => {
+                let message = format!("Error: {:?}", err);
+                return HttpResponse::InternalServerError().json(json!({"status": "error","message": message}));
+            }
+        }
+    }
+
+    // The lookup and the email happen after responding, so neither the
+    // response nor its timing reveals whether the account exists.
+    let email = body.into_inner().email;
+    tokio::spawn(async move {
+        if let Err(err) = send_reset_email(&data, &email, &meta).await {
+            log::error!("Failed to process password reset request: {:?}", err);
+        }
+    });
+
+    HttpResponse::Ok().json(
+        json!({"status": "success", "message": "If that email is registered, a reset link has been sent"})
+    )
+}
+
+async fn send_reset_email(data: &AppState, email: &str, meta: &RequestMeta) -> Result<(), sqlx::Error> {
+    let user = sqlx
+        ::query_as!(
+            UserModel,
+            "SELECT * FROM users WHERE email = $1 AND deleted_at IS NULL",
+            email
+        )
+        .fetch_optional(&data.db).await?;
+
+    let user = match user {
+        Some(user) => user,
+        None => return Ok(()),
+    };
+
+    let reset_token = token::generate();
+    let expires_at = chrono::Utc::now() + chrono::Duration::minutes(get_config().password_reset_ttl_minutes);
+
+    let mut tx = data.db.begin().await?;
+
+    // Only the most recently requested link stays valid.
+    sqlx::query!(
+        "UPDATE password_reset_tokens SET used_at = NOW() WHERE user_id = $1 AND used_at IS NULL",
+        user.id
+    )
+    .execute(&mut *tx).await?;
+
+    sqlx::query!(
+        "INSERT INTO password_reset_tokens (user_id, token_hash, expires_at) VALUES ($1, $2, $3)",
+        user.id,
+        token::hash(&reset_token),
+        expires_at
+    )
+    .execute(&mut *tx).await?;
+
+    tx.commit().await?;
+
+    let message = Message {
+        to: user.email.clone(),
+        subject: "Reset your password".to_string(),
+        body: format!(
+            "Hi {},\n\nUse the link below to choose a new password. It expires in {} minutes.\n\n{}\n\nIf you didn't ask for this, you can ignore this email.",
+            user.name,
+            get_config().password_reset_ttl_minutes,
+            mailer::link("/reset-password", &reset_token)
+        ),
+    };
+    if let Err(err) = data.mailer.send(&message).await {
+        log::error!("Failed to send password reset email: {}", err);
+    }
+
+    audit::record(&data.db, AuditEntry {
+        event: AuditEvent::PasswordResetRequest,
+        success: true,
+        user_id: Some(user.id),
+        email: Some(&user.email),
+        meta,
+        metadata: json!({}),
+    }).await;
+
+    Ok(())
+}
+
+#[post("/reset-password")]
+pub async fn reset_password_handler(
+    req: HttpRequest,
+    body: web::Json<ResetPasswordSchema>,
+    data: web::Data<AppState>
+) -> impl Responder {
+    let salt = SaltString::generate(&mut OsRng);
+    let hashed_password = Argon2::default()
+        .hash_password(body.password.as_bytes(), &salt)
+        .expect("Error while hashing password")
+        .to_string();
+
+    let reset_result = async {
+        let mut tx = data.db.begin().await?;
+
+        let user_id = sqlx
+            ::query_scalar!(
+                "UPDATE password_reset_tokens SET used_at = NOW()
+                WHERE token_hash = $1 AND used_at IS NULL AND expires_at > NOW()
+                RETURNING user_id",
+                token::hash(&body.token)
+            )
+            .fetch_optional(&mut *tx).await?;
+
+        let user = match user_id {
+            Some(user_id) => sqlx
+                ::query_as!(
+                    UserModel,
+                    "UPDATE users SET password = $1, updated_at = NOW() WHERE id = $2 AND deleted_at IS NULL RETURNING *",
+                    hashed_password,
+                    user_id
+                )
+                .fetch_optional(&mut *tx).await?,
+            None => None,
+        };
+
+        tx.commit().await?;
+        Ok::<_, sqlx::Error>(user)
+    }.await;
+
+    match reset_result {
+        Ok(Some(user)) => {
+            if let Err(err) = lockout::clear(&data.db, Scope::Account, &lockout::account_key(&user.email)).await {
+                log::error!("Failed to clear login failures: {}", err);
+            }
+
+            audit::record(&data.db, AuditEntry {
+                event: AuditEvent::PasswordChange,
+                success: true,
+                user_id: Some(user.id),
+                email: Some(&user.email),
+                meta: &RequestMeta::from_request(&req),
+                metadata: json!({"via": "reset_token"}),
+            }).await;
+
+            HttpResponse::Ok().json(json!({"status": "success", "message": "Password has been reset"}))
+        }
+        Ok(None) => {
+            HttpResponse::BadRequest().json(
+                json!({"status": "fail", "message": "Reset token is invalid or has expired"})
+            )
+        }
+        Err(err) => {
+            let message = format!("Error: {:?}", err);
+            HttpResponse::InternalServerError().json(json!({"status": "error","message": message}))
+        }
+    }
+}
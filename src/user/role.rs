@@ -0,0 +1,28 @@
+use sqlx::{Pool, Postgres};
+use uuid::Uuid;
+
+use crate::user::model::RoleModel;
+
+pub async fn fetch_role(db: &Pool<Postgres>, user_id: Uuid) -> Result<Option<RoleModel>, sqlx::Error> {
+    sqlx::query_as!(
+        RoleModel,
+        "SELECT r.* FROM roles r JOIN users u ON u.role_id = r.id WHERE u.id = $1 AND u.deleted_at IS NULL",
+        user_id
+    )
+    .fetch_optional(db)
+    .await
+}
+
+pub async fn find_role(db: &Pool<Postgres>, role_id: Uuid) -> Result<Option<RoleModel>, sqlx::Error> {
+    sqlx::query_as!(RoleModel, "SELECT * FROM roles WHERE id = $1", role_id)
+        .fetch_optional(db)
+        .await
+}
+
+pub async fn is_admin(db: &Pool<Postgres>, user_id: Uuid) -> bool {
+    matches!(fetch_role(db, user_id).await, Ok(Some(role)) if role.admin || role.super_admin)
+}
+
+pub async fn is_super_admin(db: &Pool<Postgres>, user_id: Uuid) -> bool {
+    matches!(fetch_role(db, user_id).await, Ok(Some(role)) if role.super_admin)
+}
@@ -5,6 +5,14 @@ use uuid::Uuid;
 pub struct FilterOptions {
     pub page: Option<usize>,
     pub limit: Option<usize>,
+    #[serde(rename = "withDeleted")]
+    pub with_deleted: Option<bool>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct VisibilityOptions {
+    #[serde(rename = "withDeleted")]
+    pub with_deleted: Option<bool>,
 }
 
 #[derive(Deserialize, Debug)]
@@ -23,9 +31,29 @@ pub struct CreateUserSchema {
 #[allow(non_snake_case)]
 #[derive(Serialize, Deserialize, Debug)]
 pub struct UpdateUserSchema {
-    pub name: String,
-    pub email: String,
-    pub password: String,
+    pub name: Option<String>,
+    pub email: Option<String>,
+    pub password: Option<String>,
+}
+
+#[allow(non_snake_case)]
+#[derive(Serialize, Deserialize, Debug)]
+pub struct UpdateMeSchema {
+    pub name: Option<String>,
+    pub email: Option<String>,
+}
+
+#[allow(non_snake_case)]
+#[derive(Deserialize, Debug)]
+pub struct ChangePasswordSchema {
+    pub currentPassword: String,
+    pub newPassword: String,
+}
+
+#[allow(non_snake_case)]
+#[derive(Serialize, Deserialize, Debug)]
+pub struct UpdateRoleSchema {
+    pub roleId: Uuid,
 }
 
 #[derive(Debug, Deserialize)]
@@ -39,4 +67,50 @@ pub struct TokenClaims {
     pub sub: String,
     pub iat: usize,
     pub exp: usize,
-}
\ No newline at end of file
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub mfa_pending: bool,
+    /// On impersonation tokens, the ID of the admin acting as `sub`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub act: Option<String>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub read_only: bool,
+}
+#[allow(non_snake_case)]
+#[derive(Debug, Deserialize)]
+pub struct ImpersonateSchema {
+    /// Lets the session make changes; impersonation is read-only otherwise.
+    pub allowWrites: Option<bool>,
+    pub minutes: Option<i64>,
+    pub reason: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ForgotPasswordSchema {
+    pub email: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ResetPasswordSchema {
+    pub token: String,
+    pub password: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct VerifyEmailSchema {
+    pub token: String,
+}
+
+#[allow(non_snake_case)]
+#[derive(Debug, Deserialize)]
+pub struct LoginMfaSchema {
+    pub mfaToken: String,
+    pub code: Option<String>,
+    pub recoveryCode: Option<String>,
+}
+
+#[allow(non_snake_case)]
+#[derive(Debug, Deserialize)]
+pub struct TotpCodeSchema {
+    pub code: Option<String>,
+    pub recoveryCode: Option<String>,
+}
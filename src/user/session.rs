@@ -0,0 +1,122 @@
+use actix_web::{ cookie::{ time::Duration as ActixWebDuration, Cookie }, HttpResponse };
+use jsonwebtoken::errors::Error as JwtError;
+use serde_json::json;
+use sqlx::{ Pool, Postgres };
+
+use crate::{
+    jwt::keys,
+    user::config::get_config,
+    user::model::UserModel,
+    user::schema::TokenClaims,
+};
+
+pub fn issue_token(user_id: uuid::Uuid) -> Result<String, JwtError> {
+    let now = chrono::Utc::now();
+    let iat = now.timestamp() as usize;
+    let exp = (now + chrono::Duration::minutes(keys::max_age_minutes())).timestamp() as usize;
+    let claims: TokenClaims = TokenClaims {
+        sub: user_id.to_string(),
+        exp,
+        iat,
+        mfa_pending: false,
+        act: None,
+        read_only: false,
+    };
+
+    keys::sign(&claims)
+}
+
+/// Token letting `admin_id` act as `user_id` until `expires_at`. It is never
+/// set as a cookie so the admin's own session is left untouched.
+pub fn issue_impersonation_token(
+    admin_id: uuid::Uuid,
+    user_id: uuid::Uuid,
+    read_only: bool,
+    expires_at: chrono::DateTime<chrono::Utc>
+) -> Result<String, JwtError> {
+    let claims: TokenClaims = TokenClaims {
+        sub: user_id.to_string(),
+        exp: expires_at.timestamp() as usize,
+        iat: chrono::Utc::now().timestamp() as usize,
+        mfa_pending: false,
+        act: Some(admin_id.to_string()),
+        read_only,
+    };
+
+    keys::sign(&claims)
+}
+
+/// Short-lived token proving the password step succeeded; it is only
+/// accepted by the second-factor endpoint, never by `JwtMiddleware`.
+pub fn issue_mfa_pending_token(user_id: uuid::Uuid) -> Result<String, JwtError> {
+    let now = chrono::Utc::now();
+    let iat = now.timestamp() as usize;
+    let exp = (now + chrono::Duration::minutes(get_config().mfa_pending_ttl_minutes)).timestamp() as usize;
+    let claims: TokenClaims = TokenClaims {
+        sub: user_id.to_string(),
+        exp,
+        iat,
+        mfa_pending: true,
+        act: None,
+        read_only: false,
+    };
+
+    keys::sign(&claims)
+}
+
+/// Response for a token that could not be signed, e.g. because the key
+/// configuration is broken.
+pub fn token_error(err: JwtError) -> HttpResponse {
+    let message = format!("Error: {:?}", err);
+    HttpResponse::InternalServerError().json(json!({"status": "error","message": message}))
+}
+
+pub fn token_cookie(token: &str) -> Cookie<'static> {
+    Cookie::build("token", token.to_owned())
+        .path("/")
+        .max_age(ActixWebDuration::new(keys::max_age_minutes() * 60, 0))
+        .http_only(true)
+        .finish()
+}
+
+/// Issues a session for `user` and builds the login response body with the
+/// user's role attached.
+pub async fn login_response(db: &Pool<Postgres>, user: UserModel) -> HttpResponse {
+    let token = match issue_token(user.id) {
+        Ok(token) => token,
+        Err(err) => return token_error(err),
+    };
+    let cookie = token_cookie(&token);
+
+    let role = sqlx::query!(
+        "SELECT * FROM roles WHERE id = $1",
+        user.role_id
+    )
+    .fetch_one(db)
+    .await
+    .map(|row| {
+        json!({
+            "id": row.id,
+            "name": row.name,
+            "description": row.description,
+            "admin": row.admin,
+            "super_admin": row.super_admin
+        })
+    })
+    .unwrap_or_else(|_| {
+        json!({
+            "id": user.role_id,
+            "name": "Unknown Role",
+            "description": "Role details not found",
+            "admin": false,
+            "super_admin": false
+        })
+    });
+
+    let mut user_json = serde_json::to_value(user).unwrap();
+    user_json["role"] = role;
+
+    HttpResponse::Ok()
+        .cookie(cookie)
+        .json(json!({"status": "success", "token": token, "user": user_json}))
+}
@@ -0,0 +1,197 @@
+use std::sync::Arc;
+
+use crate::{
+    jwt_auth,
+    mailer::{ self, Mailer, Message },
+    token,
+    user::config::get_config,
+    user::model::UserModel,
+    user::schema::VerifyEmailSchema,
+    AppState,
+};
+use actix_web::{ post, web, HttpResponse, Responder };
+use serde_json::json;
+use sqlx::{ Pool, Postgres };
+
+/// Issues a fresh verification token for `email` (which may differ from the
+/// user's current address when they are changing it) and mails the link.
+/// Earlier unused tokens for the user stop working.
+pub async fn send_verification_email(
+    db: &Pool<Postgres>,
+    mailer: &Arc<dyn Mailer>,
+    user: &UserModel,
+    email: &str
+) -> Result<(), sqlx::Error> {
+    let verification_token = token::generate();
+    let expires_at = chrono::Utc::now() + chrono::Duration::hours(get_config().verification_ttl_hours);
+
+    let mut tx = db.begin().await?;
+
+    sqlx::query!(
+        "UPDATE email_verification_tokens SET used_at = NOW() WHERE user_id = $1 AND used_at IS NULL",
+        user.id
+    )
+    .execute(&mut *tx).await?;
+
+    sqlx::query!(
+        "INSERT INTO email_verification_tokens (user_id, email, token_hash, expires_at) VALUES ($1, $2, $3, $4)",
+        user.id,
+        email,
+        token::hash(&verification_token),
+        expires_at
+    )
+    .execute(&mut *tx).await?;
+
+    tx.commit().await?;
+
+    let message = Message {
+        to: email.to_string(),
+        subject: "Confirm your email address".to_string(),
+        body: format!(
+            "Hi {},\n\nPlease confirm {} by opening the link below. It expires in {} hours.\n\n{}",
+            user.name,
+            email,
+            get_config().verification_ttl_hours,
+            mailer::link("/verify-email", &verification_token)
+        ),
+    };
+    if let Err(err) = mailer.send(&message).await {
+        log::error!("Failed to send verification email: {}", err);
+    }
+
+    Ok(())
+}
+
+pub async fn is_verified(db: &Pool<Postgres>, user_id: uuid::Uuid) -> Result<bool, sqlx::Error> {
+    sqlx::query_scalar!(
+        r#"SELECT verified_at IS NOT NULL AS "verified!" FROM users WHERE id = $1"#,
+        user_id
+    )
+    .fetch_optional(db).await
+    .map(|verified| verified.unwrap_or(false))
+}
+
+#[post("/verify-email")]
+pub async fn verify_email_handler(
+    body: web::Json<VerifyEmailSchema>,
+    data: web::Data<AppState>
+) -> impl Responder {
+    let verify_result = async {
+        let mut tx = data.db.begin().await?;
+
+        let pending = sqlx
+            ::query!(
+                "UPDATE email_verification_tokens SET used_at = NOW()
+                WHERE token_hash = $1 AND used_at IS NULL AND expires_at > NOW()
+                RETURNING user_id, email",
+                token::hash(&body.token)
+            )
+            .fetch_optional(&mut *tx).await?;
+
+        let user = match pending {
+            Some(pending) => sqlx
+                ::query_as!(
+                    UserModel,
+                    "UPDATE users SET email = $1, verified_at = NOW(), updated_at = NOW()
+                    WHERE id = $2 AND deleted_at IS NULL RETURNING *",
+                    pending.email,
+                    pending.user_id
+                )
+                .fetch_optional(&mut *tx).await?,
+            None => None,
+        };
+
+        tx.commit().await?;
+        Ok::<_, sqlx::Error>(user)
+    }.await;
+
+    match verify_result {
+        Ok(Some(user)) => {
+            let user_response =
+                serde_json::json!({"status": "success","data": serde_json::json!({
+                "user": user
+            })});
+
+            HttpResponse::Ok().json(user_response)
+        }
+        Ok(None) => {
+            HttpResponse::BadRequest().json(
+                json!({"status": "fail", "message": "Verification token is invalid or has expired"})
+            )
+        }
+        Err(e) => {
+            if e.to_string().contains("duplicate key value violates unique constraint") {
+                return HttpResponse::Conflict().json(
+                    json!({"status": "fail","message": "User with that email already exists"})
+                );
+            }
+
+            HttpResponse::InternalServerError().json(
+                json!({"status": "error","message": format!("{:?}", e)})
+            )
+        }
+    }
+}
+
+#[post("/resend-verification")]
+pub async fn resend_verification_handler(
+    data: web::Data<AppState>,
+    jwt: jwt_auth::JwtMiddleware
+) -> impl Responder {
+    let config = get_config();
+
+    let query_result = sqlx
+        ::query_as!(UserModel, "SELECT * FROM users WHERE id = $1 AND deleted_at IS NULL", jwt.user_id)
+        .fetch_one(&data.db).await;
+
+    let user = match query_result {
+        Ok(user) => user,
+        Err(_) => {
+            let message = format!("User with ID: {} not found", jwt.user_id);
+            return HttpResponse::NotFound().json(json!({"status": "fail","message": message}));
+        }
+    };
+
+    // A pending address change takes precedence over the current address.
+    let pending_email = sqlx
+        ::query_scalar!(
+            "SELECT email FROM email_verification_tokens
+            WHERE user_id = $1 AND used_at IS NULL AND email <> $2
+            ORDER BY created_at DESC LIMIT 1",
+            user.id,
+            user.email
+        )
+        .fetch_optional(&data.db).await
+        .unwrap_or(None);
+
+    if user.verified_at.is_some() && pending_email.is_none() {
+        return HttpResponse::BadRequest().json(
+            json!({"status": "fail","message": "Email address is already verified"})
+        );
+    }
+
+    let window_start = chrono::Utc::now() - chrono::Duration::minutes(config.verification_resend_window_minutes);
+    let recent = sqlx
+        ::query_scalar!(
+            r#"SELECT COUNT(*) AS "count!" FROM email_verification_tokens WHERE user_id = $1 AND created_at > $2"#,
+            user.id,
+            window_start
+        )
+        .fetch_one(&data.db).await
+        .unwrap_or(0);
+
+    if recent >= config.verification_resend_limit {
+        return HttpResponse::TooManyRequests().json(
+            json!({"status": "fail","message": "Too many verification emails requested, try again later"})
+        );
+    }
+
+    let email = pending_email.unwrap_or_else(|| user.email.clone());
+    match send_verification_email(&data.db, &data.mailer, &user, &email).await {
+        Ok(_) => HttpResponse::Ok().json(json!({"status": "success","message": "Verification email sent"})),
+        Err(err) => {
+            let message = format!("Error: {:?}", err);
+            HttpResponse::InternalServerError().json(json!({"status": "error","message": message}))
+        }
+    }
+}